@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use crate::target::{Args, TargetInfo};
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, ArgAction};
+
+/// The parser for the arguments a target declares, which also provides its `--help`
+pub fn parser(target: &TargetInfo, args: &Args) -> clap::Command {
+    let mut command = clap::Command::new(target.name.name.clone())
+        .bin_name(format!("pls run {}", target.name.name))
+        .no_binary_name(true);
+    if let Some(ref description) = target.description {
+        command = command.about(description.clone());
+    }
+    for (name, argument) in args.declared.iter() {
+        let mut arg = Arg::new(name.clone()).required(argument.required.unwrap_or(false));
+        if argument.flag.unwrap_or(false) {
+            arg = arg.long(name.clone()).action(ArgAction::SetTrue);
+        } else {
+            arg = arg.value_name(name.to_uppercase());
+            if !argument.positional.unwrap_or(false) {
+                arg = arg.long(name.clone());
+            }
+        }
+        if let Some(short) = argument.short {
+            arg = arg.short(short);
+        }
+        if let Some(ref help) = argument.help {
+            arg = arg.help(help.clone());
+        }
+        if let Some(ref default) = argument.default {
+            arg = arg.default_value(default.clone());
+        }
+        if let Some(ref choices) = argument.choices {
+            arg = arg.value_parser(PossibleValuesParser::new(choices.clone()));
+        }
+        command = command.arg(arg);
+    }
+    command
+}
+
+/// Parse the fixed arguments of a target, followed by those it is run with, into the values
+/// of the arguments it declares. Arguments that aren't given and have no default are left
+/// out, and flags are `true` or `false`.
+pub fn parse(
+    target: &TargetInfo,
+    args: &Args,
+    given: &[String],
+) -> Result<HashMap<String, String>, clap::Error> {
+    let matches = parser(target, args).try_get_matches_from(args.fixed.iter().chain(given))?;
+    let mut values = HashMap::new();
+    for (name, argument) in args.declared.iter() {
+        if argument.flag.unwrap_or(false) {
+            values.insert(name.clone(), matches.get_flag(name).to_string());
+        } else if let Some(value) = matches.get_one::<String>(name) {
+            values.insert(name.clone(), value.clone());
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::*;
+    use crate::config::Argument;
+    use crate::target::any_target;
+
+    fn declared() -> Args {
+        Args {
+            fixed: vec![],
+            declared: IndexMap::from([
+                (
+                    "env".to_string(),
+                    Argument {
+                        choices: Some(vec!["dev".to_string(), "prod".to_string()]),
+                        required: Some(true),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "service".to_string(),
+                    Argument {
+                        positional: Some(true),
+                        default: Some("web".to_string()),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "dry-run".to_string(),
+                    Argument {
+                        flag: Some(true),
+                        short: Some('n'),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+        }
+    }
+
+    fn args_of(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let target = any_target();
+        let values = parse(
+            target.target_info(),
+            &declared(),
+            &args_of(&["--env", "prod", "api", "-n"]),
+        )
+        .unwrap();
+        assert_eq!(
+            values,
+            HashMap::from([
+                ("env".to_string(), "prod".to_string()),
+                ("service".to_string(), "api".to_string()),
+                ("dry-run".to_string(), "true".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let target = any_target();
+        let values = parse(
+            target.target_info(),
+            &declared(),
+            &args_of(&["--env", "dev"]),
+        )
+        .unwrap();
+        assert_eq!(values.get("service"), Some(&"web".to_string()));
+        assert_eq!(values.get("dry-run"), Some(&"false".to_string()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let target = any_target();
+        let error = |a: &[&str]| {
+            parse(target.target_info(), &declared(), &args_of(a))
+                .unwrap_err()
+                .kind()
+        };
+        assert_eq!(error(&[]), clap::error::ErrorKind::MissingRequiredArgument);
+        assert_eq!(
+            error(&["--env", "staging"]),
+            clap::error::ErrorKind::InvalidValue
+        );
+        assert_eq!(error(&["--help"]), clap::error::ErrorKind::DisplayHelp);
+    }
+
+    #[test]
+    fn test_parse_fixed_args() {
+        let target = any_target();
+        let args = Args {
+            fixed: vec!["--env".to_string(), "prod".to_string()],
+            ..declared()
+        };
+        let values = parse(target.target_info(), &args, &args_of(&["api"])).unwrap();
+        assert_eq!(values.get("env"), Some(&"prod".to_string()));
+        assert_eq!(values.get("service"), Some(&"api".to_string()));
+    }
+}
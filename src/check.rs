@@ -0,0 +1,629 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+use glob::glob;
+use schemars::schema::{InstanceType, Schema, SchemaObject, SingleOrVec};
+use schemars::Map;
+
+use crate::config::{schema, VariableValue};
+use crate::context::{Context, Variable};
+use crate::name::{FullyQualifiedName, PROJECT_SEPARATOR};
+use crate::target::Target;
+use crate::template::{Filter, Segment, Template};
+
+/// The keys in a config file that aren't part of the config, and so would be ignored, as
+/// dotted paths such as `command.exec.build.reqiures`. The overrides of profiles are
+/// checked against the config as well.
+pub fn unknown_keys(table: &toml::Table) -> Vec<String> {
+    let root = schema();
+    let root_schema = Schema::Object(root.schema);
+    let mut walker = SchemaWalker {
+        definitions: &root.definitions,
+        unknown: vec![],
+    };
+    let value = toml::Value::Table(table.clone());
+    walker.walk(&value, &root_schema, "");
+    for (name, overrides) in table
+        .get("profile")
+        .and_then(|p| p.as_table())
+        .into_iter()
+        .flatten()
+    {
+        walker.walk(overrides, &root_schema, &format!("profile.{}", name));
+    }
+    walker.unknown
+}
+
+/// Walks a config value alongside the schema of the config, collecting the keys of tables
+/// that the schema doesn't have
+struct SchemaWalker<'a> {
+    definitions: &'a Map<String, Schema>,
+    unknown: Vec<String>,
+}
+
+impl SchemaWalker<'_> {
+    /// Returns whether the value is of a type the schema allows, so that the best match
+    /// can be picked from alternatives
+    fn walk(&mut self, value: &toml::Value, schema: &Schema, path: &str) -> bool {
+        let Schema::Object(schema) = schema else {
+            return true;
+        };
+        if let Some(ref reference) = schema.reference {
+            let name = reference.trim_start_matches("#/definitions/");
+            return match self.definitions.get(name) {
+                Some(definition) => self.walk(value, definition, path),
+                None => true,
+            };
+        }
+        if !has_type(value, schema) {
+            return false;
+        }
+        if let Some(alternatives) = schema
+            .subschemas
+            .as_ref()
+            .and_then(|s| s.any_of.as_ref().or(s.one_of.as_ref()))
+        {
+            let mut best: Option<Vec<String>> = None;
+            for alternative in alternatives {
+                let mut walker = SchemaWalker {
+                    definitions: self.definitions,
+                    unknown: vec![],
+                };
+                if walker.walk(value, alternative, path)
+                    && best.as_ref().is_none_or(|b| walker.unknown.len() < b.len())
+                {
+                    best = Some(walker.unknown);
+                }
+            }
+            match best {
+                Some(unknown) => self.unknown.extend(unknown),
+                None => return false,
+            }
+        }
+        match value {
+            toml::Value::Table(table) => {
+                let Some(ref object) = schema.object else {
+                    return true;
+                };
+                for (key, value) in table {
+                    let key_path = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    match object
+                        .properties
+                        .get(key)
+                        .or(object.additional_properties.as_deref())
+                    {
+                        None | Some(Schema::Bool(false)) => self.unknown.push(key_path),
+                        Some(schema) => {
+                            self.walk(value, schema, &key_path);
+                        }
+                    }
+                }
+            }
+            toml::Value::Array(items) => {
+                if let Some(SingleOrVec::Single(ref schema)) =
+                    schema.array.as_ref().and_then(|a| a.items.as_ref())
+                {
+                    for (i, item) in items.iter().enumerate() {
+                        self.walk(item, schema, &format!("{}[{}]", path, i));
+                    }
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+fn has_type(value: &toml::Value, schema: &SchemaObject) -> bool {
+    let expected = match value {
+        toml::Value::String(_) | toml::Value::Datetime(_) => InstanceType::String,
+        toml::Value::Integer(_) => InstanceType::Integer,
+        toml::Value::Float(_) => InstanceType::Number,
+        toml::Value::Boolean(_) => InstanceType::Boolean,
+        toml::Value::Array(_) => InstanceType::Array,
+        toml::Value::Table(_) => InstanceType::Object,
+    };
+    match schema.instance_type {
+        None => true,
+        Some(SingleOrVec::Single(ref t)) => **t == expected,
+        Some(SingleOrVec::Vec(ref types)) => types.contains(&expected),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the config
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
+/// Keys of the target definitions that aren't expanded, so aren't checked for variables
+const UNEXPANDED_KEYS: [&str; 8] = [
+    "name",
+    "description",
+    "tags",
+    "requires",
+    "extends",
+    "args",
+    "arguments",
+    "variables",
+];
+
+/// Where a string with variables in it is from, which decides what they refer to
+enum Scope<'a> {
+    Target(&'a FullyQualifiedName),
+    /// The globals of a project
+    Globals(&'a str),
+}
+
+impl fmt::Display for Scope<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scope::Target(name) => write!(f, "{}", name),
+            Scope::Globals("") => write!(f, "globals"),
+            Scope::Globals(project) => write!(f, "{}{}globals", project, PROJECT_SEPARATOR),
+        }
+    }
+}
+
+/// Check the config for mistakes that would otherwise only show up when a target is run,
+/// or not at all. Errors are things that can't work, while warnings are things that are
+/// probably not what was meant.
+pub fn check(context: &Context) -> Vec<Finding> {
+    let mut checker = Checker {
+        context,
+        used: HashSet::new(),
+        findings: BTreeSet::new(),
+    };
+    for key in context.unknown_keys.iter() {
+        checker.error(key.clone());
+    }
+    checker.check_variables();
+    checker.check_unused_variables();
+    checker.check_reachable();
+    checker.check_globs();
+    checker.findings.into_iter().collect()
+}
+
+/// The errors that `check` would find
+pub fn errors(context: &Context) -> Vec<Finding> {
+    check(context)
+        .into_iter()
+        .filter(|f| f.severity == Severity::Error)
+        .collect()
+}
+
+struct Checker<'a> {
+    context: &'a Context,
+    /// The full names of the variables that are referred to
+    used: HashSet<String>,
+    findings: BTreeSet<Finding>,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, message: String) {
+        self.findings.insert(Finding {
+            severity: Severity::Error,
+            message,
+        });
+    }
+
+    fn warning(&mut self, message: String) {
+        self.findings.insert(Finding {
+            severity: Severity::Warning,
+            message,
+        });
+    }
+
+    fn check_variables(&mut self) {
+        let context = self.context;
+        for (name, target) in sorted_targets(self.context) {
+            let Ok(toml::Value::Table(mut definition)) = toml::Value::try_from(target) else {
+                continue;
+            };
+            for key in UNEXPANDED_KEYS {
+                definition.remove(key);
+            }
+            let mut strings = vec![];
+            collect_strings(&toml::Value::Table(definition), &mut strings);
+            for value in context
+                .variables
+                .get(name)
+                .into_iter()
+                .flat_map(|v| v.values())
+            {
+                strings.push(variable_template(value).to_string());
+            }
+            for s in strings {
+                self.check_template(&s, &Scope::Target(name));
+            }
+        }
+        let mut globals = vec![("", &context.globals)];
+        globals.extend(
+            context
+                .project_globals
+                .iter()
+                .map(|(project, globals)| (project.as_str(), globals)),
+        );
+        globals.sort_by_key(|(project, _)| *project);
+        for (project, globals) in globals {
+            for value in globals.values() {
+                self.check_template(variable_template(value), &Scope::Globals(project));
+            }
+        }
+    }
+
+    fn check_template(&mut self, input: &str, scope: &Scope) {
+        let template = match Template::parse(input) {
+            Ok(template) => template,
+            Err(e) => {
+                self.error(format!("Invalid <{}> in <{}>: {}", input, scope, e));
+                return;
+            }
+        };
+        for segment in template.segments.iter() {
+            let Segment::Substitution(substitution) = segment else {
+                continue;
+            };
+            let has_default = substitution
+                .filters
+                .iter()
+                .any(|f| matches!(f, Filter::Default(_)));
+            match Variable::from_string(&substitution.variable) {
+                Ok(variable) => self.check_variable(variable, scope, has_default),
+                Err(e) => self.error(format!(
+                    "Invalid variable <{}> in <{}>: {}",
+                    substitution.variable, scope, e
+                )),
+            }
+        }
+    }
+
+    /// Record the use of a variable, and report it if it can never be resolved
+    fn check_variable(&mut self, variable: Variable, scope: &Scope, has_default: bool) {
+        let context = self.context;
+        let undefined = match variable {
+            Variable::Simple(key) if key == "args" => None,
+            Variable::Simple(key) => match scope {
+                Scope::Target(name) => {
+                    self.used.insert(format!("{}.{}", name, key));
+                    (!self.defines(name, |t| t.target_info().variables.contains_key(&key)))
+                        .then(|| format!("Variable <{}> used in <{}> is not defined", key, scope))
+                }
+                Scope::Globals(_) => Some(format!(
+                    "Variable <{}> used in <{}> can't refer to a target's variables",
+                    key, scope
+                )),
+            },
+            Variable::Global(key) => {
+                let project = match scope {
+                    Scope::Target(name) => name.project.as_str(),
+                    Scope::Globals(project) => project,
+                };
+                let in_project = context
+                    .project_globals
+                    .get(project)
+                    .is_some_and(|g| g.contains_key(&key));
+                if in_project {
+                    self.used
+                        .insert(format!("{}{}globals.{}", project, PROJECT_SEPARATOR, key));
+                } else {
+                    self.used.insert(format!("globals.{}", key));
+                }
+                (!in_project && !context.globals.contains_key(&key))
+                    .then(|| format!("Global <{}> used in <{}> is not defined", key, scope))
+            }
+            Variable::Env(_) => None,
+            Variable::Pls(key) => match (key.as_str(), scope) {
+                ("root" | "config_path" | "project", _) | ("target", Scope::Target(_)) => None,
+                _ => Some(format!(
+                    "Unknown variable <pls.{}> used in <{}>",
+                    key, scope
+                )),
+            },
+            Variable::Git(key) => {
+                (!matches!(key.as_str(), "sha" | "short_sha" | "branch" | "dirty"))
+                    .then(|| format!("Unknown variable <git.{}> used in <{}>", key, scope))
+            }
+            Variable::Arg(key) => match scope {
+                Scope::Target(name) => (!self.defines(name, |t| {
+                    t.command_info()
+                        .is_some_and(|c| c.args.declared.contains_key(&key))
+                }))
+                .then(|| format!("Argument <{}> used in <{}> is not declared", key, scope)),
+                Scope::Globals(_) => None,
+            },
+            Variable::Ref(target_name, key) => {
+                let target = FullyQualifiedName::from_string(&target_name);
+                self.used.insert(format!("{}.{}", target, key));
+                let defined = context
+                    .variables
+                    .get(&target)
+                    .is_some_and(|v| v.contains_key(&key));
+                (!defined).then(|| {
+                    format!(
+                        "Variable <{}> of <{}> used in <{}> is not defined",
+                        key, target_name, scope
+                    )
+                })
+            }
+            Variable::Output(target_name, _) => {
+                let producer = FullyQualifiedName::from_string(&target_name);
+                if !context.targets.contains_key(&producer) {
+                    Some(format!(
+                        "Outputs of <{}> used in <{}>, which doesn't exist",
+                        target_name, scope
+                    ))
+                } else {
+                    if let Scope::Target(name) = scope {
+                        if **name != producer && !self.requires(name, &producer) {
+                            self.warning(format!(
+                                "<{}> uses outputs of <{}> without requiring it, so they won't be set",
+                                name, producer
+                            ));
+                        }
+                    }
+                    None
+                }
+            }
+        };
+        if let Some(message) = undefined {
+            if !has_default {
+                self.error(message);
+            }
+        }
+    }
+
+    /// Whether `predicate` holds for the target or any target that extends it, directly or
+    /// indirectly. Targets that are extended are often only a base for others that
+    /// define what they leave out.
+    fn defines(&self, name: &FullyQualifiedName, predicate: impl Fn(&Target) -> bool) -> bool {
+        self.context.targets.iter().any(|(other, target)| {
+            (other == name || self.extends(other, name)) && predicate(target)
+        })
+    }
+
+    fn extends(&self, name: &FullyQualifiedName, base: &FullyQualifiedName) -> bool {
+        let mut seen = HashSet::new();
+        let mut current = self
+            .context
+            .targets
+            .get(name)
+            .and_then(|t| t.target_info().extends.as_ref());
+        while let Some(parent) = current {
+            if parent == base {
+                return true;
+            }
+            if !seen.insert(parent) {
+                return false;
+            }
+            current = self
+                .context
+                .targets
+                .get(parent)
+                .and_then(|t| t.target_info().extends.as_ref());
+        }
+        false
+    }
+
+    /// Whether the target requires `required`, directly or indirectly
+    fn requires(&self, name: &FullyQualifiedName, required: &FullyQualifiedName) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending = vec![name];
+        while let Some(current) = pending.pop() {
+            if !seen.insert(current) {
+                continue;
+            }
+            if let Some(target) = self.context.targets.get(current) {
+                for r in target.target_info().requires.iter() {
+                    if r == required {
+                        return true;
+                    }
+                    pending.push(r);
+                }
+            }
+        }
+        false
+    }
+
+    fn check_unused_variables(&mut self) {
+        let context = self.context;
+        for (name, _) in sorted_targets(self.context) {
+            let Some(variables) = context
+                .definitions
+                .get(name)
+                .and_then(|d| d.get("variables"))
+                .and_then(|v| v.as_table())
+            else {
+                continue;
+            };
+            for key in variables.keys() {
+                let used = context.targets.keys().any(|other| {
+                    (other == name || self.extends(other, name))
+                        && self.used.contains(&format!("{}.{}", other, key))
+                });
+                if !used {
+                    self.warning(format!("Variable <{}> of <{}> is never used", key, name));
+                }
+            }
+        }
+        let mut unused = vec![];
+        for key in context.globals.keys() {
+            if !self.used.contains(&format!("globals.{}", key)) {
+                unused.push(format!("globals.{}", key));
+            }
+        }
+        for (project, globals) in context.project_globals.iter() {
+            for key in globals.keys() {
+                let id = format!("{}{}globals.{}", project, PROJECT_SEPARATOR, key);
+                if !self.used.contains(&id) {
+                    unused.push(id);
+                }
+            }
+        }
+        for id in unused {
+            self.warning(format!("Global <{}> is never used", id));
+        }
+    }
+
+    /// Warn about targets that nothing tagged leads to, through `requires`, `extends` or
+    /// the members of a pod. Configs that don't use tags at all are left alone.
+    fn check_reachable(&mut self) {
+        let context = self.context;
+        let mut pending = context
+            .targets
+            .iter()
+            .filter(|(_, t)| !t.target_info().tags.is_empty())
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        if pending.is_empty() {
+            return;
+        }
+        let mut reachable = HashSet::new();
+        while let Some(name) = pending.pop() {
+            if !reachable.insert(name) {
+                continue;
+            }
+            let Some(target) = context.targets.get(name) else {
+                continue;
+            };
+            let target_info = target.target_info();
+            pending.extend(target_info.requires.iter());
+            pending.extend(target_info.extends.iter());
+            if let Ok(pod) = target.command().and_then(|c| c.pod()) {
+                pending.extend(pod.members.iter());
+            }
+        }
+        for (name, _) in sorted_targets(self.context) {
+            if !reachable.contains(name) {
+                self.warning(format!("<{}> isn't reachable from any tagged target", name));
+            }
+        }
+    }
+
+    fn check_globs(&mut self) {
+        for (name, target) in sorted_targets(self.context) {
+            let Ok(artifact) = target.artifact() else {
+                continue;
+            };
+            for pattern in artifact.artifact_info().if_files_changed.iter().flatten() {
+                // Patterns with variables in them can only be checked once they are expanded
+                if pattern.contains('{') {
+                    continue;
+                }
+                let matched = glob(pattern).is_ok_and(|mut paths| paths.next().is_some());
+                if !matched {
+                    self.warning(format!(
+                        "if_files_changed <{}> of <{}> matches no files",
+                        pattern, name
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn sorted_targets(context: &Context) -> Vec<(&FullyQualifiedName, &Target)> {
+    let mut targets = context.targets.iter().collect::<Vec<_>>();
+    targets.sort_by_key(|(name, _)| *name);
+    targets
+}
+
+fn variable_template(value: &VariableValue) -> &str {
+    match value {
+        VariableValue::Value(value) => value,
+        VariableValue::Command { command } => command,
+    }
+}
+
+fn collect_strings(value: &toml::Value, strings: &mut Vec<String>) {
+    match value {
+        toml::Value::String(s) => strings.push(s.clone()),
+        toml::Value::Array(items) => items.iter().for_each(|i| collect_strings(i, strings)),
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                strings.push(key.clone());
+                collect_strings(value, strings);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unknown(config: &str) -> Vec<String> {
+        unknown_keys(&toml::from_str(config).unwrap())
+    }
+
+    #[test]
+    fn no_unknown_keys_in_valid_config() {
+        let config = r#"
+            include = ["other.toml"]
+            env_file = ["a.env", { path = "b.env", required = false }]
+            [globals]
+            version = { command = "git describe" }
+            [command.exec.build]
+            command = "cargo build {args}"
+            requires = ["lint"]
+            arguments = { release = { flag = true } }
+            [artifact.container_image.image]
+            tag = "image"
+            if_files_changed = ["src"]
+            [profile.ci.command.exec.build]
+            command = "cargo build --locked"
+        "#;
+        assert_eq!(unknown(config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn finds_unknown_keys() {
+        let config = r#"
+            golbals = {}
+            env_file = [{ path = "b.env", requried = false }]
+            [command.exec.build]
+            command = "cargo build"
+            reqiures = ["lint"]
+            arguments = { release = { flga = true } }
+            [artifact.exec.bin]
+            command = "make"
+            if_file_changed = ["src"]
+            [profile.ci.command.exec.build]
+            comand = "cargo build --locked"
+        "#;
+        let mut found = unknown(config);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                "artifact.exec.bin.if_file_changed",
+                "command.exec.build.arguments.release.flga",
+                "command.exec.build.reqiures",
+                "env_file[0].requried",
+                "golbals",
+                "profile.ci.command.exec.build.comand",
+            ]
+        );
+    }
+}
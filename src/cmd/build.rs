@@ -5,6 +5,7 @@ use clap::Parser;
 
 use crate::cleanup::CleanupManager;
 use crate::cmd::execute::Execute;
+use crate::cmd::tagged::run_each;
 use crate::context::{CommandLookupResult, Context};
 use crate::outputs::OutputsManager;
 use crate::target::Targetable;
@@ -12,13 +13,40 @@ use crate::target::Targetable;
 #[derive(Parser, Debug)]
 pub struct BuildCommand {
     /// The name of the artifact to build
-    pub artifact: String,
+    #[arg(required_unless_present = "tag")]
+    pub artifact: Option<String>,
+
+    /// Build every artifact with this tag, in dependency order
+    #[arg(long, conflicts_with = "artifact")]
+    pub tag: Option<String>,
 }
 
 impl Execute for BuildCommand {
     fn execute(&self, context: Context, cleanup_manager: Arc<Mutex<CleanupManager>>) -> Result<()> {
         let mut outputs = OutputsManager::default();
-        match context.get_target(self.artifact.as_str()) {
+        if let Some(tag) = self.tag.as_ref() {
+            let targets = context
+                .targets_with_tag(tag)
+                .into_iter()
+                .filter(|t| t.as_buildable().is_some())
+                .collect();
+            return run_each(
+                &context,
+                tag,
+                targets,
+                &mut outputs,
+                cleanup_manager.clone(),
+                |target, outputs| {
+                    target.as_buildable().unwrap().build_no_deps(
+                        &context,
+                        outputs,
+                        cleanup_manager.clone(),
+                    )
+                },
+            );
+        }
+        let artifact = self.artifact.as_ref().unwrap();
+        match context.get_target(artifact) {
             CommandLookupResult::Found(target) => {
                 let builder = target.as_buildable();
                 if let Some(builder) = builder {
@@ -26,20 +54,20 @@ impl Execute for BuildCommand {
                 } else {
                     Err(anyhow!(
                         "Target <{}> is not buildable, use the run command instead",
-                        self.artifact
+                        artifact
                     ))
                 }
             }
             CommandLookupResult::NotFound => Err(anyhow!(
                 "Target <{}> not found in config file <{}>",
-                self.artifact,
+                artifact,
                 context.config_path
             )),
             CommandLookupResult::Duplicates(ref mut duplicates) => {
                 duplicates.sort();
                 Err(anyhow!(
                     "Target <{}> is ambiguous, possible values are <{}>, please specify the command to run using one of those names",
-                    self.artifact, duplicates.join(", ")
+                    artifact, duplicates.join(", ")
                 ))
             }
         }
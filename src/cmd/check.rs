@@ -0,0 +1,44 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+
+use crate::check::{check, Severity};
+use crate::cleanup::CleanupManager;
+use crate::cmd::execute::Execute;
+use crate::context::Context;
+
+#[derive(Parser, Debug)]
+pub struct CheckCommand {
+    /// Fail if there are any warnings, as well as if there are errors
+    #[arg(long)]
+    pub deny_warnings: bool,
+}
+
+impl Execute for CheckCommand {
+    fn execute(
+        &self,
+        context: Context,
+        _cleanup_manager: Arc<Mutex<CleanupManager>>,
+    ) -> Result<()> {
+        let findings = check(&context);
+        for finding in findings.iter() {
+            println!("{}", finding);
+        }
+        let errors = findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+            .count();
+        let warnings = findings.len() - errors;
+        if errors > 0 || (self.deny_warnings && warnings > 0) {
+            return Err(anyhow!(
+                "Found {} errors and {} warnings in config file <{}>",
+                errors,
+                warnings,
+                context.config_path
+            ));
+        }
+        println!("Found {} errors and {} warnings", errors, warnings);
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use clap::{CommandFactory, Parser, ValueEnum};
+use clap_complete::Shell;
+
+use crate::cleanup::CleanupManager;
+use crate::cmd::execute::Execute;
+use crate::cmd::Args;
+use crate::context::Context;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Parser, Debug)]
+pub struct CompletionsCommand {
+    /// The shell to print the completion script for
+    #[arg(value_enum)]
+    pub shell: CompletionShell,
+}
+
+/// Completes the names of targets for the commands that take one by listing them, and
+/// everything else with the completions that clap generates. Only daemons can be
+/// started and stopped, and only artifacts built.
+const BASH_TARGETS: &str = r#"
+_pls_with_targets() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" command="" word
+    for word in "${COMP_WORDS[@]:1:COMP_CWORD-1}"; do
+        if [[ -z "${command}" ]]; then
+            case "${word}" in
+                run|start|stop|build|status|watch) command="${word}" ;;
+            esac
+        elif [[ "${word}" != -* ]]; then
+            # The target has already been given
+            command=""
+            break
+        fi
+    done
+    if [[ -n "${command}" && "${cur}" != -* ]]; then
+        local filter=""
+        case "${command}" in
+            start|stop|status) filter="--daemons" ;;
+            build) filter="--artifacts" ;;
+        esac
+        COMPREPLY=($(compgen -W "$(pls list ${filter} --format names 2>/dev/null)" -- "${cur}"))
+    else
+        _pls "$@"
+    fi
+}
+
+complete -F _pls_with_targets -o nosort -o bashdefault -o default pls
+"#;
+
+const ZSH_TARGETS: &str = r#"
+_pls_with_targets() {
+    local command="" word
+    for word in "${(@)words[2,CURRENT-1]}"; do
+        if [[ -z "${command}" ]]; then
+            case "${word}" in
+                run|start|stop|build|status|watch) command="${word}" ;;
+            esac
+        elif [[ "${word}" != -* ]]; then
+            # The target has already been given
+            command=""
+            break
+        fi
+    done
+    if [[ -n "${command}" && "${PREFIX}" != -* ]]; then
+        local -a filter targets
+        case "${command}" in
+            start|stop|status) filter=(--daemons) ;;
+            build) filter=(--artifacts) ;;
+        esac
+        targets=(${(f)"$(pls list "${filter[@]}" --format names 2>/dev/null)"})
+        compadd -a targets
+    else
+        _pls "$@"
+    fi
+}
+
+compdef _pls_with_targets pls
+"#;
+
+const FISH_TARGETS: &str = r#"
+complete -c pls -n "__fish_seen_subcommand_from run watch" -f -a "(pls list --format names 2>/dev/null)"
+complete -c pls -n "__fish_seen_subcommand_from start stop status" -f -a "(pls list --daemons --format names 2>/dev/null)"
+complete -c pls -n "__fish_seen_subcommand_from build" -f -a "(pls list --artifacts --format names 2>/dev/null)"
+"#;
+
+impl CompletionsCommand {
+    pub fn print(&self) -> Result<()> {
+        let (shell, targets) = match self.shell {
+            CompletionShell::Bash => (Shell::Bash, BASH_TARGETS),
+            CompletionShell::Zsh => (Shell::Zsh, ZSH_TARGETS),
+            CompletionShell::Fish => (Shell::Fish, FISH_TARGETS),
+        };
+        clap_complete::generate(shell, &mut Args::command(), "pls", &mut std::io::stdout());
+        print!("{}", targets);
+        Ok(())
+    }
+}
+
+impl Execute for CompletionsCommand {
+    fn execute(
+        &self,
+        _context: Context,
+        _cleanup_manager: Arc<Mutex<CleanupManager>>,
+    ) -> Result<()> {
+        self.print()
+    }
+}
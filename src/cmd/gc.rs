@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use log::{debug, info};
+
+use crate::cleanup::CleanupManager;
+use crate::cmd::execute::Execute;
+use crate::containers::{
+    container_status, list_project_resources, remove_resource, ContainerState, Resource,
+};
+use crate::context::Context;
+use crate::target::{metadata_path, Command, Target};
+use crate::targets::command::container::named_volumes;
+
+#[derive(Parser, Debug)]
+pub struct GcCommand {
+    /// List what would be removed without removing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// The containers, networks and volumes that are still in use by the project.
+#[derive(Debug, Default)]
+struct InUse {
+    containers: HashSet<String>,
+    networks: HashSet<String>,
+    volumes: HashSet<String>,
+}
+
+impl InUse {
+    fn find(context: &Context) -> Result<Self> {
+        let mut in_use = InUse::default();
+        for (name, target) in context.targets.iter() {
+            let metadata_dir = metadata_path(name.to_string().as_str())?;
+            match target {
+                Target::Command(Command::Container(container)) => {
+                    // Named volumes hold data that should outlive the container, so
+                    // keep them while any target still mounts them.
+                    in_use.volumes.extend(named_volumes(&container.mount));
+                    if let Some(state) = ContainerState::load(&metadata_dir)? {
+                        // Anything could be removed if this isn't known, so give up instead
+                        let status = container_status(state.name.as_str()).map_err(|e| {
+                            anyhow!("Not removing anything as <{}> may be in use: {}", name, e)
+                        })?;
+                        if status.is_some() {
+                            debug!("Container <{}> of <{}> is in use", state.name, name);
+                            in_use.containers.insert(state.name);
+                            in_use.networks.extend(state.network);
+                        }
+                    }
+                }
+                Target::Command(Command::Pod(_)) => {
+                    if let Ok(network) = std::fs::read_to_string(metadata_dir.join("network")) {
+                        debug!("Network <{}> of <{}> is in use", network.trim(), name);
+                        in_use.networks.insert(network.trim().to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(in_use)
+    }
+
+    fn contains(&self, kind: Resource, name: &str) -> bool {
+        match kind {
+            Resource::Container => self.containers.contains(name),
+            Resource::Network => self.networks.contains(name),
+            Resource::Volume => self.volumes.contains(name),
+        }
+    }
+
+    /// The resources of the project that are not in use and can be removed
+    fn unused(&self, kind: Resource, names: Vec<String>) -> Vec<String> {
+        names
+            .into_iter()
+            .filter(|name| !self.contains(kind, name.as_str()))
+            .collect()
+    }
+}
+
+impl Execute for GcCommand {
+    fn execute(
+        &self,
+        context: Context,
+        _cleanup_manager: Arc<Mutex<CleanupManager>>,
+    ) -> Result<()> {
+        let in_use = InUse::find(&context)?;
+        let mut errors = vec![];
+        // Containers go first as networks and volumes can't be removed while a
+        // container is using them.
+        for kind in [Resource::Container, Resource::Network, Resource::Volume] {
+            for name in in_use.unused(kind, list_project_resources(kind, &context)?) {
+                if self.dry_run {
+                    println!("Would remove {} {}", kind, name);
+                    continue;
+                }
+                info!("Removing {} {}", kind, name);
+                match remove_resource(kind, name.as_str()) {
+                    Ok(()) => println!("Removed {} {}", kind, name),
+                    Err(e) => errors.push(format!("{} <{}>: {}", kind, name, e)),
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!("Error removing {}", errors.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unused_filters_by_kind() {
+        let in_use = InUse {
+            containers: HashSet::from(["db-abc".to_string()]),
+            networks: HashSet::from(["stack-def".to_string()]),
+            volumes: HashSet::from(["pgdata".to_string()]),
+        };
+        let names = vec![
+            "db-abc".to_string(),
+            "stack-def".to_string(),
+            "pgdata".to_string(),
+            "old".to_string(),
+        ];
+        assert_eq!(
+            in_use.unused(Resource::Container, names.clone()),
+            vec!["stack-def", "pgdata", "old"]
+        );
+        assert_eq!(
+            in_use.unused(Resource::Network, names.clone()),
+            vec!["db-abc", "pgdata", "old"]
+        );
+        assert_eq!(
+            in_use.unused(Resource::Volume, names),
+            vec!["db-abc", "stack-def", "old"]
+        );
+    }
+}
@@ -1,14 +1,109 @@
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use clap::{Parser, ValueEnum};
+use glob::Pattern;
+use serde::Serialize;
 
 use crate::cleanup::CleanupManager;
 use crate::cmd::execute::Execute;
 use crate::context::Context;
+use crate::name::FullyQualifiedName;
+use crate::target::Target;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListFormat {
+    #[default]
+    Text,
+    Json,
+    Table,
+    /// Only the names, as short as they can be given to other commands, such as for
+    /// completing them in a shell
+    Names,
+}
 
 #[derive(Parser, Debug)]
-pub struct ListCommand {}
+pub struct ListCommand {
+    /// Only list targets whose name matches this glob
+    pub pattern: Option<String>,
+
+    /// Only list targets with this tag
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Only list artifacts
+    #[arg(long)]
+    pub artifacts: bool,
+
+    /// Only list daemons
+    #[arg(long)]
+    pub daemons: bool,
+
+    /// The format to list the targets in
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+
+    /// Show the targets as a tree of their requirements
+    #[arg(long, conflicts_with = "format")]
+    pub tree: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct ListEntry {
+    name: String,
+    #[serde(rename = "type")]
+    type_tag: String,
+    description: Option<String>,
+    daemon: bool,
+    artifact: bool,
+    requires: Vec<String>,
+    extends: Option<String>,
+    tags: Vec<String>,
+}
+
+impl ListEntry {
+    fn from_target(target: &Target) -> Self {
+        let target_info = target.target_info();
+        ListEntry {
+            name: target_info.name.to_string(),
+            type_tag: target_info.name.tag.clone(),
+            description: target_info.description.clone(),
+            daemon: is_daemon(target),
+            artifact: target.artifact().is_ok(),
+            requires: target_info.requires.iter().map(|r| r.to_string()).collect(),
+            extends: target_info.extends.as_ref().map(|e| e.to_string()),
+            tags: target_info.tags.clone(),
+        }
+    }
+}
+
+fn is_daemon(target: &Target) -> bool {
+    target.command_info().map(|c| c.daemon).unwrap_or(false)
+}
+
+impl ListCommand {
+    fn matches(&self, target: &Target, pattern: Option<&Pattern>) -> bool {
+        let name = &target.target_info().name;
+        if let Some(pattern) = pattern {
+            if !pattern.matches(name.name.as_str()) && !pattern.matches(name.to_string().as_str()) {
+                return false;
+            }
+        }
+        if let Some(tag) = self.tag.as_ref() {
+            if !target.target_info().tags.contains(tag) {
+                return false;
+            }
+        }
+        if self.artifacts && target.artifact().is_err() {
+            return false;
+        }
+        if self.daemons && !is_daemon(target) {
+            return false;
+        }
+        true
+    }
+}
 
 impl Execute for ListCommand {
     fn execute(
@@ -16,15 +111,163 @@ impl Execute for ListCommand {
         context: Context,
         _cleanup_manager: Arc<Mutex<CleanupManager>>,
     ) -> Result<()> {
-        let mut targets = context.targets.iter().collect::<Vec<_>>();
+        let pattern = self
+            .pattern
+            .as_ref()
+            .map(|p| Pattern::new(p).map_err(|e| anyhow!("Invalid pattern <{}>: {}", p, e)))
+            .transpose()?;
+        let mut targets = context
+            .targets
+            .iter()
+            .filter(|(_, target)| self.matches(target, pattern.as_ref()))
+            .collect::<Vec<_>>();
         targets.sort_by(|a, b| a.0.cmp(b.0));
-        for (name, target) in targets {
-            println!(
-                "{} - {}",
-                name,
-                target.target_info().description.clone().unwrap_or_default()
-            );
+        if self.tree {
+            print!("{}", format_tree(&context, &targets));
+            return Ok(());
+        }
+        let entries = targets
+            .iter()
+            .map(|(_, target)| ListEntry::from_target(target))
+            .collect::<Vec<_>>();
+        match self.format {
+            ListFormat::Text => {
+                for entry in entries {
+                    println!("{} - {}", entry.name, entry.description.unwrap_or_default());
+                }
+            }
+            ListFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+            ListFormat::Table => print!("{}", format_table(&entries)),
+            ListFormat::Names => {
+                for (name, _) in targets {
+                    println!("{}", context.shortest_name(name));
+                }
+            }
         }
         Ok(())
     }
 }
+
+fn format_table(entries: &[ListEntry]) -> String {
+    let header = [
+        "NAME",
+        "TYPE",
+        "DAEMON",
+        "ARTIFACT",
+        "REQUIRES",
+        "EXTENDS",
+        "DESCRIPTION",
+    ];
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            [
+                entry.name.clone(),
+                entry.type_tag.clone(),
+                yes_no(entry.daemon),
+                yes_no(entry.artifact),
+                entry.requires.join(","),
+                entry.extends.clone().unwrap_or_default(),
+                entry.description.clone().unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let mut widths = header.map(|h| h.len());
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut output = String::new();
+    let header = header.map(|h| h.to_string());
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+/// Show each target with the targets it requires nested underneath it. Only targets
+/// that aren't required by another listed target are shown at the top level.
+fn format_tree(context: &Context, targets: &[(&FullyQualifiedName, &Target)]) -> String {
+    let required = targets
+        .iter()
+        .flat_map(|(_, target)| target.target_info().requires.iter())
+        .collect::<HashSet<_>>();
+    let mut output = String::new();
+    for (name, _) in targets.iter().filter(|(name, _)| !required.contains(name)) {
+        format_tree_node(context, name, 0, &mut vec![], &mut output);
+    }
+    output
+}
+
+fn format_tree_node(
+    context: &Context,
+    name: &FullyQualifiedName,
+    depth: usize,
+    path: &mut Vec<FullyQualifiedName>,
+    output: &mut String,
+) {
+    let description = context
+        .targets
+        .get(name)
+        .and_then(|t| t.target_info().description.clone())
+        .unwrap_or_default();
+    output.push_str(format!("{}{} - {}\n", "  ".repeat(depth), name, description).as_str());
+    if path.contains(name) {
+        return;
+    }
+    path.push(name.clone());
+    if let Some(target) = context.targets.get(name) {
+        for required in target.target_info().requires.iter() {
+            format_tree_node(context, required, depth + 1, path, output);
+        }
+    }
+    path.pop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, requires: Vec<&str>, description: Option<&str>) -> ListEntry {
+        ListEntry {
+            name: name.to_string(),
+            type_tag: "command.exec".to_string(),
+            description: description.map(|d| d.to_string()),
+            daemon: false,
+            artifact: false,
+            requires: requires.iter().map(|r| r.to_string()).collect(),
+            extends: None,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn test_format_table() {
+        let entries = vec![
+            entry("command.exec.a", vec![], Some("The first")),
+            entry(
+                "command.exec.bb",
+                vec!["command.exec.a", "command.exec.c"],
+                None,
+            ),
+        ];
+        assert_eq!(
+            format_table(&entries),
+            "NAME             TYPE          DAEMON  ARTIFACT  REQUIRES                       EXTENDS  DESCRIPTION\n\
+             command.exec.a   command.exec  no      no                                                The first\n\
+             command.exec.bb  command.exec  no      no        command.exec.a,command.exec.c\n"
+        );
+    }
+}
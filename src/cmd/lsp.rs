@@ -0,0 +1,28 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::cleanup::CleanupManager;
+use crate::cmd::execute::Execute;
+use crate::context::Context;
+use crate::lsp;
+
+#[derive(Parser, Debug)]
+pub struct LspCommand {}
+
+impl LspCommand {
+    pub fn serve(&self) -> Result<()> {
+        lsp::serve()
+    }
+}
+
+impl Execute for LspCommand {
+    fn execute(
+        &self,
+        _context: Context,
+        _cleanup_manager: Arc<Mutex<CleanupManager>>,
+    ) -> Result<()> {
+        self.serve()
+    }
+}
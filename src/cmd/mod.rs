@@ -4,20 +4,34 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 
 mod build;
+mod check;
+mod completions;
 mod execute;
+mod gc;
 mod list;
+mod lsp;
 mod run;
+mod schema;
+mod show;
 mod start;
 mod status;
 mod stop;
+mod tagged;
 mod watch;
 
 use crate::cleanup::CleanupManager;
 use crate::context::Context;
 use build::BuildCommand;
+use check::CheckCommand;
+pub use completions::CompletionsCommand;
 pub use execute::Execute;
+use gc::GcCommand;
 use list::ListCommand;
+pub use lsp::LspCommand;
 use run::RunCommand;
+pub use schema::SchemaCommand;
+pub use show::show_target;
+use show::ShowCommand;
 use start::StartCommand;
 use status::StatusCommand;
 use stop::StopCommand;
@@ -40,6 +54,23 @@ pub struct Args {
 
     #[arg(short = 'C', long)]
     pub directory: Option<String>,
+
+    /// Apply the overrides of this profile from the config
+    #[arg(long, env = "PLS_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Override a variable, e.g. `--set globals.registry=localhost:5000` or
+    /// `--set build.profile=release`
+    #[arg(long, value_name = "NAME=VALUE")]
+    pub set: Vec<String>,
+
+    /// Set an environment variable for every target, over any other value it has
+    #[arg(long, value_name = "NAME=VALUE")]
+    pub env: Vec<String>,
+
+    /// Fail on any error that `pls check` would report, such as unknown keys in the config
+    #[arg(long, env = "PLS_STRICT")]
+    pub strict: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -59,11 +90,29 @@ pub enum Commands {
     /// List available targets
     List(ListCommand),
 
+    /// Show the definition of a target after all extends are applied
+    Show(ShowCommand),
+
     /// Get the status of a daemon
     Status(StatusCommand),
 
     /// Watch for changes and trigger targets in response
     Watch(WatchCommand),
+
+    /// Remove containers, networks and volumes left behind by pls
+    Gc(GcCommand),
+
+    /// Check the config for mistakes, such as unknown keys and undefined variables
+    Check(CheckCommand),
+
+    /// Print the JSON Schema of the config file, for editors to complete and validate it with
+    Schema(SchemaCommand),
+
+    /// Run a language server for config files over stdin and stdout, for editors to use
+    Lsp(LspCommand),
+
+    /// Print a script for the shell that completes commands and the names of targets
+    Completions(CompletionsCommand),
     // TODO: logs
 }
 
@@ -75,8 +124,14 @@ impl Execute for Commands {
             Commands::Stop(cmd) => cmd.execute(context, cleanup_manager),
             Commands::Build(cmd) => cmd.execute(context, cleanup_manager),
             Commands::List(cmd) => cmd.execute(context, cleanup_manager),
+            Commands::Show(cmd) => cmd.execute(context, cleanup_manager),
             Commands::Status(cmd) => cmd.execute(context, cleanup_manager),
             Commands::Watch(cmd) => cmd.execute(context, cleanup_manager),
+            Commands::Gc(cmd) => cmd.execute(context, cleanup_manager),
+            Commands::Check(cmd) => cmd.execute(context, cleanup_manager),
+            Commands::Schema(cmd) => cmd.execute(context, cleanup_manager),
+            Commands::Lsp(cmd) => cmd.execute(context, cleanup_manager),
+            Commands::Completions(cmd) => cmd.execute(context, cleanup_manager),
         }
     }
 }
@@ -1,45 +1,110 @@
 use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, Result};
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
 
+use crate::arguments;
 use crate::cleanup::CleanupManager;
 use crate::cmd::execute::Execute;
+use crate::cmd::tagged::run_each;
+use crate::cmd::Args;
 use crate::context::{CommandLookupResult, Context};
 use crate::outputs::OutputsManager;
 use crate::target::Targetable;
 
 #[derive(Parser, Debug)]
+#[command(disable_help_flag = true)]
 pub struct RunCommand {
     /// The name of the command to run
-    pub name: String,
+    #[arg(required_unless_present_any = ["tag", "help"])]
+    pub name: Option<String>,
 
-    /// The arguments to pass to the command
+    /// The arguments to pass to the command, see `pls run <name> --help` for those that
+    /// it declares
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     pub args: Vec<String>,
+
+    /// Run every command with this tag, in dependency order
+    #[arg(long, conflicts_with_all = ["name", "args"])]
+    pub tag: Option<String>,
+
+    /// Print help, including the arguments that the command declares if a name is given
+    #[arg(short, long)]
+    pub help: bool,
 }
 
 impl Execute for RunCommand {
     fn execute(&self, context: Context, cleanup_manager: Arc<Mutex<CleanupManager>>) -> Result<()> {
         let mut outputs = OutputsManager::default();
-        match context.get_target(self.name.as_str()) {
+        if let Some(tag) = self.tag.as_ref() {
+            let targets = context
+                .targets_with_tag(tag)
+                .into_iter()
+                .filter(|t| t.as_runnable().is_some())
+                .collect();
+            return run_each(
+                &context,
+                tag,
+                targets,
+                &mut outputs,
+                cleanup_manager.clone(),
+                |target, outputs| {
+                    target.as_runnable().unwrap().run_no_deps(
+                        &context,
+                        outputs,
+                        cleanup_manager.clone(),
+                        vec![],
+                    )
+                },
+            );
+        }
+        let Some(name) = self.name.as_ref() else {
+            let mut command = Args::command();
+            command.build();
+            command.find_subcommand_mut("run").unwrap().print_help()?;
+            return Ok(());
+        };
+        match context.get_target(name) {
             CommandLookupResult::Found(target) => {
+                if let Some(args) = target.command_info().map(|c| &c.args) {
+                    if self.help {
+                        print!(
+                            "{}",
+                            arguments::parser(target.target_info(), args).render_help()
+                        );
+                        return Ok(());
+                    }
+                    if !args.declared.is_empty() {
+                        match arguments::parse(target.target_info(), args, &self.args) {
+                            Err(e) if e.kind() == ErrorKind::DisplayHelp => {
+                                print!("{}", e.render());
+                                return Ok(());
+                            }
+                            Err(e) => {
+                                return Err(anyhow!("Invalid arguments for <{}>: {}", name, e))
+                            }
+                            Ok(_) => (),
+                        }
+                    }
+                }
                 let runner = target.as_runnable();
                 if let Some(runner) = runner {
                     runner.run(&context, &mut outputs, cleanup_manager, self.args.clone())
                 } else {
-                    Err(anyhow!("Target <{}> is not runnable", self.name))
+                    Err(anyhow!("Target <{}> is not runnable", name))
                 }
             }
             CommandLookupResult::NotFound => Err(anyhow!(
                 "Target <{}> not found in config file <{}>",
-                self.name,
+                name,
                 context.config_path
             )),
             CommandLookupResult::Duplicates(ref mut duplicates) => {
                 duplicates.sort();
                 Err(anyhow!(
                     "Target <{}> is ambiguous, possible values are <{}>, please specify the command to run using one of those names",
-                    self.name, duplicates.join(", ")
+                    name, duplicates.join(", ")
                 ))
             }
         }
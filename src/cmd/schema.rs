@@ -0,0 +1,29 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::cleanup::CleanupManager;
+use crate::cmd::execute::Execute;
+use crate::config::schema;
+use crate::context::Context;
+
+#[derive(Parser, Debug)]
+pub struct SchemaCommand {}
+
+impl SchemaCommand {
+    pub fn print(&self) -> Result<()> {
+        println!("{}", serde_json::to_string_pretty(&schema())?);
+        Ok(())
+    }
+}
+
+impl Execute for SchemaCommand {
+    fn execute(
+        &self,
+        _context: Context,
+        _cleanup_manager: Arc<Mutex<CleanupManager>>,
+    ) -> Result<()> {
+        self.print()
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+
+use crate::cleanup::CleanupManager;
+use crate::cmd::execute::Execute;
+use crate::config::VariableValue;
+use crate::context::{CommandLookupResult, Context};
+use crate::name::FullyQualifiedName;
+use crate::outputs::OutputsManager;
+use crate::target::{Command, Target};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShowFormat {
+    #[default]
+    Toml,
+    Json,
+}
+
+#[derive(Parser, Debug)]
+pub struct ShowCommand {
+    /// The name of the target to show
+    pub name: String,
+
+    /// The format to show the target in
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ShowFormat,
+
+    /// Arguments to substitute into the command line that would be run
+    #[arg(last = true)]
+    pub args: Vec<String>,
+}
+
+/// What a target looks like once all of the `extends` layers have been applied
+#[derive(Serialize, Debug)]
+struct ShowOutput {
+    name: String,
+    /// The targets this one extends, nearest first
+    extends: Vec<String>,
+    /// Everything the target requires, directly or indirectly, in the order they
+    /// would be run
+    requires: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command_line: Option<String>,
+    variables: BTreeMap<String, ShowVariable>,
+    definition: toml::Table,
+    /// Which layers set each field of the definition, nearest first
+    sources: BTreeMap<String, Vec<String>>,
+}
+
+/// A variable of the target, expanded if that is possible without running the target or the
+/// commands of variables
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum ShowVariable {
+    Expanded(String),
+    Unexpanded { value: VariableValue, error: String },
+}
+
+const DEFAULT_SOURCE: &str = "default";
+
+impl ShowOutput {
+    fn new(context: &Context, target: &Target, args: Vec<String>) -> Result<Self> {
+        let target_info = target.target_info();
+        let layers = extends_layers(context, target);
+        let mut definition = toml::Table::try_from(target)?;
+        definition.remove("name");
+        definition.remove("extends");
+        let mut sources = BTreeMap::new();
+        for key in definition.keys() {
+            let mut set_by = layers
+                .iter()
+                .filter(|layer| {
+                    context
+                        .definitions
+                        .get(layer)
+                        .map(|d| d.contains_key(key))
+                        .unwrap_or(false)
+                })
+                .map(|layer| layer.to_string())
+                .collect::<Vec<_>>();
+            if set_by.is_empty() {
+                set_by.push(DEFAULT_SOURCE.to_string());
+            }
+            sources.insert(key.clone(), set_by);
+        }
+        definition.remove("variables");
+        let command_line = if args.is_empty() {
+            None
+        } else {
+            Some(command_line(context, target, args)?)
+        };
+        Ok(ShowOutput {
+            name: target_info.name.to_string(),
+            extends: layers.iter().skip(1).map(|l| l.to_string()).collect(),
+            requires: transitive_requires(context, &target_info.name)
+                .iter()
+                .map(|r| r.to_string())
+                .collect(),
+            command_line,
+            variables: show_variables(context, target),
+            definition,
+            sources,
+        })
+    }
+}
+
+fn show_variables(context: &Context, target: &Target) -> BTreeMap<String, ShowVariable> {
+    let target_info = target.target_info();
+    let outputs = OutputsManager::default();
+    target_info
+        .variables
+        .iter()
+        .map(|(key, value)| {
+            let shown = match context.expand_variable(&target_info.name, key, &outputs) {
+                Ok(Some(expanded)) => ShowVariable::Expanded(expanded),
+                Ok(None) => ShowVariable::Unexpanded {
+                    value: value.clone(),
+                    error: format!("Variable <{}> is not defined", key),
+                },
+                Err(e) => ShowVariable::Unexpanded {
+                    value: value.clone(),
+                    error: e.to_string(),
+                },
+            };
+            (key.clone(), shown)
+        })
+        .collect()
+}
+
+/// A target as `pls show` prints it by default
+pub fn show_target(context: &Context, target: &Target) -> Result<String> {
+    Ok(toml::to_string(&ShowOutput::new(context, target, vec![])?)?)
+}
+
+/// The target followed by each target it extends, nearest first
+fn extends_layers(context: &Context, target: &Target) -> Vec<FullyQualifiedName> {
+    let mut layers = vec![target.target_info().name.clone()];
+    let mut next = target.target_info().extends.clone();
+    while let Some(name) = next {
+        if layers.contains(&name) {
+            break;
+        }
+        next = context
+            .targets
+            .get(&name)
+            .and_then(|t| t.target_info().extends.clone());
+        layers.push(name);
+    }
+    layers
+}
+
+fn transitive_requires(context: &Context, name: &FullyQualifiedName) -> Vec<FullyQualifiedName> {
+    fn visit(
+        context: &Context,
+        name: &FullyQualifiedName,
+        visited: &mut HashSet<FullyQualifiedName>,
+        ordered: &mut Vec<FullyQualifiedName>,
+    ) {
+        if let Some(target) = context.targets.get(name) {
+            for required in target.target_info().requires.iter() {
+                if visited.insert(required.clone()) {
+                    visit(context, required, visited, ordered);
+                    ordered.push(required.clone());
+                }
+            }
+        }
+    }
+    let mut visited = HashSet::from([name.clone()]);
+    let mut ordered = vec![];
+    visit(context, name, &mut visited, &mut ordered);
+    ordered
+}
+
+fn command_line(context: &Context, target: &Target, args: Vec<String>) -> Result<String> {
+    let outputs = OutputsManager::default();
+    match target {
+        Target::Command(Command::Exec(exec)) => exec.resolve_command(context, &outputs, args),
+        Target::Command(Command::Container(container)) => {
+            container.resolve_command(context, &outputs, args)
+        }
+        _ => Err(anyhow!(
+            "Target <{}> does not accept arguments",
+            target.target_info().name
+        )),
+    }
+}
+
+impl Execute for ShowCommand {
+    fn execute(
+        &self,
+        context: Context,
+        _cleanup_manager: Arc<Mutex<CleanupManager>>,
+    ) -> Result<()> {
+        match context.get_target(self.name.as_str()) {
+            CommandLookupResult::Found(target) => {
+                let output = ShowOutput::new(&context, target, self.args.clone())?;
+                match self.format {
+                    ShowFormat::Toml => print!("{}", toml::to_string(&output)?),
+                    ShowFormat::Json => println!("{}", serde_json::to_string_pretty(&output)?),
+                }
+                Ok(())
+            }
+            CommandLookupResult::NotFound => Err(anyhow!(
+                "Target <{}> not found in config file <{}>",
+                self.name,
+                context.config_path
+            )),
+            CommandLookupResult::Duplicates(mut duplicates) => {
+                duplicates.sort();
+                Err(anyhow!(
+                    "Target <{}> is ambiguous, possible values are <{}>, please specify the command to run using one of those names",
+                    self.name, duplicates.join(", ")
+                ))
+            }
+        }
+    }
+}
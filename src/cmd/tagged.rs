@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use log::info;
+
+use crate::cleanup::CleanupManager;
+use crate::context::Context;
+use crate::name::FullyQualifiedName;
+use crate::outputs::OutputsManager;
+use crate::target::{run_requirement, Target};
+
+enum Outcome {
+    Succeeded,
+    Failed(anyhow::Error),
+    Skipped(FullyQualifiedName),
+}
+
+/// Run `action` on each target in `targets` in order, carrying on past failures so
+/// that a summary of everything can be printed at the end. What each target requires is
+/// run first, with every target run at most once however many require it, so `action`
+/// shouldn't run the requirements itself. Targets that require one that failed are
+/// skipped.
+pub fn run_each<'a>(
+    context: &'a Context,
+    tag: &str,
+    targets: Vec<&'a Target>,
+    outputs: &mut OutputsManager,
+    cleanup_manager: Arc<Mutex<CleanupManager>>,
+    mut action: impl FnMut(&'a Target, &mut OutputsManager) -> Result<()>,
+) -> Result<()> {
+    if targets.is_empty() {
+        return Err(anyhow!(
+            "No targets with tag <{}> found in config file <{}>",
+            tag,
+            context.config_path
+        ));
+    }
+    let mut done = HashSet::new();
+    let mut failed = HashSet::new();
+    let mut outcomes = vec![];
+    for target in targets.iter() {
+        let name = &target.target_info().name;
+        if let Some(failed_requirement) = find_failed_requirement(context, target, &failed) {
+            info!("[{}] Skipping as <{}> failed", name, failed_requirement);
+            outcomes.push((name, Outcome::Skipped(failed_requirement)));
+            continue;
+        }
+        let mut result = Ok(());
+        for required in requirements_in_order(context, target) {
+            let required_name = &required.target_info().name;
+            if !done.insert(required_name.clone()) {
+                continue;
+            }
+            result = run_requirement(required, context, outputs, cleanup_manager.clone(), false);
+            if result.is_err() {
+                failed.insert(required_name.clone());
+                break;
+            }
+        }
+        done.insert(name.clone());
+        match result.and_then(|()| action(target, outputs)) {
+            Ok(()) => outcomes.push((name, Outcome::Succeeded)),
+            Err(e) => {
+                failed.insert(name.clone());
+                outcomes.push((name, Outcome::Failed(e)));
+            }
+        }
+    }
+    println!("Summary for tag <{}>:", tag);
+    for (name, outcome) in outcomes.iter() {
+        match outcome {
+            Outcome::Succeeded => println!("  {}: ok", name),
+            Outcome::Failed(e) => println!("  {}: failed: {}", name, e),
+            Outcome::Skipped(requirement) => {
+                println!("  {}: skipped as <{}> failed", name, requirement)
+            }
+        }
+    }
+    let not_ok = outcomes
+        .iter()
+        .filter(|(_, outcome)| !matches!(outcome, Outcome::Succeeded))
+        .count();
+    if not_ok > 0 {
+        return Err(anyhow!(
+            "{} of {} targets with tag <{}> did not succeed",
+            not_ok,
+            outcomes.len(),
+            tag
+        ));
+    }
+    Ok(())
+}
+
+/// Everything that a target requires, directly or through the targets it requires, with
+/// each after those it requires
+fn requirements_in_order<'a>(context: &'a Context, target: &Target) -> Vec<&'a Target> {
+    fn visit<'a>(
+        context: &'a Context,
+        target: &Target,
+        visited: &mut HashSet<FullyQualifiedName>,
+        ordered: &mut Vec<&'a Target>,
+    ) {
+        for name in target.target_info().requires.iter() {
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+            if let Some(required) = context.targets.get(name) {
+                visit(context, required, visited, ordered);
+                ordered.push(required);
+            }
+        }
+    }
+    let mut ordered = vec![];
+    visit(context, target, &mut HashSet::new(), &mut ordered);
+    ordered
+}
+
+fn find_failed_requirement(
+    context: &Context,
+    target: &Target,
+    failed: &HashSet<FullyQualifiedName>,
+) -> Option<FullyQualifiedName> {
+    let mut to_visit = target.target_info().requires.clone();
+    let mut visited = HashSet::new();
+    while let Some(name) = to_visit.pop() {
+        if failed.contains(&name) {
+            return Some(name);
+        }
+        if !visited.insert(name.clone()) {
+            continue;
+        }
+        if let Some(required) = context.targets.get(&name) {
+            to_visit.extend(required.target_info().requires.iter().cloned());
+        }
+    }
+    None
+}
@@ -6,11 +6,23 @@ use anyhow::{anyhow, Result};
 use log::debug;
 use nix::errno::Errno;
 
+/// Environment variables to run a command with, as `KEY=VALUE`, which are added to the
+/// environment pls is run in unless `clear` is set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    pub vars: Vec<String>,
+    pub clear: bool,
+}
+
 pub fn build_command(command: &str) -> Result<std::process::Command> {
-    build_command_with_env(command, &[])
+    build_command_with_env(command, &Environment::default(), None)
 }
 
-pub fn build_command_with_env(command: &str, env: &[String]) -> Result<std::process::Command> {
+pub fn build_command_with_env(
+    command: &str,
+    env: &Environment,
+    cwd: Option<&str>,
+) -> Result<std::process::Command> {
     let mut split = shlex::Shlex::new(command);
     debug!(
         "Split command <{}> into parts: <{}>",
@@ -20,7 +32,15 @@ pub fn build_command_with_env(command: &str, env: &[String]) -> Result<std::proc
     split = shlex::Shlex::new(command);
     if let Some(cmd) = split.next() {
         let mut cmd = std::process::Command::new(cmd);
-        for env_v in env {
+        if let Some(cwd) = cwd {
+            debug!("Running command <{}> in <{}>", command, cwd);
+            cmd.current_dir(cwd);
+        }
+        if env.clear {
+            debug!("Running command <{}> with a cleared environment", command);
+            cmd.env_clear();
+        }
+        for env_v in env.vars.iter() {
             let split = env_v.split_once('=');
             if let Some((key, val)) = split {
                 cmd.env(key, val);
@@ -93,11 +113,11 @@ pub fn stop_process(pid: nix::unistd::Pid) -> Result<()> {
 }
 
 pub fn run_command(cmd: &str) -> Result<()> {
-    run_command_with_env(cmd, &[])
+    run_command_with_env(cmd, &Environment::default(), None)
 }
 
-pub fn run_command_with_env(cmd: &str, env: &[String]) -> Result<()> {
-    let mut cmd = build_command_with_env(cmd, env)?;
+pub fn run_command_with_env(cmd: &str, env: &Environment, cwd: Option<&str>) -> Result<()> {
+    let mut cmd = build_command_with_env(cmd, env, cwd)?;
     let status = cmd.status()?;
     if !status.success() {
         if let Some(code) = status.code() {
@@ -109,6 +129,24 @@ pub fn run_command_with_env(cmd: &str, env: &[String]) -> Result<()> {
     Ok(())
 }
 
+pub fn run_command_with_output(cmd: &str) -> Result<String> {
+    let mut cmd = build_command(cmd)?;
+    let output = cmd.output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let Some(code) = output.status.code() {
+            return Err(anyhow!(
+                "Command failed with exit code: {}: {}",
+                code,
+                stderr.trim()
+            ));
+        } else {
+            return Err(anyhow!("Command terminated by a signal: {}", stderr.trim()));
+        }
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
 /*
 pub fn run_command_with_cleanup(cmd: &str, cleanup_manager: Arc<Mutex<CleanupManager>>) -> Result<(), Box<dyn std::error::Error>> {
     let mut cmd = build_command(cmd)?;
@@ -133,7 +171,8 @@ pub fn run_command_with_cleanup(cmd: &str, cleanup_manager: Arc<Mutex<CleanupMan
 
 pub fn spawn_command_with_pidfile(
     cmd: &str,
-    env: &[String],
+    env: &Environment,
+    cwd: Option<&str>,
     pid_path: &std::path::PathBuf,
     log_path: &std::path::PathBuf,
     on_start: impl Fn(),
@@ -157,8 +196,7 @@ pub fn spawn_command_with_pidfile(
 
     debug!("Starting daemon with command <{}>", cmd);
     on_start();
-    // TODO: cwd
-    let mut cmd = build_command_with_env(cmd, env)?;
+    let mut cmd = build_command_with_env(cmd, env, cwd)?;
     let child = cmd
         .stdout(log.try_clone()?)
         .stderr(log.try_clone()?)
@@ -273,6 +311,16 @@ mod tests {
         assert_eq!(cmd.get_args().collect::<Vec<_>>(), &["hello"]);
     }
 
+    #[test]
+    fn test_run_command_with_output() {
+        assert_eq!(run_command_with_output("echo hello").unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_run_command_with_output_fails() {
+        assert!(run_command_with_output("false").is_err());
+    }
+
     #[test]
     fn test_is_process_alive() {
         let pid = nix::unistd::Pid::from_raw(std::process::id() as i32);
@@ -300,6 +348,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::zombie_processes)]
     fn test_stop_process() {
         let start = std::time::Instant::now();
         let child = build_command("sleep 4").unwrap().spawn().unwrap();
@@ -1,46 +1,106 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use glob::{glob, MatchOptions, Pattern};
+use indexmap::IndexMap;
 use log::debug;
-use serde::Deserialize;
-use validator::Validate;
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationErrors};
 
+use crate::check;
 use crate::context::{
     resolve_target_names_in, resolve_target_names_in_map, resolve_target_names_in_vec,
 };
+use crate::diagnostic::{validation_errors, Diagnostic, Diagnostics};
 use crate::name::FullyQualifiedName;
 
-#[derive(Deserialize, Clone, Default, Debug, Validate)]
+/// The config of pls, read from `pls.toml` and the files it includes
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default, Debug, Validate)]
 pub struct Config {
-    #[validate(custom(function = "crate::validate::keys_and_values_non_empty_strings"))]
-    pub globals: Option<HashMap<String, String>>,
+    /// Other config files to load as part of this one, as paths or globs relative to it
+    #[validate(custom(function = "crate::validate::non_empty_strings"))]
+    pub include: Option<Vec<String>>,
+    /// The directories of the projects in a monorepo that this is the root config file of,
+    /// as paths or globs relative to it, each with a `pls.toml` of its own
+    #[validate(custom(function = "crate::validate::non_empty_strings"))]
+    pub projects: Option<Vec<String>>,
+
+    /// Variables that every target can use as `{globals.<name>}`
+    #[validate(custom(function = "crate::validate::variables"))]
+    pub globals: Option<HashMap<String, VariableValue>>,
+    /// Environment variables set for every target, before those of the target itself
+    #[validate(custom(function = "crate::validate::keys_non_empty_strings"))]
+    pub env: Option<HashMap<String, String>>,
+    /// Env files loaded for every target, before those of the target itself
+    #[validate(custom(function = "crate::validate::env_files"))]
+    pub env_file: Option<Vec<EnvFile>>,
 
+    /// Targets that run a command
     #[validate(nested)]
     pub command: Option<Command>,
+    /// Targets that build something, and are only run again when it is out of date
     #[validate(nested)]
     pub artifact: Option<Artifact>,
+
+    /// Overrides for any part of the config, which are applied when the profile is selected
+    #[serde(skip_serializing)]
+    #[schemars(with = "Option<HashMap<String, serde_json::Map<String, serde_json::Value>>>")]
+    pub profile: Option<HashMap<String, toml::Table>>,
+
+    /// Keys in the config files that don't match any setting, and so are ignored
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub unknown_keys: Vec<String>,
+
+    /// The file each global, env var and target is defined in, by its key, such as
+    /// `command.exec.build`
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub origins: HashMap<String, PathBuf>,
+
+    /// Errors in the config files that don't stop it from being loaded, which are reported
+    /// along with those found resolving its targets
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub errors: Vec<Diagnostic>,
+
+    /// The text of the config files it was loaded from, which errors are located in
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub sources: Sources,
 }
 
-#[derive(Deserialize, Clone, Debug, Validate)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
 pub struct Artifact {
     //pub command: Option<HashMap<String, CommandArtifact>>,
+    /// Container images built with `podman build`
     #[validate(nested)]
     pub container_image: Option<HashMap<String, ContainerBuild>>,
 
+    /// Artifacts built by running a command
     #[validate(nested)]
     pub exec: Option<HashMap<String, ExecArtifact>>,
 }
 
-#[derive(Deserialize, Clone, Debug, Validate)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
 pub struct TargetInfo {
+    /// Targets that are run or built before this one
     #[validate(custom(function = "crate::validate::non_empty_strings"))]
     pub requires: Option<Vec<String>>,
+    /// A target of the same type whose settings this one starts from
     #[validate(length(min = 1, message = "Name must not be empty"))]
     pub extends: Option<String>,
+    /// Variables that this target can use as `{<name>}`, and others as `{<target>.<name>}`
     #[validate(custom(function = "crate::validate::keys_non_empty_strings"))]
-    pub variables: Option<HashMap<String, String>>,
+    pub variables: Option<HashMap<String, VariableValue>>,
+    /// What the target is for, which is shown by `pls list`
     pub description: Option<String>,
+    /// Groups that the target is in, for `--tag`
+    #[validate(custom(function = "crate::validate::non_empty_strings"))]
+    pub tags: Option<Vec<String>>,
 }
 
 impl TargetInfo {
@@ -52,15 +112,102 @@ impl TargetInfo {
         new.variables = self
             .variables
             .as_ref()
-            .map(|i| resolve_target_names_in_map(i, name_map))
+            .map(|i| {
+                i.iter()
+                    .map(|(k, v)| {
+                        Ok((
+                            resolve_target_names_in(k, name_map)?,
+                            v.with_resolved_targets(name_map)?,
+                        ))
+                    })
+                    .collect::<Result<_>>()
+            })
             .transpose()?;
         Ok(new)
     }
 }
 
-#[derive(Deserialize, Clone, Debug, Validate)]
+/// The value of a variable, either given directly or computed from the output of a
+/// command that is run the first time the variable is used
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum VariableValue {
+    /// The value itself, which can use other variables
+    Value(String),
+    Command {
+        /// The command line whose output is the value, which can use other variables
+        command: String,
+    },
+}
+
+impl VariableValue {
+    pub fn with_resolved_targets(
+        &self,
+        name_map: &HashMap<String, Vec<FullyQualifiedName>>,
+    ) -> Result<Self> {
+        Ok(match self {
+            Self::Value(value) => Self::Value(resolve_target_names_in(value, name_map)?),
+            Self::Command { command } => Self::Command {
+                command: resolve_target_names_in(command, name_map)?,
+            },
+        })
+    }
+}
+
+impl From<&str> for VariableValue {
+    fn from(value: &str) -> Self {
+        Self::Value(value.to_string())
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
 pub struct CommandInfo {
+    /// Whether the command keeps running in the background, with `pls start` and `pls stop`
     pub daemon: Option<bool>,
+    /// Arguments that are always passed to the command, before those it is run with
+    #[validate(custom(function = "crate::validate::fixed_args"))]
+    pub args: Option<Args>,
+    /// The arguments the command takes, in the order that positional ones are given
+    #[validate(custom(function = "crate::validate::arguments"))]
+    pub arguments: Option<IndexMap<String, Argument>>,
+}
+
+/// The `args` of a command, which are always passed to it, as a string or a list
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Args {
+    Line(String),
+    Words(Vec<String>),
+}
+
+impl Args {
+    /// The arguments that are always passed to the command
+    pub fn fixed(&self) -> Option<Vec<String>> {
+        match self {
+            Self::Line(line) => shlex::split(line),
+            Self::Words(words) => Some(words.clone()),
+        }
+    }
+}
+
+/// An argument that a command declares, which is available as `{args.<name>}`
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Argument {
+    /// What the argument is for, which is shown by `pls run <target> --help`
+    pub help: Option<String>,
+    /// The value the argument has when it isn't given
+    pub default: Option<String>,
+    /// Whether the argument must be given
+    pub required: Option<bool>,
+    /// The only values the argument can have
+    pub choices: Option<Vec<String>>,
+    /// Whether the argument is a flag that is either given or not, rather than taking a value
+    pub flag: Option<bool>,
+    /// Whether the argument is given by its position rather than as `--<name> <value>`
+    pub positional: Option<bool>,
+    /// A single letter that the argument can be given with, as `-<short>`
+    pub short: Option<char>,
 }
 
 impl CommandInfo {
@@ -73,10 +220,50 @@ impl CommandInfo {
     }
 }
 
-#[derive(Deserialize, Clone, Debug, Validate)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
+pub struct EnvInfo {
+    /// Environment variables to set, as `NAME=value`
+    #[validate(custom(function = "crate::validate::non_empty_strings"))]
+    pub env: Option<Vec<String>>,
+    /// Env files to load environment variables from
+    #[validate(custom(function = "crate::validate::env_files"))]
+    pub env_file: Option<Vec<EnvFile>>,
+    /// Whether to start from an empty environment rather than the one pls is run in
+    pub clear_env: Option<bool>,
+    /// Globs of the environment variables that are passed through from the environment pls
+    /// is run in
+    #[validate(custom(function = "crate::validate::non_empty_strings"))]
+    pub pass_env: Option<Vec<String>>,
+}
+
+impl EnvInfo {
+    pub fn with_resolved_targets(
+        &self,
+        name_map: &HashMap<String, Vec<FullyQualifiedName>>,
+    ) -> Result<Self> {
+        let mut new = self.clone();
+        new.env = self
+            .env
+            .as_ref()
+            .map(|e| resolve_target_names_in_vec(e, name_map))
+            .transpose()?;
+        Ok(new)
+    }
+
+    fn relative_to(&mut self, dir: &Path) {
+        for env_file in self.env_file.iter_mut().flatten() {
+            env_file.relative_to(dir);
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
 pub struct ArtifactInfo {
+    /// The paths that building the artifact updates, which are compared with
+    /// `if_files_changed` to decide whether it is out of date
     #[validate(custom(function = "crate::validate::non_empty_strings"))]
     pub updates_paths: Option<Vec<String>>,
+    /// Globs of the files that the artifact is built again after they change
     #[validate(custom(function = "crate::validate::non_empty_strings"))]
     pub if_files_changed: Option<Vec<String>>,
 }
@@ -101,21 +288,37 @@ impl ArtifactInfo {
     }
 }
 
-#[derive(Deserialize, Clone, Debug, Validate)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
 pub struct Command {
+    /// Commands run directly on the machine
     #[validate(nested)]
     pub exec: Option<HashMap<String, ExecCommand>>,
+    /// Commands run in a container with `podman`
     #[validate(nested)]
     pub container: Option<HashMap<String, ContainerCommand>>,
+    /// Groups of containers that are started together in a network
+    #[validate(nested)]
+    pub pod: Option<HashMap<String, Pod>>,
 }
 
-#[derive(Deserialize, Clone, Debug, Validate)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
 pub struct ExecCommand {
+    /// The command line to run, which can use variables
     #[validate(length(min = 1, message = "Command must not be empty"))]
     pub command: Option<String>,
+    /// Arguments to pass when none are given on the command line
     pub default_args: Option<String>,
-    #[validate(custom(function = "crate::validate::non_empty_strings"))]
-    pub env: Option<Vec<String>>,
+    /// The directory to run the command in
+    #[validate(length(min = 1, message = "cwd must not be empty"))]
+    pub cwd: Option<String>,
+    /// The directory of the file the target was included from, which it runs in if
+    /// it doesn't set a `cwd`
+    #[serde(skip)]
+    pub default_cwd: Option<String>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub env_info: EnvInfo,
 
     #[serde(flatten)]
     #[validate(nested)]
@@ -154,32 +357,43 @@ impl ExecCommand {
             .as_ref()
             .map(|e| resolve_target_names_in(e, name_map))
             .transpose()?;
-        new.env = self
-            .env
+        new.env_info = self.env_info.with_resolved_targets(name_map)?;
+        new.cwd = self
+            .cwd
             .as_ref()
-            .map(|e| resolve_target_names_in_vec(e, name_map))
+            .map(|c| resolve_target_names_in(c, name_map))
             .transpose()?;
         Ok(new)
     }
 }
 
-#[derive(Deserialize, Clone, Debug, Validate)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
 pub struct ContainerCommand {
+    /// The image to run
     #[validate(length(min = 1, message = "image must not be empty"))]
     pub image: Option<String>,
-    #[validate(custom(function = "crate::validate::non_empty_strings"))]
-    pub env: Option<Vec<String>>,
+    /// The command to run in the container, rather than that of the image
     #[validate(length(min = 1, message = "command must not be empty"))]
     pub command: Option<String>,
+    /// Paths or named volumes to mount, each with where it is mounted in the container
     #[validate(custom(function = "crate::validate::keys_and_values_non_empty_strings"))]
     pub mount: Option<HashMap<String, String>>,
+    /// The directory to run the command in, inside the container
     #[validate(length(min = 1, message = "workdir must not be empty"))]
     pub workdir: Option<String>,
+    /// The network to run the container in
     #[validate(length(min = 1, message = "network must not be empty"))]
     pub network: Option<String>,
+    /// Whether to create a network for the container, which is given as its `network`
+    /// output
     pub create_network: Option<bool>,
+    /// Arguments to pass when none are given on the command line
     pub default_args: Option<String>,
 
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub env_info: EnvInfo,
+
     #[serde(flatten)]
     #[validate(nested)]
     pub target_info: TargetInfo,
@@ -212,11 +426,7 @@ impl ContainerCommand {
             .as_ref()
             .map(|i| resolve_target_names_in(i, name_map))
             .transpose()?;
-        new.env = self
-            .env
-            .as_ref()
-            .map(|e| resolve_target_names_in_vec(e, name_map))
-            .transpose()?;
+        new.env_info = self.env_info.with_resolved_targets(name_map)?;
         new.command = self
             .command
             .as_ref()
@@ -247,12 +457,139 @@ impl ContainerCommand {
     }
 }
 
-#[derive(Deserialize, Clone, Debug, Validate)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
+pub struct Pod {
+    /// The container commands to start in the pod
+    #[validate(
+        length(min = 1, message = "members must not be empty"),
+        custom(function = "crate::validate::non_empty_strings")
+    )]
+    pub members: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub target_info: TargetInfo,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub command_info: CommandInfo,
+}
+
+impl Pod {
+    pub fn tag() -> &'static str {
+        "command.pod"
+    }
+
+    pub fn type_tag(&self) -> &'static str {
+        Self::tag()
+    }
+
+    pub fn is_artifact(&self) -> bool {
+        false
+    }
+
+    pub fn with_resolved_targets(
+        &self,
+        _name_map: &HashMap<String, Vec<FullyQualifiedName>>,
+    ) -> Result<Self> {
+        let new = self.clone();
+        Ok(new)
+    }
+}
+
+/// A dotenv file to load environment variables from, either just its path, which must
+/// exist, or a table that can make it optional
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum EnvFile {
+    Path(String),
+    Options {
+        /// The path of the file
+        path: String,
+        /// Whether it is an error for the file not to exist
+        #[serde(default = "default_required")]
+        required: bool,
+    },
+}
+
+fn default_required() -> bool {
+    true
+}
+
+impl EnvFile {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Path(path) => path,
+            Self::Options { path, .. } => path,
+        }
+    }
+
+    pub fn required(&self) -> bool {
+        match self {
+            Self::Path(_) => true,
+            Self::Options { required, .. } => *required,
+        }
+    }
+
+    fn relative_to(&mut self, dir: &Path) {
+        match self {
+            Self::Path(path) => *path = rebase_path(path, dir),
+            Self::Options { path, .. } => *path = rebase_path(path, dir),
+        }
+    }
+}
+
+/// When to pull the base images of a container image build
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PullPolicy {
+    Always,
+    Missing,
+    Never,
+    Newer,
+}
+
+impl std::fmt::Display for PullPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Always => write!(f, "always"),
+            Self::Missing => write!(f, "missing"),
+            Self::Never => write!(f, "never"),
+            Self::Newer => write!(f, "newer"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
 pub struct ContainerBuild {
+    /// The directory to build the image in
     #[validate(length(min = 1, message = "context must not be empty"))]
     pub context: Option<String>,
+    /// The tag to give the image
     #[validate(length(min = 1, message = "tag must not be empty"))]
     pub tag: Option<String>,
+    /// The path of the Containerfile, if not `Containerfile` in `context`
+    #[validate(length(min = 1, message = "file must not be empty"))]
+    pub file: Option<String>,
+    /// Build arguments, whose values can use variables
+    #[validate(custom(function = "crate::validate::keys_non_empty_strings"))]
+    pub build_args: Option<HashMap<String, String>>,
+    /// The stage to build in a multi-stage Containerfile
+    #[validate(length(min = 1, message = "target must not be empty"))]
+    pub target: Option<String>,
+    /// Labels to add to the image
+    #[validate(custom(function = "crate::validate::keys_non_empty_strings"))]
+    pub labels: Option<HashMap<String, String>>,
+    /// The platform to build for, e.g. `linux/arm64`
+    #[validate(length(min = 1, message = "platform must not be empty"))]
+    pub platform: Option<String>,
+    /// Whether to build without using cached layers
+    pub no_cache: Option<bool>,
+    /// When to pull the base images
+    pub pull: Option<PullPolicy>,
+    /// Secrets to expose to the build, e.g. `id=token,src=token.txt`
+    #[validate(custom(function = "crate::validate::non_empty_strings"))]
+    pub secrets: Option<Vec<String>>,
 
     #[serde(flatten)]
     #[validate(nested)]
@@ -291,16 +628,56 @@ impl ContainerBuild {
             .as_ref()
             .map(|i| resolve_target_names_in(i, name_map))
             .transpose()?;
+        new.file = self
+            .file
+            .as_ref()
+            .map(|f| resolve_target_names_in(f, name_map))
+            .transpose()?;
+        new.build_args = self
+            .build_args
+            .as_ref()
+            .map(|b| resolve_target_names_in_map(b, name_map))
+            .transpose()?;
+        new.target = self
+            .target
+            .as_ref()
+            .map(|t| resolve_target_names_in(t, name_map))
+            .transpose()?;
+        new.labels = self
+            .labels
+            .as_ref()
+            .map(|l| resolve_target_names_in_map(l, name_map))
+            .transpose()?;
+        new.platform = self
+            .platform
+            .as_ref()
+            .map(|p| resolve_target_names_in(p, name_map))
+            .transpose()?;
+        new.secrets = self
+            .secrets
+            .as_ref()
+            .map(|s| resolve_target_names_in_vec(s, name_map))
+            .transpose()?;
         Ok(new)
     }
 }
 
-#[derive(Deserialize, Clone, Debug, Validate)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Validate)]
 pub struct ExecArtifact {
+    /// The command line that builds the artifact, which can use variables
     #[validate(length(min = 1, message = "Command must not be empty"))]
     pub command: Option<String>,
-    #[validate(custom(function = "crate::validate::non_empty_strings"))]
-    pub env: Option<Vec<String>>,
+    /// The directory to run the command in
+    #[validate(length(min = 1, message = "cwd must not be empty"))]
+    pub cwd: Option<String>,
+    /// The directory of the file the target was included from, which it runs in if
+    /// it doesn't set a `cwd`
+    #[serde(skip)]
+    pub default_cwd: Option<String>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub env_info: EnvInfo,
 
     #[serde(flatten)]
     #[validate(nested)]
@@ -334,10 +711,11 @@ impl ExecArtifact {
             .as_ref()
             .map(|i| resolve_target_names_in(i, name_map))
             .transpose()?;
-        new.env = self
-            .env
+        new.env_info = self.env_info.with_resolved_targets(name_map)?;
+        new.cwd = self
+            .cwd
             .as_ref()
-            .map(|e| resolve_target_names_in_vec(e, name_map))
+            .map(|c| resolve_target_names_in(c, name_map))
             .transpose()?;
         Ok(new)
     }
@@ -345,26 +723,667 @@ impl ExecArtifact {
 
 pub const CONFIG_FILE_NAME: &str = "pls.toml";
 
+/// The JSON Schema of a config file, which is also what unknown keys are found with
+pub fn schema() -> RootSchema {
+    schemars::schema_for!(Config)
+}
+
+/// Find the config file for the current directory, which is the nearest one in it or a
+/// parent, or the root config file of a monorepo that declares it as one of its projects
 pub fn find_config_file() -> Option<std::path::PathBuf> {
-    let mut config_dir = std::env::current_dir().unwrap();
+    find_config_file_from(&std::env::current_dir().unwrap())
+}
+
+/// Find the config file for `dir`, as `find_config_file` does
+pub fn find_config_file_from(dir: &Path) -> Option<std::path::PathBuf> {
+    let mut config_dir = dir.to_path_buf();
+    let mut found: Option<PathBuf> = None;
     loop {
         let config_path = config_dir.join(CONFIG_FILE_NAME);
         if config_path.exists() {
-            debug!("Found config file at <{}>", config_path.display());
-            return Some(config_path);
+            match found {
+                None => found = Some(config_path),
+                Some(ref project) => {
+                    let project_dir = project.parent().unwrap_or(Path::new(""));
+                    if declares_project(&config_path, project_dir) {
+                        debug!(
+                            "Using <{}> as the root of the project at <{}>",
+                            config_path.display(),
+                            project_dir.display()
+                        );
+                        found = Some(config_path);
+                    }
+                }
+            }
         }
         if !config_dir.pop() {
-            return None;
+            if let Some(ref config_path) = found {
+                debug!("Found config file at <{}>", config_path.display());
+            }
+            return found;
+        }
+    }
+}
+
+/// Whether the config file at `config_path` has the directory `project_dir` in its
+/// `projects`. A config file that can't be read doesn't have any.
+fn declares_project(config_path: &Path, project_dir: &Path) -> bool {
+    let dir = config_path.parent().unwrap_or(Path::new(""));
+    let Ok(relative_dir) = project_dir.strip_prefix(dir) else {
+        return false;
+    };
+    let Some(table) = std::fs::read_to_string(config_path)
+        .ok()
+        .and_then(|text| text.parse::<toml::Table>().ok())
+    else {
+        return false;
+    };
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    table
+        .get("projects")
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|p| p.as_str())
+        .filter_map(|p| Pattern::new(project_pattern(p)).ok())
+        .any(|p| p.matches_path_with(relative_dir, options))
+}
+
+/// A path or glob from `projects`, without the parts that don't change what it matches
+fn project_pattern(pattern: &str) -> &str {
+    pattern.trim_start_matches("./").trim_end_matches('/')
+}
+
+/// The text of config files by their canonical path, which is read in place of what is
+/// saved, such as that of files being edited that haven't been saved yet
+#[derive(Debug, Clone, Default)]
+pub struct Sources(BTreeMap<PathBuf, String>);
+
+impl Sources {
+    /// Use `text` as the text of the config file at `path`
+    pub fn insert(&mut self, path: &Path, text: String) {
+        self.0.insert(canonical_path(path), text);
+    }
+
+    /// Go back to what is saved for the config file at `path`
+    pub fn remove(&mut self, path: &Path) {
+        self.0.remove(&canonical_path(path));
+    }
+
+    /// Add the text of the config files of `other`
+    pub fn extend(&mut self, other: &Sources) {
+        self.0
+            .extend(other.0.iter().map(|(p, t)| (p.clone(), t.clone())));
+    }
+
+    /// The text of the config file at `path`
+    pub fn read(&self, path: &Path) -> std::io::Result<String> {
+        if let Some(text) = self.0.get(&canonical_path(path)) {
+            return Ok(text.clone());
+        }
+        std::fs::read_to_string(path)
+    }
+}
+
+/// The config files of the projects that the root config file at `root_config_path`
+/// declares in `projects`, sorted by path
+fn project_config_files(root_config_path: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let root_dir = root_config_path.parent().unwrap_or(Path::new(""));
+    let mut found = vec![];
+    for pattern in patterns {
+        let full_pattern = root_dir.join(project_pattern(pattern));
+        let dirs = glob(full_pattern.to_string_lossy().as_ref())
+            .map_err(|e| {
+                anyhow!(
+                    "Invalid project <{}> in <{}>: {}",
+                    pattern,
+                    root_config_path.display(),
+                    e
+                )
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let config_paths = dirs
+            .into_iter()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .filter(|config_path| config_path.exists())
+            .collect::<Vec<_>>();
+        if config_paths.is_empty() && !pattern.contains(['*', '?', '[']) {
+            return Err(anyhow!(
+                "Project <{}> has no {}, declared in <{}>",
+                full_pattern.display(),
+                CONFIG_FILE_NAME,
+                root_config_path.display()
+            ));
+        }
+        found.extend(config_paths);
+    }
+    found.sort();
+    found.dedup();
+    Ok(found)
+}
+
+/// The config of a project, along with its path relative to the root project, which
+/// has an empty name.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub name: String,
+    pub config: Config,
+}
+
+impl Project {
+    /// Load the root project and the projects it declares in `projects`. Config files that
+    /// are included by another config file are part of that project rather than their own.
+    /// Config files in `sources` are read from there rather than from what is saved.
+    pub fn load_all(root_config_path: &Path, sources: &Sources) -> Result<Vec<Project>> {
+        let root_dir = root_config_path.parent().unwrap_or(Path::new(""));
+        let mut loaded = HashSet::new();
+        let mut errors = vec![];
+        let mut projects = vec![];
+        let mut project_paths = vec![];
+        match Config::load(root_config_path, root_dir, &mut loaded, sources) {
+            Ok(config) => {
+                let patterns = config.projects.clone().unwrap_or_default();
+                match project_config_files(root_config_path, &patterns) {
+                    Ok(paths) => project_paths = paths,
+                    Err(e) => errors.push(e),
+                }
+                projects.push(Project {
+                    name: String::new(),
+                    config,
+                });
+            }
+            Err(e) => errors.push(e),
         }
+        for config_path in project_paths {
+            if loaded.contains(&canonical_path(&config_path)) {
+                continue;
+            }
+            let dir = config_path.parent().unwrap_or(Path::new(""));
+            let name = dir
+                .strip_prefix(root_dir)
+                .unwrap_or(dir)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            debug!("Found project <{}> at <{}>", name, config_path.display());
+            match Config::load(&config_path, root_dir, &mut loaded, sources) {
+                Ok(config) => projects.push(Project { name, config }),
+                Err(e) => errors.push(e),
+            }
+        }
+        if !errors.is_empty() {
+            errors.extend(projects.iter().flat_map(|p| p.config.errors()));
+            Diagnostics::check(errors)?;
+        }
+        Ok(projects)
+    }
+
+    /// Apply the profile `name` to each of the projects that has it, which must be at least one
+    pub fn apply_profile(projects: &mut [Project], name: &str) -> Result<()> {
+        let mut found = false;
+        for project in projects.iter_mut() {
+            if let Some(config) = project.config.with_profile(name)? {
+                debug!("Applied profile <{}> to project <{}>", name, project.name);
+                project.config = config;
+                found = true;
+            }
+        }
+        if !found {
+            return Err(anyhow!("Profile <{}> not found in any config file", name));
+        }
+        Ok(())
+    }
+
+    /// The name of the innermost project containing `dir`, which is relative to the root
+    pub fn containing<'a>(projects: &'a [Project], dir: &Path) -> &'a str {
+        projects
+            .iter()
+            .filter(|p| dir.starts_with(p.name.as_str()))
+            .max_by_key(|p| p.name.len())
+            .map(|p| p.name.as_str())
+            .unwrap_or("")
     }
 }
 
+fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or(path.into())
+}
+
 impl Config {
-    pub fn load_and_validate(config_path: &PathBuf) -> Result<Self> {
-        let config_str = std::fs::read_to_string(config_path)?;
-        let config: Config = toml::from_str(config_str.as_str())?;
+    pub fn load_and_validate(config_path: &Path) -> Result<Self> {
+        let root_dir = config_path.parent().unwrap_or(Path::new(""));
+        let config = Self::load(
+            config_path,
+            root_dir,
+            &mut HashSet::new(),
+            &Sources::default(),
+        )?;
+        Diagnostics::check(config.errors())?;
+        Ok(config)
+    }
+
+    /// The errors found loading this config, as errors that can be reported together
+    pub fn errors(&self) -> Vec<anyhow::Error> {
+        self.errors
+            .iter()
+            .cloned()
+            .map(anyhow::Error::from)
+            .collect()
+    }
+
+    /// Load a config file and the files it includes, with relative paths rewritten to be
+    /// relative to `root_dir`. Only errors that stop the config from being read fail it;
+    /// the others are kept in `errors`.
+    fn load(
+        config_path: &Path,
+        root_dir: &Path,
+        loaded: &mut HashSet<PathBuf>,
+        sources: &Sources,
+    ) -> Result<Self> {
+        let mut loader = IncludeLoader {
+            root_dir,
+            origins: HashMap::new(),
+            loaded,
+            sources: sources.clone(),
+            errors: vec![],
+        };
+        let mut config = loader.load(config_path)?;
         debug!("Loaded config: {:?}", config);
+        config.errors = loader.errors.iter().flat_map(Diagnostics::of).collect();
+        config.origins = loader.origins;
+        config.sources = loader.sources;
+        if let Err(e) = config.validate() {
+            let diagnostics = config.validation_diagnostics(&e, config_path);
+            config.errors.extend(diagnostics);
+        }
+        Ok(config)
+    }
+
+    /// The diagnostics for the errors found validating this config, each at the definition
+    /// it is for
+    fn validation_diagnostics(
+        &self,
+        errors: &ValidationErrors,
+        config_path: &Path,
+    ) -> Vec<Diagnostic> {
+        validation_errors(errors)
+            .into_iter()
+            .map(|(mut keys, message)| {
+                // Targets are validated in the order of their map, which is all that says
+                // which one an error is for
+                let index = keys.get(2).and_then(|i| i.parse().ok());
+                if let Some(name) = index.and_then(|i| self.target_name(&keys[0], &keys[1], i)) {
+                    keys[2] = name;
+                }
+                let path = [3, 2]
+                    .iter()
+                    .filter_map(|n| keys.get(..*n))
+                    .find_map(|k| self.origins.get(&k.join(".")))
+                    .map(|p| p.as_path())
+                    .unwrap_or(config_path);
+                Diagnostic::at_key(
+                    format!("Error validating <{}>: {}", keys.join("."), message),
+                    path,
+                    &self.sources,
+                    &keys,
+                )
+            })
+            .collect()
+    }
+
+    /// The name of the target at `index` in the map of targets of its kind
+    fn target_name(&self, section: &str, kind: &str, index: usize) -> Option<String> {
+        fn nth_key<T>(map: &Option<HashMap<String, T>>, index: usize) -> Option<String> {
+            map.as_ref()?.keys().nth(index).cloned()
+        }
+        match (section, kind) {
+            ("command", "exec") => nth_key(&self.command.as_ref()?.exec, index),
+            ("command", "container") => nth_key(&self.command.as_ref()?.container, index),
+            ("command", "pod") => nth_key(&self.command.as_ref()?.pod, index),
+            ("artifact", "container_image") => {
+                nth_key(&self.artifact.as_ref()?.container_image, index)
+            }
+            ("artifact", "exec") => nth_key(&self.artifact.as_ref()?.exec, index),
+            _ => None,
+        }
+    }
+
+    /// This config with the overrides of the profile `name` applied on top of it, or `None`
+    /// if it doesn't have that profile
+    pub fn with_profile(&self, name: &str) -> Result<Option<Self>> {
+        let Some(overrides) = self.profile.as_ref().and_then(|p| p.get(name)) else {
+            return Ok(None);
+        };
+        let mut table = toml::Table::try_from(self)?;
+        merge_tables(&mut table, overrides.clone());
+        let mut config: Config = table
+            .try_into()
+            .map_err(|e| anyhow!("Error applying profile <{}>: {}", name, e))?;
+        config.unknown_keys = self.unknown_keys.clone();
+        config.origins = self.origins.clone();
+        config.errors = self.errors.clone();
+        config.sources = self.sources.clone();
+        config.copy_default_cwds(self);
         config.validate()?;
+        Ok(Some(config))
+    }
+
+    /// Carry over the directories that exec targets run in by default, which aren't part
+    /// of the file and so are lost when it is converted to a table
+    fn copy_default_cwds(&mut self, from: &Config) {
+        if let (Some(command), Some(from)) = (self.command.as_mut(), from.command.as_ref()) {
+            for (name, exec) in command.exec.iter_mut().flatten() {
+                exec.default_cwd = from
+                    .exec
+                    .as_ref()
+                    .and_then(|m| m.get(name))
+                    .and_then(|e| e.default_cwd.clone());
+            }
+        }
+        if let (Some(artifact), Some(from)) = (self.artifact.as_mut(), from.artifact.as_ref()) {
+            for (name, exec) in artifact.exec.iter_mut().flatten() {
+                exec.default_cwd = from
+                    .exec
+                    .as_ref()
+                    .and_then(|m| m.get(name))
+                    .and_then(|e| e.default_cwd.clone());
+            }
+        }
+    }
+
+    fn parse(config_path: &Path, config_str: &str) -> Result<Self> {
+        let mut config: Config = toml::from_str(config_str).map_err(|e| {
+            Diagnostic::at_span(
+                format!(
+                    "Error parsing config file <{}>: {}",
+                    config_path.display(),
+                    e.message()
+                ),
+                config_path,
+                config_str,
+                e.span(),
+            )
+        })?;
+        let table = toml::from_str(config_str)?;
+        config.unknown_keys = check::unknown_keys(&table)
+            .into_iter()
+            .map(|key| format!("Unknown key <{}> in <{}>", key, config_path.display()))
+            .collect();
         Ok(config)
     }
+
+    /// Add the definitions from `other`, which must not overlap with those already here
+    fn merge(&mut self, other: Config) {
+        merge_maps(&mut self.globals, other.globals);
+        self.unknown_keys.extend(other.unknown_keys);
+        for (name, overrides) in other.profile.into_iter().flatten() {
+            merge_tables(
+                self.profile
+                    .get_or_insert_with(HashMap::new)
+                    .entry(name)
+                    .or_default(),
+                overrides,
+            );
+        }
+        merge_maps(&mut self.env, other.env);
+        if let Some(other_env_file) = other.env_file {
+            self.env_file
+                .get_or_insert_with(Vec::new)
+                .extend(other_env_file);
+        }
+        if let Some(other_command) = other.command {
+            let command = self.command.get_or_insert(Command {
+                exec: None,
+                container: None,
+                pod: None,
+            });
+            merge_maps(&mut command.exec, other_command.exec);
+            merge_maps(&mut command.container, other_command.container);
+            merge_maps(&mut command.pod, other_command.pod);
+        }
+        if let Some(other_artifact) = other.artifact {
+            let artifact = self.artifact.get_or_insert(Artifact {
+                container_image: None,
+                exec: None,
+            });
+            merge_maps(
+                &mut artifact.container_image,
+                other_artifact.container_image,
+            );
+            merge_maps(&mut artifact.exec, other_artifact.exec);
+        }
+    }
+
+    /// Make the relative paths in this config, which are written relative to the file it
+    /// was loaded from, relative to the root config file instead
+    fn relative_to(&mut self, dir: &Path) {
+        for env_file in self.env_file.iter_mut().flatten() {
+            env_file.relative_to(dir);
+        }
+        for overrides in self.profile.iter_mut().flat_map(|p| p.values_mut()) {
+            rebase_profile(overrides, dir);
+        }
+        if let Some(ref mut command) = self.command {
+            for exec in command.exec.iter_mut().flat_map(|m| m.values_mut()) {
+                exec.cwd = exec.cwd.as_deref().map(|c| rebase_path(c, dir));
+                exec.default_cwd = Some(rebase_path(".", dir));
+                exec.env_info.relative_to(dir);
+            }
+            for container in command.container.iter_mut().flat_map(|m| m.values_mut()) {
+                container.env_info.relative_to(dir);
+                if let Some(ref mut mount) = container.mount {
+                    *mount = mount
+                        .drain()
+                        .map(|(source, target)| {
+                            if source.starts_with('.') {
+                                (rebase_path(source.as_str(), dir), target)
+                            } else {
+                                (source, target)
+                            }
+                        })
+                        .collect();
+                }
+            }
+        }
+        if let Some(ref mut artifact) = self.artifact {
+            for image in artifact
+                .container_image
+                .iter_mut()
+                .flat_map(|m| m.values_mut())
+            {
+                image.context = image.context.as_deref().map(|c| rebase_path(c, dir));
+                image.file = image.file.as_deref().map(|f| rebase_path(f, dir));
+                image.artifact_info.relative_to(dir);
+            }
+            for exec in artifact.exec.iter_mut().flat_map(|m| m.values_mut()) {
+                exec.cwd = exec.cwd.as_deref().map(|c| rebase_path(c, dir));
+                exec.default_cwd = Some(rebase_path(".", dir));
+                exec.env_info.relative_to(dir);
+                exec.artifact_info.relative_to(dir);
+            }
+        }
+    }
+}
+
+impl ArtifactInfo {
+    fn relative_to(&mut self, dir: &Path) {
+        for paths in [&mut self.updates_paths, &mut self.if_files_changed]
+            .into_iter()
+            .flatten()
+        {
+            *paths = paths.iter().map(|p| rebase_path(p, dir)).collect();
+        }
+    }
+}
+
+/// Rebase the paths in the overrides of a profile like those in the rest of its file. The
+/// overrides are left alone if they aren't a valid config, as that is reported when the
+/// profile is applied.
+fn rebase_profile(overrides: &mut toml::Table, dir: &Path) {
+    let Ok(mut config) = overrides.clone().try_into::<Config>() else {
+        return;
+    };
+    config.relative_to(dir);
+    if let Ok(rebased) = toml::Table::try_from(&config) {
+        *overrides = rebased;
+    }
+}
+
+/// Join a relative path onto `dir`, leaving alone paths that are absolute, in the
+/// home directory or that start with a substitution
+fn rebase_path(path: &str, dir: &Path) -> String {
+    if path.starts_with(['/', '~', '{']) {
+        return path.to_string();
+    }
+    if path == "." {
+        return dir.display().to_string();
+    }
+    dir.join(path.strip_prefix("./").unwrap_or(path))
+        .display()
+        .to_string()
+}
+
+/// Loads a config file along with the files that it includes, remembering where each
+/// definition came from so that duplicates can be reported.
+struct IncludeLoader<'a> {
+    root_dir: &'a Path,
+    origins: HashMap<String, PathBuf>,
+    loaded: &'a mut HashSet<PathBuf>,
+    /// The text of the files, which those that are loaded are added to
+    sources: Sources,
+    /// Errors in the files that are included, which are reported once all are loaded
+    errors: Vec<anyhow::Error>,
+}
+
+impl IncludeLoader<'_> {
+    fn load(&mut self, config_path: &Path) -> Result<Config> {
+        self.loaded.insert(canonical_path(config_path));
+        let source = self.sources.read(config_path).map_err(|e| {
+            anyhow!(
+                "Error reading config file <{}>: {}",
+                config_path.display(),
+                e
+            )
+        })?;
+        let mut config = Config::parse(config_path, &source)?;
+        self.sources.insert(config_path, source);
+        let dir = config_path.parent().unwrap_or(Path::new(""));
+        let relative_dir = dir.strip_prefix(self.root_dir).unwrap_or(dir);
+        if relative_dir != Path::new("") {
+            config.relative_to(relative_dir);
+        }
+        self.record_origins(&config, config_path);
+        for pattern in config.include.take().unwrap_or_default() {
+            let full_pattern = dir.join(pattern.as_str());
+            let mut paths = glob(full_pattern.to_string_lossy().as_ref())
+                .map_err(|e| {
+                    anyhow!(
+                        "Invalid include <{}> in <{}>: {}",
+                        pattern,
+                        config_path.display(),
+                        e
+                    )
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            if paths.is_empty() && !pattern.contains(['*', '?', '[']) {
+                return Err(anyhow!(
+                    "Included file <{}> not found, included from <{}>",
+                    full_pattern.display(),
+                    config_path.display()
+                ));
+            }
+            paths.sort();
+            for path in paths {
+                if self.loaded.contains(&canonical_path(&path)) {
+                    debug!("Skipping <{}> as it is already loaded", path.display());
+                    continue;
+                }
+                debug!(
+                    "Including <{}> from <{}>",
+                    path.display(),
+                    config_path.display()
+                );
+                // Carry on past a file with errors, so that they are all reported together
+                match self.load(&path) {
+                    Ok(included) => config.merge(included),
+                    Err(e) => self.errors.push(e),
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    fn record_origins(&mut self, config: &Config, config_path: &Path) {
+        let mut names = vec![];
+        for name in config.globals.iter().flat_map(|g| g.keys()) {
+            names.push(("Global", format!("globals.{}", name)));
+        }
+        for name in config.env.iter().flat_map(|e| e.keys()) {
+            names.push(("Env var", format!("env.{}", name)));
+        }
+        if let Some(ref command) = config.command {
+            for name in command.exec.iter().flat_map(|m| m.keys()) {
+                names.push(("Target", format!("{}.{}", ExecCommand::tag(), name)));
+            }
+            for name in command.container.iter().flat_map(|m| m.keys()) {
+                names.push(("Target", format!("{}.{}", ContainerCommand::tag(), name)));
+            }
+            for name in command.pod.iter().flat_map(|m| m.keys()) {
+                names.push(("Target", format!("{}.{}", Pod::tag(), name)));
+            }
+        }
+        if let Some(ref artifact) = config.artifact {
+            for name in artifact.container_image.iter().flat_map(|m| m.keys()) {
+                names.push(("Target", format!("{}.{}", ContainerBuild::tag(), name)));
+            }
+            for name in artifact.exec.iter().flat_map(|m| m.keys()) {
+                names.push(("Target", format!("{}.{}", ExecArtifact::tag(), name)));
+            }
+        }
+        for (kind, name) in names {
+            if let Some(existing) = self.origins.get(&name) {
+                let keys = name.split('.').map(String::from).collect::<Vec<_>>();
+                self.errors.push(
+                    Diagnostic::at_key(
+                        format!(
+                            "{} <{}> is defined in both <{}> and <{}>",
+                            kind,
+                            name,
+                            existing.display(),
+                            config_path.display()
+                        ),
+                        config_path,
+                        &self.sources,
+                        &keys,
+                    )
+                    .into(),
+                );
+                continue;
+            }
+            self.origins.insert(name, config_path.to_path_buf());
+        }
+    }
+}
+
+/// Merge `from` into `into`, where tables in both are merged and any other value in `from`
+/// replaces the one in `into`
+fn merge_tables(into: &mut toml::Table, from: toml::Table) {
+    for (key, value) in from {
+        match (into.get_mut(&key), value) {
+            (Some(toml::Value::Table(into)), toml::Value::Table(from)) => merge_tables(into, from),
+            (_, value) => {
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+fn merge_maps<T>(into: &mut Option<HashMap<String, T>>, from: Option<HashMap<String, T>>) {
+    if let Some(from) = from {
+        into.get_or_insert_with(HashMap::new).extend(from);
+    }
 }
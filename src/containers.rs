@@ -1 +1,293 @@
+use std::path::Path;
 
+use anyhow::{anyhow, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{run_command, run_command_with_output};
+use crate::context::Context;
+use crate::name::FullyQualifiedName;
+use crate::shell::escape_string;
+
+const CONTAINER_STATE_FILE_NAME: &str = "container.toml";
+
+pub const PROJECT_LABEL: &str = "pls.project";
+pub const TARGET_LABEL: &str = "pls.target";
+
+/// Arguments to label a container, network or volume with the project and target
+/// that created it, so that they can be found again by `pls gc`.
+pub fn label_args(context: &Context, target_name: &FullyQualifiedName) -> Result<String> {
+    Ok(format!(
+        "--label {} --label {}",
+        escape_string(format!("{}={}", PROJECT_LABEL, context.root().display()).as_str())?,
+        escape_string(format!("{}={}", TARGET_LABEL, target_name).as_str())?
+    ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Container,
+    Network,
+    Volume,
+}
+
+impl std::fmt::Display for Resource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Container => write!(f, "container"),
+            Self::Network => write!(f, "network"),
+            Self::Volume => write!(f, "volume"),
+        }
+    }
+}
+
+impl Resource {
+    fn list_command(&self) -> &'static str {
+        match self {
+            Self::Container => "podman ps -a --format {{.Names}}",
+            Self::Network => "podman network ls --format {{.Name}}",
+            Self::Volume => "podman volume ls --format {{.Name}}",
+        }
+    }
+
+    fn remove_command(&self) -> &'static str {
+        match self {
+            Self::Container => "podman rm -f",
+            Self::Network => "podman network rm",
+            Self::Volume => "podman volume rm",
+        }
+    }
+}
+
+/// The command to list the resources of the given kind, filtered by the project label
+fn list_project_command(kind: Resource, context: &Context) -> Result<String> {
+    Ok(format!(
+        "{} --filter {}",
+        kind.list_command(),
+        escape_string(format!("label={}={}", PROJECT_LABEL, context.root().display()).as_str())?
+    ))
+}
+
+/// The names of all resources of the given kind that were created by the project.
+pub fn list_project_resources(kind: Resource, context: &Context) -> Result<Vec<String>> {
+    let output = run_command_with_output(list_project_command(kind, context)?.as_str())?;
+    Ok(output
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| l.to_string())
+        .collect())
+}
+
+pub fn remove_resource(kind: Resource, name: &str) -> Result<()> {
+    run_command(format!("{} {}", kind.remove_command(), escape_string(name)?).as_str())
+}
+
+/// What a container daemon created when it started, so that it can all be cleaned up
+/// when it is stopped.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ContainerState {
+    pub name: String,
+    pub network: Option<String>,
+    /// Whether the network was created for this container, and so should be removed
+    /// along with it
+    pub created_network: bool,
+    pub volumes: Vec<String>,
+}
+
+impl ContainerState {
+    pub fn load(metadata_dir: &Path) -> Result<Option<Self>> {
+        let path = metadata_dir.join(CONTAINER_STATE_FILE_NAME);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(contents.as_str()).map(Some).map_err(|e| {
+                anyhow!(
+                    "Error reading container state from <{}>: {}",
+                    path.display(),
+                    e
+                )
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow!(
+                "Error reading container state from <{}>: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    pub fn save(&self, metadata_dir: &Path) -> Result<()> {
+        let path = metadata_dir.join(CONTAINER_STATE_FILE_NAME);
+        debug!("Storing container state at <{}>", path.display());
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn remove(metadata_dir: &Path) -> Result<()> {
+        let path = metadata_dir.join(CONTAINER_STATE_FILE_NAME);
+        debug!("Removing container state at <{}>", path.display());
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+/// Whether podman failed because the container doesn't exist, rather than for some other
+/// reason such as podman itself being broken.
+fn is_container_not_found(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("no such container") || error.contains("no container with name or id")
+}
+
+/// The status of the container, e.g. "running", or `None` if it isn't running or doesn't
+/// exist. Any other error from podman is returned, as then it isn't known whether the
+/// container is running.
+pub fn container_status(name: &str) -> Result<Option<String>> {
+    match run_command_with_output(
+        format!(
+            "podman container inspect --format {} {}",
+            escape_string("{{.State.Status}}")?,
+            escape_string(name)?
+        )
+        .as_str(),
+    ) {
+        Ok(status) if status == "running" => Ok(Some(status)),
+        Ok(status) => {
+            debug!("Container <{}> has status <{}>", name, status);
+            Ok(None)
+        }
+        Err(e) if is_container_not_found(e.to_string().as_str()) => {
+            debug!("Container <{}> not found: {}", name, e);
+            Ok(None)
+        }
+        Err(e) => Err(anyhow!("Error inspecting container <{}>: {}", name, e)),
+    }
+}
+
+/// Strip the algorithm prefix from an image ID so that IDs from `--iidfile` and
+/// `podman image inspect` can be compared.
+pub fn normalize_image_id(id: &str) -> &str {
+    let id = id.trim();
+    id.strip_prefix("sha256:").unwrap_or(id)
+}
+
+fn inspect_image(reference: &str, format: &str) -> Result<String> {
+    run_command_with_output(
+        format!(
+            "podman image inspect --format {} {}",
+            escape_string(format)?,
+            escape_string(reference)?
+        )
+        .as_str(),
+    )
+}
+
+/// Whether podman failed because the image doesn't exist, rather than for some other
+/// reason such as podman itself being broken.
+fn is_image_not_found(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("image not known") || error.contains("no such image")
+}
+
+/// The ID of the image in the local store, or `None` if there is no such image.
+pub fn image_id(reference: &str) -> Result<Option<String>> {
+    match inspect_image(reference, "{{.Id}}") {
+        Ok(id) => Ok(Some(id)),
+        Err(e) if is_image_not_found(e.to_string().as_str()) => {
+            debug!("Image <{}> not found in local store: {}", reference, e);
+            Ok(None)
+        }
+        Err(e) => Err(anyhow!("Error inspecting image <{}>: {}", reference, e)),
+    }
+}
+
+pub fn image_digest(reference: &str) -> Result<String> {
+    inspect_image(reference, "{{.Digest}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_state_round_trip() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        assert_eq!(ContainerState::load(dir.path()).unwrap(), None);
+        let state = ContainerState {
+            name: "command.container.db-abc12345".to_string(),
+            network: Some("command.container.db-abc12345".to_string()),
+            created_network: true,
+            volumes: vec!["pgdata".to_string()],
+        };
+        state.save(dir.path()).unwrap();
+        assert_eq!(ContainerState::load(dir.path()).unwrap(), Some(state));
+        ContainerState::remove(dir.path()).unwrap();
+        assert_eq!(ContainerState::load(dir.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_label_args() {
+        let context = Context {
+            config_path: "/home/user/project/pls.toml".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            label_args(
+                &context,
+                &FullyQualifiedName {
+                    project: String::new(),
+                    tag: "command.container".to_string(),
+                    name: "db".to_string(),
+                }
+            )
+            .unwrap(),
+            "--label 'pls.project=/home/user/project' --label 'pls.target=command.container.db'"
+        );
+    }
+
+    #[test]
+    fn test_list_project_command() {
+        let context = Context {
+            config_path: "/home/user/project/pls.toml".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            list_project_command(Resource::Container, &context).unwrap(),
+            "podman ps -a --format {{.Names}} --filter 'label=pls.project=/home/user/project'"
+        );
+        assert_eq!(
+            list_project_command(Resource::Volume, &context).unwrap(),
+            "podman volume ls --format {{.Name}} --filter 'label=pls.project=/home/user/project'"
+        );
+    }
+
+    #[test]
+    fn test_is_image_not_found() {
+        assert!(is_image_not_found(
+            "Command failed with exit code: 125: Error: myimage: image not known"
+        ));
+        assert!(is_image_not_found(
+            "Command failed with exit code: 125: Error: no such image"
+        ));
+        assert!(!is_image_not_found(
+            "Command failed with exit code: 125: Error: cannot connect to Podman"
+        ));
+    }
+
+    #[test]
+    fn test_is_container_not_found() {
+        assert!(is_container_not_found(
+            "Command failed with exit code: 125: Error: no such container db-abc"
+        ));
+        assert!(is_container_not_found(
+            "Command failed with exit code: 125: Error: no container with name or ID \"db-abc\" found"
+        ));
+        assert!(!is_container_not_found(
+            "Command failed with exit code: 125: Error: cannot connect to Podman"
+        ));
+    }
+
+    #[test]
+    fn test_normalize_image_id() {
+        assert_eq!(normalize_image_id("sha256:abc123\n"), "abc123");
+        assert_eq!(normalize_image_id("abc123"), "abc123");
+    }
+}
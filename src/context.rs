@@ -1,72 +1,219 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 use anyhow::{anyhow, Result};
 use log::debug;
-use validator::Validate;
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationErrors};
 
 use crate::{
+    arguments,
+    commands::{run_command_with_output, Environment},
     config::{
         ArtifactInfo as ConfigArtifactInfo, CommandInfo as ConfigCommandInfo, Config,
         ContainerBuild as ConfigContainerBuild, ContainerCommand as ConfigContainerCommand,
-        ExecArtifact as ConfigExecArtifact, ExecCommand as ConfigExecCommand,
-        TargetInfo as ConfigTargetInfo,
+        EnvFile, ExecArtifact as ConfigExecArtifact, ExecCommand as ConfigExecCommand,
+        Pod as ConfigPod, Project, Sources, TargetInfo as ConfigTargetInfo, VariableValue,
     },
     default::default_to,
-    name::FullyQualifiedName,
+    diagnostic::{validation_errors, Diagnostic, Diagnostics},
+    dotenv::load_env_files,
+    git::GitInfo,
+    name::{FullyQualifiedName, PROJECT_SEPARATOR},
     outputs::OutputsManager,
     shell::escape_string,
-    target::{Artifact, ArtifactInfo, Command, CommandInfo, Target, TargetInfo},
-    targets::{ContainerArtifact, ContainerCommand, ExecArtifact, ExecCommand},
+    target::{Artifact, ArtifactInfo, Command, CommandInfo, EnvInfo, Target, TargetInfo},
+    targets::{ContainerArtifact, ContainerCommand, ExecArtifact, ExecCommand, PodCommand},
+    template::{Filter, Template},
 };
 
-enum Variable {
+pub(crate) enum Variable {
     Simple(String),
     Global(String),
+    /// An environment variable of the process pls is run in
+    Env(String),
+    /// Information about the project and the target, such as `pls.root`
+    Pls(String),
+    /// Information about the git repository, such as `git.sha`
+    Git(String),
+    /// An argument that the target declares, such as `args.env`
+    Arg(String),
     Ref(String, String),
     Output(String, String),
 }
 
 impl Variable {
+    /// Parse a variable, where a reference to a target in another project is prefixed
+    /// with the project and the last `:`, so that the project path can contain `.`
     pub fn from_string(input: &str) -> Result<Self> {
-        if !input.contains('.') {
+        let (project, rest) = match input.rsplit_once(PROJECT_SEPARATOR) {
+            Some((project, rest)) => (Some(project), rest),
+            None => (None, input),
+        };
+        let qualify = |target: String| match project {
+            Some(project) => format!("{}{}{}", project, PROJECT_SEPARATOR, target),
+            None => target,
+        };
+        if !rest.contains('.') {
             Ok(Self::Simple(input.to_string()))
+        } else if project.is_some() {
+            Ok(Self::target_variable(rest, qualify))
         } else if let Some(key) = input.strip_prefix("globals.") {
             Ok(Self::Global(key.to_string()))
+        } else if let Some(key) = input.strip_prefix("env.") {
+            Ok(Self::Env(key.to_string()))
+        } else if let Some(key) = input.strip_prefix("pls.") {
+            Ok(Self::Pls(key.to_string()))
+        } else if let Some(key) = input.strip_prefix("git.") {
+            Ok(Self::Git(key.to_string()))
+        } else if let Some(key) = input.strip_prefix("args.") {
+            Ok(Self::Arg(key.to_string()))
         } else {
-            let parts = input.split('.').collect::<Vec<_>>();
-            if parts.len() > 2 && parts[parts.len() - 2] == "output" {
-                Ok(Self::Output(
-                    parts[0..parts.len() - 2].join(".").to_string(),
-                    parts[parts.len() - 1].to_string(),
-                ))
-            } else {
-                Ok(Self::Ref(
-                    parts[0..parts.len() - 1].join(".").to_string(),
-                    parts[parts.len() - 1].to_string(),
-                ))
-            }
+            Ok(Self::target_variable(rest, qualify))
+        }
+    }
+
+    /// A variable or output of a target, e.g. `build.version` or `db.outputs.name`
+    fn target_variable(input: &str, qualify: impl Fn(String) -> String) -> Self {
+        let parts = input.split('.').collect::<Vec<_>>();
+        if parts.len() > 2 && matches!(parts[parts.len() - 2], "output" | "outputs") {
+            Self::Output(
+                qualify(parts[0..parts.len() - 2].join(".")),
+                parts[parts.len() - 1].to_string(),
+            )
+        } else {
+            Self::Ref(
+                qualify(parts[0..parts.len() - 1].join(".")),
+                parts[parts.len() - 1].to_string(),
+            )
         }
     }
 }
 
 #[derive(Debug, Default)]
 pub struct Context {
-    pub variables: HashMap<FullyQualifiedName, HashMap<String, String>>,
-    pub globals: HashMap<String, String>,
+    pub variables: HashMap<FullyQualifiedName, HashMap<String, VariableValue>>,
+    pub globals: HashMap<String, VariableValue>,
+    /// The globals of each nested project, which take precedence over those of the
+    /// root project for its targets
+    pub project_globals: HashMap<String, HashMap<String, VariableValue>>,
+    /// The env files of each project that are loaded for all of its targets
+    pub env_files: HashMap<String, Vec<EnvFile>>,
+    /// The environment variables of each project that are set for all of its targets
+    pub env: HashMap<String, HashMap<String, String>>,
 
     pub targets: HashMap<FullyQualifiedName, Target>,
+    /// The definition of each target as written in the config file, before any
+    /// `extends` are applied
+    pub definitions: HashMap<FullyQualifiedName, toml::Table>,
 
     pub config_path: String,
+    /// The project that the current directory is in, the empty string for the root
+    pub current_project: String,
+    /// The git information for the `git.` variables, which is only read when needed
+    pub git: OnceLock<std::result::Result<GitInfo, String>>,
+    /// Variables that have been expanded, by their full name, so that each is only
+    /// expanded once
+    pub expanded: Mutex<HashMap<String, Expansion>>,
+    /// The output of the commands of variables, by command, so that each is only run once
+    pub command_outputs: Mutex<HashMap<String, String>>,
+    /// The variables each target has used, and what they depended on
+    pub used_inputs: Mutex<HashMap<FullyQualifiedName, VariableInputs>>,
+    /// The values of the variables that were overridden with `--set`, by their full name
+    pub overrides: HashMap<String, String>,
+    /// Environment variables set with `--env`, which take precedence over all others
+    pub env_overrides: Vec<String>,
+    /// The keys in the config files that don't match any setting
+    pub unknown_keys: Vec<String>,
+    /// The config file each target is defined in
+    pub origins: HashMap<FullyQualifiedName, PathBuf>,
+    /// The text of the config files, which errors and definitions are located in
+    pub sources: Sources,
+}
+
+/// The inputs of a target that come from variables rather than files, which are recorded
+/// when an artifact is built so that it is built again if any of them change
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VariableInputs {
+    /// The full names of the variables that were used, whether or not they were defined
+    #[serde(default)]
+    pub variables: BTreeSet<String>,
+    /// The values of the variables used that were overridden with `--set`
+    #[serde(default)]
+    pub overrides: BTreeMap<String, String>,
+    /// The output of the commands of the variables used, by command
+    #[serde(default)]
+    pub commands: BTreeMap<String, String>,
+}
+
+impl VariableInputs {
+    /// Add the inputs of `other` to these
+    fn extend(&mut self, other: &VariableInputs) {
+        self.variables.extend(other.variables.iter().cloned());
+        self.overrides.extend(other.overrides.clone());
+        self.commands.extend(other.commands.clone());
+    }
+}
+
+/// The value of an expanded variable, along with the inputs it depends on, which are
+/// recorded again for each target that uses the cached value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub value: String,
+    pub inputs: VariableInputs,
+}
+
+type NameMap = HashMap<String, Vec<FullyQualifiedName>>;
+
+/// Records the names each target in a project can be referred to by
+struct Names<'a> {
+    project: &'a str,
+    commands: &'a mut HashMap<FullyQualifiedName, ConfigWrapper>,
+    /// Names that can be used from within the project
+    local: &'a mut NameMap,
+    /// Names that can be used from any project
+    qualified: &'a mut NameMap,
+}
+
+impl Names<'_> {
+    fn register(&mut self, name: &str, tag: &str, command: ConfigWrapper) {
+        let fully_qualified_name = FullyQualifiedName {
+            project: self.project.to_string(),
+            tag: tag.to_string(),
+            name: name.to_string(),
+        };
+        self.commands.insert(fully_qualified_name.clone(), command);
+        for local_name in [name.to_string(), format!("{}.{}", tag, name)] {
+            self.qualified
+                .entry(format!(
+                    "{}{}{}",
+                    self.project, PROJECT_SEPARATOR, local_name
+                ))
+                .or_default()
+                .push(fully_qualified_name.clone());
+            self.local
+                .entry(local_name)
+                .or_default()
+                .push(fully_qualified_name.clone());
+        }
+    }
 }
 
-fn get_lookup_name(name: String, default_tag: String) -> FullyQualifiedName {
+fn get_lookup_name(name: String, default_tag: String, project: &str) -> FullyQualifiedName {
+    let (project, name) = name
+        .rsplit_once(PROJECT_SEPARATOR)
+        .map(|(p, n)| (p.to_string(), n.to_string()))
+        .unwrap_or((project.to_string(), name));
     if let Some((tag, name)) = name.split_once('.') {
         FullyQualifiedName {
+            project,
             tag: tag.to_string(),
             name: name.to_string(),
         }
     } else {
         FullyQualifiedName {
+            project,
             tag: default_tag,
             name,
         }
@@ -105,38 +252,18 @@ where
         .collect()
 }
 
-fn extract_variables(input: &str) -> Vec<String> {
-    let mut results = Vec::new();
-    let mut start = None;
-
-    for (i, c) in input.chars().enumerate() {
-        match c {
-            '{' => start = Some(i + 1),
-            '}' => {
-                if let Some(s) = start {
-                    results.push(input[s..i].to_string());
-                    start = None;
-                }
-            }
-            _ => (),
-        }
-    }
-
-    results
-}
-
 fn resolve_variables<'a, I>(
     variables: I,
     name_map: &HashMap<String, Vec<FullyQualifiedName>>,
-) -> Result<HashMap<String, String>>
+) -> Result<HashMap<String, VariableValue>>
 where
-    I: Iterator<Item = (&'a String, &'a String)>,
+    I: Iterator<Item = (&'a String, &'a VariableValue)>,
 {
     variables
         .map(|r| {
             Ok((
                 resolve_target_names_in(r.0, name_map)?,
-                resolve_target_names_in(r.1, name_map)?,
+                r.1.with_resolved_targets(name_map)?,
             ))
         })
         .collect()
@@ -147,12 +274,17 @@ pub fn resolve_target_names_in(
     input: &str,
     name_map: &HashMap<String, Vec<FullyQualifiedName>>,
 ) -> Result<String> {
-    let mut output = input.to_string();
-    for variable in extract_variables(input) {
+    let mut template = Template::parse(input)?;
+    for substitution in template.substitutions_mut() {
+        let variable = substitution.variable.clone();
         let var = Variable::from_string(variable.as_str())?;
         let (target_name, key) = match var {
-            Variable::Simple(_) => continue,
-            Variable::Global(_) => continue,
+            Variable::Simple(_)
+            | Variable::Global(_)
+            | Variable::Env(_)
+            | Variable::Pls(_)
+            | Variable::Git(_)
+            | Variable::Arg(_) => continue,
             Variable::Ref(target_name, key) => (target_name, key),
             Variable::Output(target_name, key) => (target_name, format!("output.{}", key)),
         };
@@ -173,16 +305,21 @@ pub fn resolve_target_names_in(
                     candidates.first().unwrap()
                 }
             }
+            // A reference with a default can be to a target that doesn't exist
+            None if substitution
+                .filters
+                .iter()
+                .any(|f| matches!(f, Filter::Default(_))) =>
+            {
+                continue
+            }
             None => return Err(anyhow!("Non-existent reference <{}>", target_name)),
         };
         let resolved = format!("{}.{}", matched, key);
         debug!("Resolved <{}> to <{}>", variable, resolved);
-        output = output.replace(
-            format!("{{{}}}", variable).as_str(),
-            format!("{{{}}}", resolved.as_str()).as_str(),
-        );
+        substitution.variable = resolved;
     }
-    Ok(output)
+    Ok(template.to_string())
 }
 
 pub fn resolve_target_names_in_map(
@@ -215,6 +352,7 @@ fn target_info_from_config(
     config: &ConfigTargetInfo,
     name_map: &HashMap<String, Vec<FullyQualifiedName>>,
     base: Option<&TargetInfo>,
+    origin: Option<(&Path, &Sources)>,
 ) -> Result<TargetInfo> {
     let mut requires = base
         .as_ref()
@@ -224,8 +362,17 @@ fn target_info_from_config(
         .requires
         .as_ref()
         .map(|rs| {
-            resolve_requires(rs.iter(), name_map)
-                .map_err(|e| anyhow!("Invalid reference from <{}>: {}", name, e))
+            resolve_requires(rs.iter(), name_map).map_err(|e| {
+                let message = format!("Invalid reference from <{}>: {}", name, e);
+                match origin {
+                    Some((path, sources)) => {
+                        let mut keys = target_keys(&name);
+                        keys.push("requires".to_string());
+                        Diagnostic::at_key(message, path, sources, &keys).into()
+                    }
+                    None => anyhow!(message),
+                }
+            })
         })
         .transpose()?;
     if let Some(others) = others {
@@ -246,11 +393,19 @@ fn target_info_from_config(
     if let Some(other_variables) = other_variables {
         variables.extend(other_variables);
     }
+    let mut tags = base.as_ref().map(|b| b.tags.clone()).unwrap_or_default();
+    for tag in config.tags.iter().flatten() {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
     Ok(TargetInfo {
         name,
         requires,
         variables,
         description: config.description.clone(),
+        tags,
+        extends: base.map(|b| b.name.clone()),
     })
 }
 
@@ -259,8 +414,16 @@ fn command_info_from_config(
     config: &ConfigCommandInfo,
     base: Option<&CommandInfo>,
 ) -> CommandInfo {
+    let mut args = base.map(|b| b.args.clone()).unwrap_or_default();
+    if let Some(ref fixed) = config.args {
+        args.fixed.extend(fixed.fixed().unwrap_or_default());
+    }
+    if let Some(ref declared) = config.arguments {
+        args.declared.extend(declared.clone());
+    }
     CommandInfo {
         daemon: default_to!(config, base, daemon, false),
+        args,
     }
 }
 
@@ -318,24 +481,105 @@ fn artifact_info_from_config(
     })
 }
 
+/// Resolve a target with the targets it extends applied, with any error in it reported at its
+/// definition in the config file it is from
 fn resolve_extends(
     name: FullyQualifiedName,
     command: &ConfigWrapper,
     commands: &HashMap<FullyQualifiedName, ConfigWrapper>,
-    name_map: &HashMap<String, Vec<FullyQualifiedName>>,
+    name_maps: &HashMap<String, NameMap>,
+    origins: &HashMap<FullyQualifiedName, PathBuf>,
+    sources: &Sources,
 ) -> Result<Target> {
+    resolve_target(name.clone(), command, commands, name_maps, origins, sources)
+        .map_err(|e| locate_error(e, &name, origins, sources))
+}
+
+/// The keys of the definition of a target in its config file
+fn target_keys(name: &FullyQualifiedName) -> Vec<String> {
+    let mut keys = name.tag.split('.').map(String::from).collect::<Vec<_>>();
+    keys.push(name.name.clone());
+    keys
+}
+
+/// Report an error in a target at its definition, or at the field it is about for errors
+/// from validating it. Errors from the targets it extends are already reported at theirs.
+fn locate_error(
+    error: anyhow::Error,
+    name: &FullyQualifiedName,
+    origins: &HashMap<FullyQualifiedName, PathBuf>,
+    sources: &Sources,
+) -> anyhow::Error {
+    let Some(path) = origins.get(name) else {
+        return error;
+    };
+    if error.is::<Diagnostic>() || error.is::<Diagnostics>() {
+        return error;
+    }
+    let Some(validation) = error.downcast_ref::<ValidationErrors>() else {
+        return Diagnostic::at_key(error.to_string(), path, sources, &target_keys(name)).into();
+    };
+    let errors = validation_errors(validation)
+        .into_iter()
+        .map(|(fields, message)| {
+            let mut keys = target_keys(name);
+            keys.extend(fields.iter().cloned());
+            Diagnostic::at_key(
+                format!(
+                    "Error validating <{}.{}>: {}",
+                    name,
+                    fields.join("."),
+                    message
+                ),
+                path,
+                sources,
+                &keys,
+            )
+            .into()
+        })
+        .collect();
+    Diagnostics::check(errors).unwrap_err()
+}
+
+fn resolve_target(
+    name: FullyQualifiedName,
+    command: &ConfigWrapper,
+    commands: &HashMap<FullyQualifiedName, ConfigWrapper>,
+    name_maps: &HashMap<String, NameMap>,
+    origins: &HashMap<FullyQualifiedName, PathBuf>,
+    sources: &Sources,
+) -> Result<Target> {
+    let name_map = &name_maps[&name.project];
     let base = if let Some(extends) = command.extends() {
-        let extends_fully_qualified =
-            get_lookup_name(extends.clone(), command.type_tag().to_string());
+        let extends_fully_qualified = get_lookup_name(
+            extends.clone(),
+            command.type_tag().to_string(),
+            &name.project,
+        );
         let base = commands.get(&extends_fully_qualified);
         if let Some(base) = base {
-            resolve_extends(extends_fully_qualified, base, commands, name_map).map(Some)
+            resolve_extends(
+                extends_fully_qualified,
+                base,
+                commands,
+                name_maps,
+                origins,
+                sources,
+            )
+            .map(Some)
         } else {
-            Err(anyhow!(
+            let message = format!(
                 "<{}> extends non-existent <{}>",
-                name,
-                extends_fully_qualified
-            ))
+                name, extends_fully_qualified
+            );
+            match origins.get(&name) {
+                Some(path) => {
+                    let mut keys = target_keys(&name);
+                    keys.push("extends".to_string());
+                    Err(Diagnostic::at_key(message, path, sources, &keys).into())
+                }
+                None => Err(anyhow!(message)),
+            }
         }
     } else {
         Ok(None)
@@ -345,6 +589,7 @@ fn resolve_extends(
         &command.target_info().with_resolved_targets(name_map)?,
         name_map,
         base.as_ref().map(|b| b.target_info()),
+        origins.get(&name).map(|path| (path.as_path(), sources)),
     )?;
     if command.is_artifact() {
         let artifact_info = artifact_info_from_config(
@@ -426,11 +671,38 @@ fn resolve_extends(
                     &command.with_resolved_targets(name_map)?,
                     base,
                 );
-                container
-                    .validate()
-                    .map_err(|e| anyhow!("Error validating <{}>: {}", name, e))?;
+                container.validate()?;
                 Ok(Target::Command(Command::Container(container)))
             }
+            ConfigWrapper::Pod(command) => {
+                let base = base
+                    .as_ref()
+                    .map::<Result<_>, _>(|b| b.command()?.pod())
+                    .transpose()?;
+                let members = command
+                    .members
+                    .as_ref()
+                    .map(|ms| {
+                        resolve_requires(ms.iter(), name_map)
+                            .map_err(|e| anyhow!("Invalid member of <{}>: {}", name, e))
+                    })
+                    .transpose()?;
+                let pod = PodCommand::from_config(
+                    target_info,
+                    command_info,
+                    &command.with_resolved_targets(name_map)?,
+                    members,
+                    base,
+                );
+                pod.validate()?;
+                if pod.members.is_empty() {
+                    return Err(anyhow!(
+                        "Error validating <{}>: members must not be empty",
+                        name
+                    ));
+                }
+                Ok(Target::Command(Command::Pod(pod)))
+            }
             _ => panic!("Unknown command type, got <{}>", command.type_tag()),
         }
     }
@@ -445,6 +717,7 @@ pub enum CommandLookupResult<'a> {
 enum ConfigWrapper {
     Exec(ConfigExecCommand),
     Container(ConfigContainerCommand),
+    Pod(ConfigPod),
     ContainerBuild(ConfigContainerBuild),
     ExecArtifact(ConfigExecArtifact),
 }
@@ -454,6 +727,7 @@ impl ConfigWrapper {
         match self {
             Self::Exec(command) => &command.target_info,
             Self::Container(command) => &command.target_info,
+            Self::Pod(command) => &command.target_info,
             Self::ContainerBuild(command) => &command.target_info,
             Self::ExecArtifact(command) => &command.target_info,
         }
@@ -463,6 +737,7 @@ impl ConfigWrapper {
         match self {
             Self::Exec(command) => Some(&command.command_info),
             Self::Container(command) => Some(&command.command_info),
+            Self::Pod(command) => Some(&command.command_info),
             Self::ContainerBuild(_) => None,
             Self::ExecArtifact(_) => None,
         }
@@ -472,11 +747,22 @@ impl ConfigWrapper {
         match self {
             Self::Exec(_) => None,
             Self::Container(_) => None,
+            Self::Pod(_) => None,
             Self::ContainerBuild(command) => Some(&command.artifact_info),
             Self::ExecArtifact(command) => Some(&command.artifact_info),
         }
     }
 
+    fn definition(&self) -> Result<toml::Table> {
+        Ok(match self {
+            Self::Exec(command) => toml::Table::try_from(command)?,
+            Self::Container(command) => toml::Table::try_from(command)?,
+            Self::Pod(command) => toml::Table::try_from(command)?,
+            Self::ContainerBuild(command) => toml::Table::try_from(command)?,
+            Self::ExecArtifact(command) => toml::Table::try_from(command)?,
+        })
+    }
+
     fn extends(&self) -> Option<String> {
         self.target_info().extends.clone()
     }
@@ -485,6 +771,7 @@ impl ConfigWrapper {
         match self {
             Self::Exec(c) => c.type_tag(),
             Self::Container(c) => c.type_tag(),
+            Self::Pod(c) => c.type_tag(),
             Self::ContainerBuild(c) => c.type_tag(),
             Self::ExecArtifact(c) => c.type_tag(),
         }
@@ -494,6 +781,7 @@ impl ConfigWrapper {
         match self {
             Self::Exec(c) => c.is_artifact(),
             Self::Container(c) => c.is_artifact(),
+            Self::Pod(c) => c.is_artifact(),
             Self::ContainerBuild(c) => c.is_artifact(),
             Self::ExecArtifact(c) => c.is_artifact(),
         }
@@ -501,115 +789,262 @@ impl ConfigWrapper {
 }
 
 impl Context {
+    /// The root directory of the project, where the config file is
+    pub fn root(&self) -> std::path::PathBuf {
+        std::path::Path::new(self.config_path.as_str())
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default()
+    }
+
     pub fn from_config(config: &Config, path: String) -> Result<Context> {
+        let project = Project {
+            name: String::new(),
+            config: config.clone(),
+        };
+        Self::from_projects(&[project], path, String::new())
+    }
+
+    /// Build the context for a root project and the projects nested under it, with
+    /// names that don't say which project they are in looked up in `current_project`
+    /// first.
+    pub fn from_projects(
+        projects: &[Project],
+        path: String,
+        current_project: String,
+    ) -> Result<Context> {
         let mut context = Context {
             config_path: path,
+            current_project,
             ..Default::default()
         };
-        if let Some(ref globals) = config.globals {
-            context.globals = globals.clone();
-        }
         let mut commands = HashMap::new();
-        let mut name_map = HashMap::new();
-        if let Some(ref c) = config.command {
-            for (name, config_command) in c.exec.iter().flatten() {
-                let fully_qualified_name = FullyQualifiedName {
-                    tag: config_command.type_tag().to_string(),
-                    name: name.clone(),
-                };
-                commands.insert(
-                    fully_qualified_name.clone(),
-                    ConfigWrapper::Exec(config_command.clone()),
-                );
-                name_map
-                    .entry(name.clone())
-                    .or_insert_with(Vec::new)
-                    .push(fully_qualified_name.clone());
-                name_map
-                    .entry(fully_qualified_name.to_string())
-                    .or_insert_with(Vec::new)
-                    .push(fully_qualified_name);
+        let mut local_names = HashMap::new();
+        let mut qualified_names = NameMap::new();
+        for project in projects {
+            let config = &project.config;
+            context
+                .unknown_keys
+                .extend(config.unknown_keys.iter().cloned());
+            context.sources.extend(&config.sources);
+            for (key, path) in config.origins.iter() {
+                if key.starts_with("command.") || key.starts_with("artifact.") {
+                    let name = FullyQualifiedName {
+                        project: project.name.clone(),
+                        ..FullyQualifiedName::from_string(key)
+                    };
+                    context.origins.insert(name, path.clone());
+                }
             }
-            for (name, config_command) in c.container.iter().flatten() {
-                let fully_qualified_name = FullyQualifiedName {
-                    tag: config_command.type_tag().to_string(),
-                    name: name.clone(),
-                };
-                commands.insert(
-                    fully_qualified_name.clone(),
-                    ConfigWrapper::Container(config_command.clone()),
-                );
-                name_map
-                    .entry(name.clone())
-                    .or_insert_with(Vec::new)
-                    .push(fully_qualified_name.clone());
-                name_map
-                    .entry(fully_qualified_name.to_string())
-                    .or_insert_with(Vec::new)
-                    .push(fully_qualified_name);
+            if let Some(ref globals) = config.globals {
+                if project.name.is_empty() {
+                    context.globals = globals.clone();
+                } else {
+                    context
+                        .project_globals
+                        .insert(project.name.clone(), globals.clone());
+                }
             }
-        }
-        if let Some(ref c) = config.artifact {
-            for (name, config_command) in c.container_image.iter().flatten() {
-                let fully_qualified_name = FullyQualifiedName {
-                    tag: config_command.type_tag().to_string(),
-                    name: name.clone(),
-                };
-                commands.insert(
-                    fully_qualified_name.clone(),
-                    ConfigWrapper::ContainerBuild(config_command.clone()),
-                );
-                name_map
-                    .entry(name.clone())
-                    .or_insert_with(Vec::new)
-                    .push(fully_qualified_name.clone());
-                name_map
-                    .entry(fully_qualified_name.to_string())
-                    .or_insert_with(Vec::new)
-                    .push(fully_qualified_name);
+            if let Some(ref env) = config.env {
+                context.env.insert(project.name.clone(), env.clone());
             }
-            for (name, config_command) in c.exec.iter().flatten() {
-                let fully_qualified_name = FullyQualifiedName {
-                    tag: config_command.type_tag().to_string(),
-                    name: name.clone(),
-                };
-                commands.insert(
-                    fully_qualified_name.clone(),
-                    ConfigWrapper::ExecArtifact(config_command.clone()),
-                );
-                name_map
-                    .entry(name.clone())
-                    .or_insert_with(Vec::new)
-                    .push(fully_qualified_name.clone());
-                name_map
-                    .entry(fully_qualified_name.to_string())
-                    .or_insert_with(Vec::new)
-                    .push(fully_qualified_name);
+            if let Some(ref env_file) = config.env_file {
+                context
+                    .env_files
+                    .insert(project.name.clone(), env_file.clone());
+            }
+            let mut names = Names {
+                project: project.name.as_str(),
+                commands: &mut commands,
+                local: local_names.entry(project.name.clone()).or_default(),
+                qualified: &mut qualified_names,
+            };
+            if let Some(ref c) = config.command {
+                for (name, config_command) in c.exec.iter().flatten() {
+                    names.register(
+                        name,
+                        config_command.type_tag(),
+                        ConfigWrapper::Exec(config_command.clone()),
+                    );
+                }
+                for (name, config_command) in c.container.iter().flatten() {
+                    names.register(
+                        name,
+                        config_command.type_tag(),
+                        ConfigWrapper::Container(config_command.clone()),
+                    );
+                }
+                for (name, config_command) in c.pod.iter().flatten() {
+                    names.register(
+                        name,
+                        config_command.type_tag(),
+                        ConfigWrapper::Pod(config_command.clone()),
+                    );
+                }
+            }
+            if let Some(ref c) = config.artifact {
+                for (name, config_command) in c.container_image.iter().flatten() {
+                    names.register(
+                        name,
+                        config_command.type_tag(),
+                        ConfigWrapper::ContainerBuild(config_command.clone()),
+                    );
+                }
+                for (name, config_command) in c.exec.iter().flatten() {
+                    names.register(
+                        name,
+                        config_command.type_tag(),
+                        ConfigWrapper::ExecArtifact(config_command.clone()),
+                    );
+                }
             }
         }
+        let name_maps = local_names
+            .into_iter()
+            .map(|(project, mut local)| {
+                for (name, targets) in qualified_names.iter() {
+                    local
+                        .entry(name.clone())
+                        .or_default()
+                        .extend(targets.iter().cloned());
+                }
+                (project, local)
+            })
+            .collect::<HashMap<_, _>>();
         for (name, command) in commands.iter() {
-            if let Some(ref variables) = command.target_info().variables {
-                context.variables.insert(name.clone(), (*variables).clone());
-            }
+            context
+                .definitions
+                .insert(name.clone(), command.definition()?);
         }
-        context.resolve_extends(&commands, &name_map)?;
+        let config_errors = projects
+            .iter()
+            .flat_map(|p| p.config.errors.iter().cloned())
+            .collect::<Vec<_>>();
+        context.resolve_extends(&commands, &name_maps, &config_errors)?;
+        // Use the variables once extends are applied and the names of targets in them are
+        // resolved, so that variables that refer to other variables can be expanded
+        context.variables = context
+            .targets
+            .iter()
+            .map(|(name, target)| (name.clone(), target.target_info().variables.clone()))
+            .collect();
         Ok(context)
     }
 
+    /// Resolve the targets, with their errors reported along with `config_errors`, those
+    /// found loading the config files. Targets are validated again once what they extend
+    /// is applied, so errors at a place that the config files already have one are left out.
     fn resolve_extends(
         &mut self,
         commands: &HashMap<FullyQualifiedName, ConfigWrapper>,
-        name_map: &HashMap<String, Vec<FullyQualifiedName>>,
+        name_maps: &HashMap<String, NameMap>,
+        config_errors: &[Diagnostic],
     ) -> Result<()> {
-        for (name, command) in commands.iter() {
-            self.targets.insert(
+        let mut names = commands.keys().collect::<Vec<_>>();
+        names.sort();
+        let mut errors = config_errors
+            .iter()
+            .cloned()
+            .map(anyhow::Error::from)
+            .collect::<Vec<_>>();
+        for name in names {
+            match resolve_extends(
                 name.clone(),
-                resolve_extends(name.clone(), command, commands, name_map)?,
-            );
+                &commands[name],
+                commands,
+                name_maps,
+                &self.origins,
+                &self.sources,
+            ) {
+                Ok(target) => {
+                    self.targets.insert(name.clone(), target);
+                }
+                Err(e) => errors.extend(
+                    Diagnostics::of(&e)
+                        .into_iter()
+                        .filter(|d| {
+                            d.location.is_none()
+                                || !config_errors.iter().any(|c| c.location == d.location)
+                        })
+                        .map(anyhow::Error::from),
+                ),
+            }
+        }
+        Diagnostics::check(errors)?;
+        for target in self.targets.values() {
+            if let Ok(pod) = target.command().and_then(|c| c.pod()) {
+                pod.members_in_start_order(self)?;
+            }
         }
         Ok(())
     }
 
+    /// The environment for a target. Variables are taken from, in increasing order of
+    /// precedence, the environment pls is run in if they match `pass_env`, the top-level env
+    /// files and `env` of the root project and then the target's project, and then the
+    /// target's own env files and `env`.
+    pub fn resolve_env(
+        &self,
+        name: &FullyQualifiedName,
+        env_info: &EnvInfo,
+        outputs: &OutputsManager,
+    ) -> Result<Environment> {
+        let mut vars = vec![];
+        let pass_env = env_info
+            .pass_env
+            .iter()
+            .map(|p| {
+                glob::Pattern::new(p)
+                    .map_err(|e| anyhow!("Invalid pass_env <{}> for <{}>: {}", p, name, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut passed = std::env::vars()
+            .filter(|(key, _)| pass_env.iter().any(|p| p.matches(key)))
+            .collect::<Vec<_>>();
+        passed.sort();
+        for (key, value) in passed {
+            vars.push(format!("{}={}", key, value));
+        }
+        let mut projects = vec![""];
+        if !name.project.is_empty() {
+            projects.push(name.project.as_str());
+        }
+        let mut defined = HashMap::new();
+        let global_env_files = projects
+            .iter()
+            .flat_map(|p| self.env_files.get(*p).into_iter().flatten());
+        vars.extend(
+            load_env_files(global_env_files, &mut defined)
+                .map_err(|e| anyhow!("Error loading env files for <{}>: {}", name, e))?,
+        );
+        for project in projects.iter() {
+            let mut env = self
+                .env
+                .get(*project)
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            env.sort();
+            for (key, value) in env {
+                let value = self.resolve_substitutions(value, name, outputs)?;
+                defined.insert(key.clone(), value.clone());
+                vars.push(format!("{}={}", key, value));
+            }
+        }
+        vars.extend(
+            load_env_files(env_info.env_file.iter(), &mut defined)
+                .map_err(|e| anyhow!("Error loading env files for <{}>: {}", name, e))?,
+        );
+        for e in env_info.env.iter() {
+            vars.push(self.resolve_substitutions(e, name, outputs)?);
+        }
+        vars.extend(self.env_overrides.iter().cloned());
+        Ok(Environment {
+            vars,
+            clear: env_info.clear_env,
+        })
+    }
+
     pub fn resolve_substitutions(
         &self,
         command: &str,
@@ -619,6 +1054,35 @@ impl Context {
         self.resolve_substitutions_inner(command, this_target_name, outputs, None, &None)
     }
 
+    /// The value of the variable `key` of a target, with any variables it uses expanded, which
+    /// is an error if that needs the output of a command as commands aren't run for it
+    pub fn expand_variable(
+        &self,
+        this_target_name: &FullyQualifiedName,
+        key: &str,
+        outputs: &OutputsManager,
+    ) -> Result<Option<String>> {
+        let mut expander = Expander {
+            context: self,
+            target: this_target_name,
+            outputs,
+            args: "",
+            named_args: &HashMap::new(),
+            replaced_args: false,
+            chain: vec![],
+            frames: vec![],
+            cacheable: true,
+            run_commands: false,
+        };
+        expander.expand(
+            format!("{}.{}", this_target_name, key),
+            &Scope::Target(this_target_name.clone()),
+            self.variables
+                .get(this_target_name)
+                .and_then(|v| v.get(key)),
+        )
+    }
+
     fn resolve_substitutions_inner(
         &self,
         command: &str,
@@ -631,73 +1095,181 @@ impl Context {
             "Resolving variables in <{}> for <{}>",
             command, this_target_name
         );
+        let command_args = self
+            .targets
+            .get(this_target_name)
+            .and_then(|t| t.command_info())
+            .map(|c| &c.args);
+        let declared_args = command_args.filter(|a| !a.declared.is_empty());
+        let named_args = match (declared_args, &args) {
+            (Some(declared), Some(args)) => {
+                let target_info = self.targets[this_target_name].target_info();
+                arguments::parse(target_info, declared, args)
+                    .map_err(|e| anyhow!("Invalid arguments for <{}>: {}", this_target_name, e))?
+            }
+            _ => HashMap::new(),
+        };
         let escaped_args_str = if let Some(ref args) = args {
-            if args.is_empty() {
-                default_args.clone().unwrap_or_default()
+            if declared_args.is_some() {
+                "".to_string()
             } else {
+                // Fixed arguments come first, then those given, or the defaults if none are
+                let fixed_args = command_args.map(|a| a.fixed.as_slice()).unwrap_or_default();
                 let mut escaped_args = vec![];
-                for arg in args {
+                for arg in fixed_args.iter().chain(args.iter()) {
                     escaped_args.push(
                         escape_string(arg)
                             .map_err(|e| anyhow!("While escaping argument <{}>: {}", arg, e))?,
                     );
                 }
+                if args.is_empty() {
+                    escaped_args.extend(default_args.clone());
+                }
                 escaped_args.join(" ")
             }
         } else {
             "".to_string()
         };
-        let mut replaced_args = false;
-        let mut resolved = command.to_string();
-        let mut index = 0;
-        while index < command.len() {
-            let res = command[index..].find('{').and_then(|found| {
-                command[index + found..].find('}').map(|end_index| {
-                    let variable = &command[index + found + 1..index + found + end_index];
-                    debug!("Found variable <{}>", variable);
-                    index += found + end_index + 1;
-                    let replacement = match Variable::from_string(variable)? {
-                        Variable::Simple(key) => {
-                            if key == "args" {
-                                replaced_args = true;
-                                Some(&escaped_args_str)
-                            } else {
-                                self.variables
-                                    .get(this_target_name)
-                                    .and_then(|variables| variables.get(&key))
-                            }
-                        }
-                        Variable::Global(key) => self.globals.get(&key),
-                        Variable::Output(target_name, key) => outputs
-                            .get(&FullyQualifiedName::from_string(target_name.as_str()), &key),
-                        Variable::Ref(target_name, key) => self
-                            .variables
-                            .get(&FullyQualifiedName::from_string(target_name.as_str()))
-                            .and_then(|variables| variables.get(&key)),
-                    };
-                    if let Some(replacement) = replacement {
-                        let new_resolved =
-                            resolved.replace(format!("{{{}}}", variable).as_str(), replacement);
-                        if new_resolved != resolved {
-                            debug!("Resolved variable <{}> to <{}>", variable, replacement);
-                        }
-                        resolved = new_resolved;
-                        Ok(())
-                    } else {
-                        Err(anyhow!("Variable <{}> not found", variable))
+        let mut expander = Expander {
+            context: self,
+            target: this_target_name,
+            outputs,
+            args: escaped_args_str.as_str(),
+            named_args: &named_args,
+            replaced_args: false,
+            chain: vec![],
+            frames: vec![],
+            cacheable: true,
+            run_commands: true,
+        };
+        let resolved = expander.render(command, &Scope::Target(this_target_name.clone()))?;
+        // Arguments that are declared are only used where the command refers to them
+        if !expander.replaced_args && args.is_some() && declared_args.is_none() {
+            return Ok(format!("{} {}", resolved, escaped_args_str));
+        }
+        Ok(resolved)
+    }
+
+    /// The output of the command of a variable, which is run the first time it is needed
+    pub fn run_variable_command(&self, command: &str) -> Result<String> {
+        if let Some(output) = self.command_outputs.lock().unwrap().get(command) {
+            return Ok(output.clone());
+        }
+        debug!("Running command <{}> for a variable", command);
+        let output = run_command_with_output(command)?;
+        self.command_outputs
+            .lock()
+            .unwrap()
+            .insert(command.to_string(), output.clone());
+        Ok(output)
+    }
+
+    /// Describe the first of the variable inputs recorded for a target that is different
+    /// now, if there is one
+    pub fn changed_variable_input(&self, recorded: &VariableInputs) -> Result<Option<String>> {
+        for id in recorded.variables.iter() {
+            if self.overrides.get(id) != recorded.overrides.get(id) {
+                return Ok(Some(format!("the override of variable <{}>", id)));
+            }
+        }
+        for (command, output) in recorded.commands.iter() {
+            if self.run_variable_command(command)? != *output {
+                return Ok(Some(format!(
+                    "the output of variable command <{}>",
+                    command
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Override variables with `--set <name>=<value>`, where the name is `globals.<key>` or
+    /// `<target>.<key>`, and set environment variables for every target with
+    /// `--env <name>=<value>`
+    pub fn apply_overrides(&mut self, set: &[String], env: &[String]) -> Result<()> {
+        for s in set {
+            let (name, value) = s
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid --set <{}>, expected <name=value>", s))?;
+            let value = value.to_string();
+            if let Some(key) = name.strip_prefix("globals.") {
+                self.globals
+                    .insert(key.to_string(), VariableValue::Value(value.clone()));
+                self.overrides.insert(name.to_string(), value.clone());
+                // Globals of a project would otherwise take precedence over the override
+                for (project, globals) in self.project_globals.iter_mut() {
+                    if globals.contains_key(key) {
+                        globals.insert(key.to_string(), VariableValue::Value(value.clone()));
+                        self.overrides.insert(
+                            format!("{}{}{}", project, PROJECT_SEPARATOR, name),
+                            value.clone(),
+                        );
                     }
-                })
-            });
-            if let Some(res) = res {
-                res?;
-            } else {
-                break;
+                }
+                continue;
             }
+            let (target, key) = name.rsplit_once('.').ok_or_else(|| {
+                anyhow!(
+                    "Invalid --set <{}>, the name must be <globals.key> or <target.key>",
+                    s
+                )
+            })?;
+            let target_name = match self.get_target(target) {
+                CommandLookupResult::Found(t) => t.target_info().name.clone(),
+                CommandLookupResult::NotFound => {
+                    return Err(anyhow!("Target <{}> not found for --set <{}>", target, s))
+                }
+                CommandLookupResult::Duplicates(mut duplicates) => {
+                    duplicates.sort();
+                    return Err(anyhow!(
+                        "Target <{}> is ambiguous for --set <{}>, possible values are <{}>",
+                        target,
+                        s,
+                        duplicates.join(", ")
+                    ));
+                }
+            };
+            self.variables
+                .entry(target_name.clone())
+                .or_default()
+                .insert(key.to_string(), VariableValue::Value(value.clone()));
+            self.overrides
+                .insert(format!("{}.{}", target_name, key), value);
         }
-        if !replaced_args && args.is_some() {
-            return Ok(format!("{} {}", resolved, escaped_args_str));
+        for e in env {
+            if !e.contains('=') {
+                return Err(anyhow!("Invalid --env <{}>, expected <name=value>", e));
+            }
+        }
+        self.env_overrides = env.to_vec();
+        Ok(())
+    }
+
+    fn pls_variable(&self, key: &str, scope: &Scope) -> Option<String> {
+        match (key, scope) {
+            ("root", _) => Some(self.root().display().to_string()),
+            ("config_path", _) => Some(self.config_path.clone()),
+            ("target", Scope::Target(name)) => Some(name.to_string()),
+            ("project", _) => Some(scope.project().to_string()),
+            _ => None,
+        }
+    }
+
+    /// Look up a `git.` variable, reading the repository the first time one is used
+    fn git_variable(&self, key: &str) -> Result<Option<String>> {
+        let git = self.git.get_or_init(|| {
+            GitInfo::read(self.root().as_path()).map_err(|e| {
+                format!(
+                    "Could not read git information for <{}>: {}",
+                    self.root().display(),
+                    e
+                )
+            })
+        });
+        match git {
+            Ok(git) => Ok(git.get(key)),
+            Err(e) => Err(anyhow!("{}", e)),
         }
-        Ok(resolved)
     }
 
     pub fn resolve_substitutions_with_args(
@@ -717,18 +1289,54 @@ impl Context {
         )
     }
 
-    pub fn get_target(&self, name: &str) -> CommandLookupResult {
+    /// Look up a target by name. Names without a project are looked up in the current
+    /// project first, and then in all projects.
+    pub fn get_target(&self, name: &str) -> CommandLookupResult<'_> {
+        if let Some((project, name)) = name.rsplit_once(PROJECT_SEPARATOR) {
+            return self.get_target_in(Some(project), name);
+        }
+        match self.get_target_in(Some(self.current_project.as_str()), name) {
+            CommandLookupResult::NotFound => self.get_target_in(None, name),
+            result => result,
+        }
+    }
+
+    /// The shortest name that `get_target` finds a target by, which is its name alone
+    /// unless another target has the same one. Targets in other projects are always given
+    /// with their project, as that is how the config refers to them.
+    pub fn shortest_name(&self, name: &FullyQualifiedName) -> String {
+        if name.project != self.current_project {
+            return name.to_string();
+        }
+        let found = |candidate: &str| {
+            matches!(
+                self.get_target(candidate),
+                CommandLookupResult::Found(t) if t.target_info().name == *name
+            )
+        };
+        let qualified = format!("{}.{}", name.tag, name.name);
+        if found(&name.name) {
+            name.name.clone()
+        } else if found(&qualified) {
+            qualified
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn get_target_in(&self, project: Option<&str>, name: &str) -> CommandLookupResult<'_> {
+        let in_project = |key: &FullyQualifiedName| project.is_none_or(|p| key.project == p);
         if name.contains('.') {
-            let (tag, name) = name.split_once('.').unwrap();
-            let fully_qualified_name = FullyQualifiedName {
-                tag: tag.to_string(),
-                name: name.to_string(),
-            };
-            return self
-                .targets
-                .get(&fully_qualified_name)
-                .map(CommandLookupResult::Found)
-                .unwrap_or(CommandLookupResult::NotFound);
+            let fully_qualified_name = FullyQualifiedName::from_string(name);
+            self.targets
+                .iter()
+                .find(|(key, _)| {
+                    in_project(key)
+                        && key.tag == fully_qualified_name.tag
+                        && key.name == fully_qualified_name.name
+                })
+                .map(|(_, target)| CommandLookupResult::Found(target))
+                .unwrap_or(CommandLookupResult::NotFound)
         } else {
             debug!(
                 "Looking up command <{}> in <{:?}>",
@@ -741,7 +1349,7 @@ impl Context {
             let duplicates = self
                 .targets
                 .keys()
-                .filter(|key| key.name == name)
+                .filter(|key| in_project(key) && key.name == name)
                 .collect::<Vec<_>>();
             if duplicates.len() > 1 {
                 return CommandLookupResult::Duplicates(
@@ -758,9 +1366,260 @@ impl Context {
             }
         }
     }
+
+    /// The targets with the given tag, ordered so that each comes after any other
+    /// tagged target that it requires, directly or indirectly.
+    pub fn targets_with_tag(&self, tag: &str) -> Vec<&Target> {
+        let mut names = self
+            .targets
+            .iter()
+            .filter(|(_, target)| target.target_info().tags.iter().any(|t| t == tag))
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        names.sort();
+        let mut ordered = vec![];
+        let mut visited = HashSet::new();
+        for name in names.iter() {
+            self.visit_tagged(name, tag, &mut visited, &mut ordered);
+        }
+        ordered
+    }
+
+    fn visit_tagged<'a>(
+        &'a self,
+        name: &FullyQualifiedName,
+        tag: &str,
+        visited: &mut HashSet<FullyQualifiedName>,
+        ordered: &mut Vec<&'a Target>,
+    ) {
+        if !visited.insert(name.clone()) {
+            return;
+        }
+        if let Some(target) = self.targets.get(name) {
+            for required in target.target_info().requires.iter() {
+                self.visit_tagged(required, tag, visited, ordered);
+            }
+            if target.target_info().tags.iter().any(|t| t == tag) {
+                ordered.push(target);
+            }
+        }
+    }
+}
+
+/// Where a variable is being expanded, which decides what the variables in its value
+/// refer to
+#[derive(Debug, Clone)]
+enum Scope {
+    Target(FullyQualifiedName),
+    /// The globals of a project
+    Globals(String),
+}
+
+impl Scope {
+    fn project(&self) -> &str {
+        match self {
+            Self::Target(name) => name.project.as_str(),
+            Self::Globals(project) => project.as_str(),
+        }
+    }
+}
+
+/// Expands the variables in a string, along with any variables in their values
+struct Expander<'a> {
+    context: &'a Context,
+    /// The target the string being expanded belongs to
+    target: &'a FullyQualifiedName,
+    outputs: &'a OutputsManager,
+    args: &'a str,
+    /// The values of the arguments the target declares
+    named_args: &'a HashMap<String, String>,
+    replaced_args: bool,
+    /// The variables currently being expanded, to detect cycles
+    chain: Vec<String>,
+    /// The inputs of each of the variables currently being expanded, which are cached
+    /// along with its value
+    frames: Vec<VariableInputs>,
+    /// Whether the current expansion only depends on the config, and so can be cached
+    cacheable: bool,
+    /// Whether the commands of variables are run, so that their output can be used
+    run_commands: bool,
+}
+
+impl Expander<'_> {
+    fn render(&mut self, input: &str, scope: &Scope) -> Result<String> {
+        Template::parse(input)?.render(|variable| {
+            let replacement = self.lookup(variable, scope)?;
+            if let Some(ref replacement) = replacement {
+                debug!("Resolved variable <{}> to <{}>", variable, replacement);
+            }
+            Ok(replacement)
+        })
+    }
+
+    fn lookup(&mut self, variable: &str, scope: &Scope) -> Result<Option<String>> {
+        let context = self.context;
+        match Variable::from_string(variable)? {
+            Variable::Simple(key) if key == "args" => {
+                self.replaced_args = true;
+                self.cacheable = false;
+                Ok(Some(self.args.to_string()))
+            }
+            Variable::Simple(key) => match scope {
+                Scope::Target(name) => self.expand(
+                    format!("{}.{}", name, key),
+                    scope,
+                    context.variables.get(name).and_then(|v| v.get(&key)),
+                ),
+                Scope::Globals(_) => Ok(None),
+            },
+            Variable::Global(key) => {
+                let project = scope.project();
+                if let Some(value) = context
+                    .project_globals
+                    .get(project)
+                    .and_then(|globals| globals.get(&key))
+                {
+                    self.expand(
+                        format!("{}{}globals.{}", project, PROJECT_SEPARATOR, key),
+                        &Scope::Globals(project.to_string()),
+                        Some(value),
+                    )
+                } else {
+                    self.expand(
+                        format!("globals.{}", key),
+                        &Scope::Globals(String::new()),
+                        context.globals.get(&key),
+                    )
+                }
+            }
+            Variable::Env(key) => Ok(std::env::var(key).ok()),
+            Variable::Pls(key) => Ok(context.pls_variable(&key, scope)),
+            Variable::Git(key) => context.git_variable(&key),
+            Variable::Arg(key) => {
+                self.cacheable = false;
+                self.named_args
+                    .get(&key)
+                    .map(|value| {
+                        escape_string(value)
+                            .map_err(|e| anyhow!("While escaping argument <{}>: {}", key, e))
+                    })
+                    .transpose()
+            }
+            Variable::Output(target_name, key) => {
+                self.cacheable = false;
+                Ok(self
+                    .outputs
+                    .get(&FullyQualifiedName::from_string(target_name.as_str()), &key)
+                    .cloned())
+            }
+            Variable::Ref(target_name, key) => {
+                let name = FullyQualifiedName::from_string(target_name.as_str());
+                let value = context.variables.get(&name).and_then(|v| v.get(&key));
+                self.expand(format!("{}.{}", name, key), &Scope::Target(name), value)
+            }
+        }
+    }
+
+    /// Expand the variables in the value of the variable `id`, using the cached expansion
+    /// if there is one
+    fn expand(
+        &mut self,
+        id: String,
+        scope: &Scope,
+        value: Option<&VariableValue>,
+    ) -> Result<Option<String>> {
+        self.record_input(&id);
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let cached = self.context.expanded.lock().unwrap().get(&id).cloned();
+        if let Some(cached) = cached {
+            self.add_inputs(&cached.inputs);
+            return Ok(Some(cached.value));
+        }
+        if let Some(start) = self.chain.iter().position(|c| *c == id) {
+            let mut cycle = self.chain[start..].to_vec();
+            cycle.push(id);
+            return Err(anyhow!(
+                "Variables refer to each other in a cycle <{}>",
+                cycle.join(" -> ")
+            ));
+        }
+        self.chain.push(id.clone());
+        self.frames.push(VariableInputs::default());
+        let outer_cacheable = std::mem::replace(&mut self.cacheable, true);
+        let expanded = match value {
+            VariableValue::Value(value) => self.render(value, scope),
+            VariableValue::Command { command } => self
+                .render(command, scope)
+                .and_then(|command| self.run_command(&id, command)),
+        };
+        self.chain.pop();
+        let inputs = self.frames.pop().unwrap_or_default();
+        // The variable that uses this one depends on the same inputs
+        if let Some(outer) = self.frames.last_mut() {
+            outer.extend(&inputs);
+        }
+        let expanded = expanded?;
+        if self.cacheable {
+            self.context.expanded.lock().unwrap().insert(
+                id,
+                Expansion {
+                    value: expanded.clone(),
+                    inputs,
+                },
+            );
+        }
+        self.cacheable &= outer_cacheable;
+        Ok(Some(expanded))
+    }
+    fn run_command(&mut self, id: &str, command: String) -> Result<String> {
+        if !self.run_commands {
+            return Err(anyhow!(
+                "Variable <{}> is the output of <{}>, which is only run when the target is",
+                id,
+                command
+            ));
+        }
+        let output = self
+            .context
+            .run_variable_command(&command)
+            .map_err(|e| anyhow!("Error running <{}> for variable <{}>: {}", command, id, e))?;
+        self.add_inputs(&VariableInputs {
+            commands: BTreeMap::from([(command, output.clone())]),
+            ..Default::default()
+        });
+        Ok(output)
+    }
+
+    fn record_input(&mut self, id: &str) {
+        let mut inputs = VariableInputs {
+            variables: BTreeSet::from([id.to_string()]),
+            ..Default::default()
+        };
+        if let Some(value) = self.context.overrides.get(id) {
+            inputs.overrides.insert(id.to_string(), value.clone());
+        }
+        self.add_inputs(&inputs);
+    }
+
+    /// Record inputs against the target, and the variable being expanded if there is one
+    fn add_inputs(&mut self, inputs: &VariableInputs) {
+        self.context
+            .used_inputs
+            .lock()
+            .unwrap()
+            .entry(self.target.clone())
+            .or_default()
+            .extend(inputs);
+        if let Some(frame) = self.frames.last_mut() {
+            frame.extend(inputs);
+        }
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
     use crate::targets::command::exec::ExecCommand;
@@ -782,21 +1641,20 @@ mod tests {
             .globals
             .as_mut()
             .unwrap()
-            .insert("key".to_string(), "value".to_string());
+            .insert("key".to_string(), "value".into());
         let context = Context::from_config(&config, "test".to_string()).unwrap();
         assert_eq!(context.variables.len(), 0);
         assert_eq!(context.globals.len(), 1);
-        assert_eq!(context.globals.get("key"), Some(&"value".to_string()));
+        assert_eq!(context.globals.get("key"), Some(&"value".into()));
     }
 
     #[test]
     fn resolve_substitutions_with_variable() {
         let mut context = Context::default();
         let outputs = OutputsManager::default();
-        context
-            .globals
-            .insert("key".to_string(), "value".to_string());
+        context.globals.insert("key".to_string(), "value".into());
         let qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "command".to_string(),
             name: "foo".to_string(),
         };
@@ -811,6 +1669,7 @@ mod tests {
         let mut context = Context::default();
         let mut outputs = OutputsManager::default();
         let qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: ConfigExecCommand::tag().to_string(),
             name: "foo".to_string(),
         };
@@ -821,16 +1680,21 @@ mod tests {
                 requires: vec![],
                 variables: HashMap::new(),
                 description: None,
+                tags: vec![],
+                extends: None,
             },
-            command_info: CommandInfo { daemon: false },
+            command_info: CommandInfo::default(),
             command: "echo {foo.output.key}".to_string(),
             default_args: None,
-            env: vec![],
+            env_info: EnvInfo::default(),
+            cwd: None,
+            default_cwd: None,
         };
         context
             .targets
             .insert(qualified_name, Target::Command(Command::Exec(cmd)));
         let this_target = FullyQualifiedName {
+            project: String::new(),
             tag: ConfigContainerCommand::tag().to_string(),
             name: "bar".to_string(),
         };
@@ -844,6 +1708,7 @@ mod tests {
     fn resolve_substitutions_with_no_match() {
         let context = Context::default();
         let qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "command".to_string(),
             name: "foo".to_string(),
         };
@@ -863,6 +1728,7 @@ mod tests {
     fn resolve_substitutions_for_current_target_name() {
         let mut context = Context::default();
         let qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "command".to_string(),
             name: "foo".to_string(),
         };
@@ -870,17 +1736,220 @@ mod tests {
             .variables
             .entry(qualified_name.clone())
             .or_insert(HashMap::new());
-        test_variables.insert("key".to_string(), "value".to_string());
+        test_variables.insert("key".to_string(), "value".into());
         let resolved = context
             .resolve_substitutions("echo {key}", &qualified_name, &OutputsManager::default())
             .unwrap();
         assert_eq!(resolved, "echo value");
     }
 
+    #[test]
+    fn resolve_substitutions_with_builtins() {
+        let context = Context {
+            config_path: "/project/pls.toml".to_string(),
+            ..Default::default()
+        };
+        let qualified_name = FullyQualifiedName {
+            project: String::new(),
+            tag: "command.exec".to_string(),
+            name: "foo".to_string(),
+        };
+        std::env::set_var("PLS_TEST_BUILTIN", "from env");
+        let resolved = context
+            .resolve_substitutions(
+                "{env.PLS_TEST_BUILTIN} {pls.root} {pls.config_path} {pls.target}",
+                &qualified_name,
+                &OutputsManager::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            resolved,
+            "from env /project /project/pls.toml command.exec.foo"
+        );
+        let err = context
+            .resolve_substitutions("{pls.unknown}", &qualified_name, &OutputsManager::default())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Variable <pls.unknown> not found");
+    }
+
+    #[test]
+    fn resolve_substitutions_with_git() {
+        let context = Context::default();
+        context
+            .git
+            .set(Ok(GitInfo {
+                sha: "0123456789abcdef".to_string(),
+                short_sha: "0123456".to_string(),
+                branch: "main".to_string(),
+                dirty: true,
+            }))
+            .unwrap();
+        let resolved = context
+            .resolve_substitutions(
+                "myimage:{git.short_sha} {git.branch} {git.dirty}",
+                &FullyQualifiedName::from_string("command.exec.foo"),
+                &OutputsManager::default(),
+            )
+            .unwrap();
+        assert_eq!(resolved, "myimage:0123456 main true");
+    }
+
+    fn context_with_variables(variables: &[(&str, &str)]) -> (Context, FullyQualifiedName) {
+        let mut context = Context::default();
+        let qualified_name = FullyQualifiedName::from_string("command.exec.foo");
+        context.variables.insert(
+            qualified_name.clone(),
+            variables
+                .iter()
+                .map(|(k, v)| (k.to_string(), (*v).into()))
+                .collect(),
+        );
+        (context, qualified_name)
+    }
+
+    #[test]
+    fn resolve_substitutions_recursively() {
+        let (mut context, qualified_name) =
+            context_with_variables(&[("out", "{dir}/bin"), ("dir", "target/{globals.profile}")]);
+        context
+            .globals
+            .insert("profile".to_string(), "{globals.mode}-x86".into());
+        context.globals.insert("mode".to_string(), "release".into());
+        let resolved = context
+            .resolve_substitutions("ls {out}", &qualified_name, &OutputsManager::default())
+            .unwrap();
+        assert_eq!(resolved, "ls target/release-x86/bin");
+        assert_eq!(
+            context
+                .expanded
+                .lock()
+                .unwrap()
+                .get("command.exec.foo.dir")
+                .map(|e| e.value.as_str()),
+            Some("target/release-x86")
+        );
+    }
+
+    #[test]
+    fn resolve_substitutions_does_not_cache_outputs() {
+        let (context, qualified_name) =
+            context_with_variables(&[("path", "{command.exec.foo.output.key}")]);
+        let mut outputs = OutputsManager::default();
+        outputs.store_output(qualified_name.clone(), "key", "first");
+        let resolved = context
+            .resolve_substitutions("{path}", &qualified_name, &outputs)
+            .unwrap();
+        assert_eq!(resolved, "first");
+        outputs.store_output(qualified_name.clone(), "key", "second");
+        let resolved = context
+            .resolve_substitutions("{path}", &qualified_name, &outputs)
+            .unwrap();
+        assert_eq!(resolved, "second");
+    }
+
+    #[test]
+    fn resolve_substitutions_runs_commands_once() {
+        let (mut context, qualified_name) = context_with_variables(&[]);
+        context.globals.insert(
+            "version".to_string(),
+            VariableValue::Command {
+                command: "echo 1.0".to_string(),
+            },
+        );
+        context
+            .command_outputs
+            .lock()
+            .unwrap()
+            .insert("echo 1.0".to_string(), "cached".to_string());
+        let resolved = context
+            .resolve_substitutions(
+                "v{globals.version}",
+                &qualified_name,
+                &OutputsManager::default(),
+            )
+            .unwrap();
+        assert_eq!(resolved, "vcached");
+        let used_inputs = context.used_inputs.lock().unwrap();
+        let inputs = used_inputs.get(&qualified_name).unwrap();
+        assert_eq!(
+            inputs.commands,
+            BTreeMap::from([("echo 1.0".to_string(), "cached".to_string())])
+        );
+        assert_eq!(
+            inputs.variables,
+            BTreeSet::from(["globals.version".to_string()])
+        );
+    }
+
+    #[test]
+    fn resolve_substitutions_records_inputs_of_cached_variables() {
+        let mut context = Context::default();
+        context.globals.insert("registry".to_string(), "r1".into());
+        context
+            .globals
+            .insert("img".to_string(), "{globals.registry}/img".into());
+        context
+            .apply_overrides(&["globals.registry=r2".to_string()], &[])
+            .unwrap();
+        let a = FullyQualifiedName::from_string("artifact.exec.a");
+        let b = FullyQualifiedName::from_string("artifact.exec.b");
+        for name in [&a, &b] {
+            let resolved = context
+                .resolve_substitutions("{globals.img}", name, &OutputsManager::default())
+                .unwrap();
+            assert_eq!(resolved, "r2/img");
+        }
+        let used_inputs = context.used_inputs.lock().unwrap();
+        assert_eq!(used_inputs.get(&a), used_inputs.get(&b));
+        assert_eq!(
+            used_inputs.get(&b).unwrap().overrides,
+            BTreeMap::from([("globals.registry".to_string(), "r2".to_string())])
+        );
+    }
+
+    #[test]
+    fn apply_overrides_to_project_globals() {
+        let mut context = Context::default();
+        context.globals.insert("mode".to_string(), "debug".into());
+        context.project_globals.insert(
+            "web".to_string(),
+            HashMap::from([("mode".to_string(), "dev".into())]),
+        );
+        context
+            .apply_overrides(&["globals.mode=release".to_string()], &[])
+            .unwrap();
+        let resolved = context
+            .resolve_substitutions(
+                "{globals.mode}",
+                &FullyQualifiedName::from_string("web:command.exec.foo"),
+                &OutputsManager::default(),
+            )
+            .unwrap();
+        assert_eq!(resolved, "release");
+        assert_eq!(
+            context.overrides.get("web:globals.mode"),
+            Some(&"release".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_substitutions_with_cycle() {
+        let (context, qualified_name) =
+            context_with_variables(&[("a", "{b}"), ("b", "x{c}"), ("c", "{a}")]);
+        let err = context
+            .resolve_substitutions("{a}", &qualified_name, &OutputsManager::default())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Variables refer to each other in a cycle <command.exec.foo.a -> command.exec.foo.b -> command.exec.foo.c -> command.exec.foo.a>"
+        );
+    }
+
     #[test]
     fn resolve_substitutions_with_args_replaces_args() {
         let context = Context::default();
         let qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "command".to_string(),
             name: "foo".to_string(),
         };
@@ -900,6 +1969,7 @@ mod tests {
     fn resolve_substitutions_with_args_appends_args() {
         let context = Context::default();
         let qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "command".to_string(),
             name: "foo".to_string(),
         };
@@ -919,6 +1989,7 @@ mod tests {
     fn resolve_substitutions_with_args_escapes_args() {
         let context = Context::default();
         let qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "command".to_string(),
             name: "foo".to_string(),
         };
@@ -938,6 +2009,7 @@ mod tests {
     fn resolve_substitutions_with_args_uses_default_args() {
         let context = Context::default();
         let qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "command".to_string(),
             name: "foo".to_string(),
         };
@@ -955,12 +2027,22 @@ mod tests {
 
     #[test]
     fn test_get_lookup_name() {
-        let name = get_lookup_name("tag.name".to_string(), "default".to_string());
+        let name = get_lookup_name("tag.name".to_string(), "default".to_string(), "");
+        assert_eq!(name.project, "");
         assert_eq!(name.tag, "tag");
         assert_eq!(name.name, "name");
-        let name = get_lookup_name("name".to_string(), "default".to_string());
+        let name = get_lookup_name("name".to_string(), "default".to_string(), "");
         assert_eq!(name.tag, "default");
         assert_eq!(name.name, "name");
+        let name = get_lookup_name("name".to_string(), "default".to_string(), "services/api");
+        assert_eq!(name.project, "services/api");
+        let name = get_lookup_name(
+            "lib:name".to_string(),
+            "default".to_string(),
+            "services/api",
+        );
+        assert_eq!(name.project, "lib");
+        assert_eq!(name.name, "name");
     }
 
     #[test]
@@ -969,6 +2051,7 @@ mod tests {
         name_map.insert(
             "a".to_string(),
             vec![FullyQualifiedName {
+                project: String::new(),
                 tag: "tag".to_string(),
                 name: "a".to_string(),
             }],
@@ -976,6 +2059,7 @@ mod tests {
         name_map.insert(
             "b".to_string(),
             vec![FullyQualifiedName {
+                project: String::new(),
                 tag: "tag".to_string(),
                 name: "b".to_string(),
             }],
@@ -993,6 +2077,7 @@ mod tests {
         name_map.insert(
             "a".to_string(),
             vec![FullyQualifiedName {
+                project: String::new(),
                 tag: "tag".to_string(),
                 name: "a".to_string(),
             }],
@@ -1001,10 +2086,12 @@ mod tests {
             "b".to_string(),
             vec![
                 FullyQualifiedName {
+                    project: String::new(),
                     tag: "tag".to_string(),
                     name: "b".to_string(),
                 },
                 FullyQualifiedName {
+                    project: String::new(),
                     tag: "tag".to_string(),
                     name: "b".to_string(),
                 },
@@ -1038,11 +2125,13 @@ mod tests {
             variables: Some(HashMap::new()),
             extends: None,
             description: Some("description".to_string()),
+            tags: Some(vec!["ci".to_string()]),
         };
         let mut name_map = HashMap::new();
         name_map.insert(
             "a".to_string(),
             vec![FullyQualifiedName {
+                project: String::new(),
                 tag: "tag".to_string(),
                 name: "a".to_string(),
             }],
@@ -1050,24 +2139,28 @@ mod tests {
         name_map.insert(
             "b".to_string(),
             vec![FullyQualifiedName {
+                project: String::new(),
                 tag: "tag".to_string(),
                 name: "b".to_string(),
             }],
         );
         let target_info = target_info_from_config(
             FullyQualifiedName {
+                project: String::new(),
                 tag: "tag".to_string(),
                 name: "name".to_string(),
             },
             &config,
             &name_map,
             None,
+            None,
         )
         .unwrap();
         assert_eq!(target_info.name.name, "name");
         assert_eq!(target_info.requires.len(), 2);
         assert_eq!(target_info.variables.len(), 0);
         assert_eq!(target_info.description, Some("description".to_string()));
+        assert_eq!(target_info.tags, vec!["ci".to_string()]);
     }
 
     #[test]
@@ -1112,6 +2205,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_variable_from_string_outputs() {
+        let variable = Variable::from_string("foo.outputs.bar").unwrap();
+        match variable {
+            Variable::Output(target, s) => {
+                assert_eq!(target, "foo");
+                assert_eq!(s, "bar");
+            }
+            _ => panic!("Expected output variable"),
+        }
+    }
+
     #[test]
     fn test_variable_from_string_ref() {
         let variable = Variable::from_string("commands.foo.bar").unwrap();
@@ -1136,12 +2241,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_variable_from_string_with_dot_in_project() {
+        match Variable::from_string("libs/v1.2:build.version").unwrap() {
+            Variable::Ref(target, key) => {
+                assert_eq!(target, "libs/v1.2:build");
+                assert_eq!(key, "version");
+            }
+            _ => panic!("Expected ref variable"),
+        }
+        match Variable::from_string("libs/v1.2:db.outputs.name").unwrap() {
+            Variable::Output(target, key) => {
+                assert_eq!(target, "libs/v1.2:db");
+                assert_eq!(key, "name");
+            }
+            _ => panic!("Expected output variable"),
+        }
+    }
+
     #[test]
     fn test_resolve_target_names_in() {
         let mut name_map = HashMap::new();
         name_map.insert(
             "foo".to_string(),
             vec![FullyQualifiedName {
+                project: String::new(),
                 tag: "command".to_string(),
                 name: "foo".to_string(),
             }],
@@ -1156,6 +2280,7 @@ mod tests {
         name_map.insert(
             "foo".to_string(),
             vec![FullyQualifiedName {
+                project: String::new(),
                 tag: "commands".to_string(),
                 name: "foo".to_string(),
             }],
@@ -1164,6 +2289,23 @@ mod tests {
         assert_eq!(resolved, "{commands.foo.output.bar}");
     }
 
+    #[test]
+    fn test_resolve_target_names_in_non_existent() {
+        let name_map = HashMap::new();
+        let resolved = resolve_target_names_in("{foo.bar}", &name_map);
+        assert_eq!(
+            resolved.unwrap_err().to_string(),
+            "Non-existent reference <foo>"
+        );
+    }
+
+    #[test]
+    fn test_resolve_target_names_in_non_existent_with_default() {
+        let name_map = HashMap::new();
+        let resolved = resolve_target_names_in("{foo.bar|default:baz}", &name_map).unwrap();
+        assert_eq!(resolved, "{foo.bar|default:baz}");
+    }
+
     #[test]
     fn test_resolve_target_names_in_with_global() {
         let name_map = HashMap::new();
@@ -1179,4 +2321,95 @@ mod tests {
     }
 
     // TODO: from_config tests
+
+    fn projects() -> Vec<Project> {
+        let root = r#"
+            [command.exec.test]
+            command = "echo root"
+        "#;
+        let api = r#"
+            [globals]
+            service = "api"
+
+            [artifact.exec.build]
+            command = "make"
+
+            [command.exec.test]
+            command = "echo {globals.service} {build.output.path}"
+            requires = ["build"]
+
+            [command.exec.deploy]
+            command = "echo {services/api:build.output.path}"
+            requires = [":test", "services/api:build"]
+        "#;
+        vec![
+            Project {
+                name: String::new(),
+                config: toml::from_str(root).unwrap(),
+            },
+            Project {
+                name: "services/api".to_string(),
+                config: toml::from_str(api).unwrap(),
+            },
+        ]
+    }
+
+    #[test]
+    fn get_target_prefers_current_project() {
+        let context =
+            Context::from_projects(&projects(), "test".to_string(), String::new()).unwrap();
+        let CommandLookupResult::Found(target) = context.get_target("test") else {
+            panic!("Expected to find <test>");
+        };
+        assert_eq!(target.target_info().name.project, "");
+        let CommandLookupResult::Found(target) = context.get_target("services/api:test") else {
+            panic!("Expected to find <services/api:test>");
+        };
+        assert_eq!(target.target_info().name.project, "services/api");
+        assert!(matches!(
+            context.get_target("build"),
+            CommandLookupResult::Found(_)
+        ));
+
+        let context =
+            Context::from_projects(&projects(), "test".to_string(), "services/api".to_string())
+                .unwrap();
+        let CommandLookupResult::Found(target) = context.get_target("test") else {
+            panic!("Expected to find <test>");
+        };
+        assert_eq!(target.target_info().name.project, "services/api");
+    }
+
+    #[test]
+    fn resolves_references_between_projects() {
+        let context =
+            Context::from_projects(&projects(), "test".to_string(), String::new()).unwrap();
+        let build = FullyQualifiedName::from_string("services/api:artifact.exec.build");
+        let deploy = FullyQualifiedName::from_string("services/api:command.exec.deploy");
+        let requires = &context.targets[&deploy].target_info().requires;
+        assert_eq!(
+            requires,
+            &vec![
+                FullyQualifiedName::from_string("command.exec.test"),
+                build.clone()
+            ]
+        );
+        let mut outputs = OutputsManager::default();
+        outputs.store_output(build, "path", "out/api");
+        let Target::Command(Command::Exec(ref exec)) = context.targets[&deploy] else {
+            panic!("Expected an exec command");
+        };
+        let resolved = context
+            .resolve_substitutions(&exec.command, &deploy, &outputs)
+            .unwrap();
+        assert_eq!(resolved, "echo out/api");
+        let test = FullyQualifiedName::from_string("services/api:command.exec.test");
+        let Target::Command(Command::Exec(ref exec)) = context.targets[&test] else {
+            panic!("Expected an exec command");
+        };
+        let resolved = context
+            .resolve_substitutions(&exec.command, &test, &outputs)
+            .unwrap();
+        assert_eq!(resolved, "echo api out/api");
+    }
 }
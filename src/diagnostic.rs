@@ -0,0 +1,291 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::config::Sources;
+
+/// An error in a config file, along with where in the file it is when that is known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+/// A span of a config file, along with the line it starts on so that it can be shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: String,
+    /// The line and column the span starts at, counting from 1
+    pub line: usize,
+    pub column: usize,
+    /// The text of the line the span starts on
+    pub text: String,
+    /// How many characters of the line the span covers
+    pub width: usize,
+}
+
+impl Location {
+    pub fn new(path: &Path, source: &str, span: Range<usize>) -> Self {
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = source[start..]
+            .find('\n')
+            .map(|i| start + i)
+            .unwrap_or(source.len());
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        let end = span.end.clamp(start, line_end);
+        Location {
+            path: path.display().to_string(),
+            line: source[..line_start].matches('\n').count() + 1,
+            column: source[line_start..start].chars().count() + 1,
+            text: text.to_string(),
+            width: source[start..end].chars().count().max(1),
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn new(message: String) -> Self {
+        Diagnostic {
+            message,
+            location: None,
+        }
+    }
+
+    /// A diagnostic for the span of `source`, the contents of the config file at `path`
+    pub fn at_span(message: String, path: &Path, source: &str, span: Option<Range<usize>>) -> Self {
+        Diagnostic {
+            message,
+            location: span.map(|span| Location::new(path, source, span)),
+        }
+    }
+
+    /// A diagnostic for the value of a key in the config file at `path`, given as the path of
+    /// keys to it from the top of the file. Keys that aren't in the file are skipped, so
+    /// that a setting which isn't there is reported at the table it would be in.
+    pub fn at_key(message: String, path: &Path, sources: &Sources, keys: &[String]) -> Self {
+        let Ok(source) = sources.read(path) else {
+            return Self::new(message);
+        };
+        let span = key_span(&source, keys);
+        Self::at_span(message, path, &source, span)
+    }
+}
+
+pub(crate) fn key_span(source: &str, keys: &[String]) -> Option<Range<usize>> {
+    let document = toml_edit::ImDocument::parse(source).ok()?;
+    let mut table: &dyn toml_edit::TableLike = document.as_table();
+    let mut found = None;
+    for key in keys {
+        let (Some(k), Some(item)) = (table.key(key), table.get(key)) else {
+            continue;
+        };
+        found = Some((k, item));
+        match item.as_table_like() {
+            Some(t) => table = t,
+            None => break,
+        }
+    }
+    let (key, item) = found?;
+    match item {
+        toml_edit::Item::Value(value) => value.span().or_else(|| key.span()),
+        _ => key.span(),
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(ref location) = self.location {
+            let gutter = " ".repeat(location.line.to_string().len());
+            let indent = location
+                .text
+                .chars()
+                .take(location.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            write!(
+                f,
+                "\n{gutter}--> {}:{}:{}\n{gutter} |\n{} | {}\n{gutter} | {}{}",
+                location.path,
+                location.line,
+                location.column,
+                location.line,
+                location.text,
+                indent,
+                "^".repeat(location.width),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Several diagnostics that are reported together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    /// Combine errors into one that reports all of them, or succeed if there are none.
+    /// Errors that are the same, such as those of a target that is extended by several
+    /// others, are only reported once.
+    pub fn check(errors: Vec<anyhow::Error>) -> anyhow::Result<()> {
+        let mut diagnostics: Vec<Diagnostic> = vec![];
+        for error in errors {
+            for diagnostic in Self::of(&error) {
+                if !diagnostics.contains(&diagnostic) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+        match diagnostics.len() {
+            0 => Ok(()),
+            1 => Err(diagnostics.remove(0).into()),
+            _ => Err(Diagnostics(diagnostics).into()),
+        }
+    }
+
+    /// The diagnostics that an error reports, which is one without a location if it isn't
+    /// a diagnostic itself
+    pub fn of(error: &anyhow::Error) -> Vec<Diagnostic> {
+        if let Some(d) = error.downcast_ref::<Diagnostics>() {
+            d.0.clone()
+        } else if let Some(d) = error.downcast_ref::<Diagnostic>() {
+            vec![d.clone()]
+        } else {
+            vec![Diagnostic::new(error.to_string())]
+        }
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Found {} errors in the config", self.0.len())?;
+        for diagnostic in self.0.iter() {
+            write!(f, "\n\n{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Fields that are flattened into the table they are in, so aren't keys in the config
+const FLATTENED_FIELDS: [&str; 4] = ["target_info", "command_info", "env_info", "artifact_info"];
+
+/// The errors of a validation, each with the path of keys to the field it is for. Items
+/// of lists and maps are given by their index.
+pub fn validation_errors(errors: &ValidationErrors) -> Vec<(Vec<String>, String)> {
+    let mut found = vec![];
+    flatten_validation_errors(errors, &mut vec![], &mut found);
+    found
+}
+
+fn flatten_validation_errors(
+    errors: &ValidationErrors,
+    path: &mut Vec<String>,
+    found: &mut Vec<(Vec<String>, String)>,
+) {
+    let mut fields = errors.errors().iter().collect::<Vec<_>>();
+    fields.sort_by_key(|(field, _)| **field);
+    for (field, kind) in fields {
+        let flattened = FLATTENED_FIELDS.contains(field);
+        if !flattened {
+            path.push(field.to_string());
+        }
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                for error in errors {
+                    found.push((path.clone(), describe(error)));
+                }
+            }
+            ValidationErrorsKind::Struct(errors) => flatten_validation_errors(errors, path, found),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    path.push(index.to_string());
+                    flatten_validation_errors(errors, path, found);
+                    path.pop();
+                }
+            }
+        }
+        if !flattened {
+            path.pop();
+        }
+    }
+}
+
+fn describe(error: &ValidationError) -> String {
+    if let Some(ref message) = error.message {
+        return message.to_string();
+    }
+    match error.code.as_ref() {
+        "length" if error.params.get("min").is_some_and(|m| m == 1) => {
+            "must not be empty".to_string()
+        }
+        code => format!("is invalid ({})", code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "[command.exec.build]\ncommand = \"\"\nrequires = [\"a\"]\n";
+
+    #[test]
+    fn location_of_span() {
+        let location = Location::new(Path::new("pls.toml"), SOURCE, 31..33);
+        assert_eq!(location.line, 2);
+        assert_eq!(location.column, 11);
+        assert_eq!(location.text, "command = \"\"");
+        assert_eq!(location.width, 2);
+    }
+
+    #[test]
+    fn span_of_value() {
+        let keys = ["command", "exec", "build", "command"].map(String::from);
+        assert_eq!(key_span(SOURCE, &keys), Some(31..33));
+    }
+
+    #[test]
+    fn span_of_missing_key_is_its_table() {
+        let keys = ["command", "exec", "build", "target_info", "cwd"].map(String::from);
+        assert_eq!(key_span(SOURCE, &keys), Some(14..19));
+    }
+
+    #[test]
+    fn renders_code_frame() {
+        let diagnostic = Diagnostic::at_span(
+            "command must not be empty".to_string(),
+            Path::new("pls.toml"),
+            SOURCE,
+            Some(31..33),
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "command must not be empty\n \
+             --> pls.toml:2:11\n  \
+             |\n\
+             2 | command = \"\"\n  \
+             |           ^^"
+        );
+    }
+
+    #[test]
+    fn combines_errors() {
+        assert!(Diagnostics::check(vec![]).is_ok());
+        let error = Diagnostics::check(vec![
+            anyhow::anyhow!("first"),
+            anyhow::anyhow!("second"),
+            anyhow::anyhow!("first"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Found 2 errors in the config\n\nfirst\n\nsecond"
+        );
+    }
+}
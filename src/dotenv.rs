@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use log::debug;
+
+use crate::config::EnvFile;
+
+/// Load env files in order, with values in later files taking precedence, returning
+/// `KEY=VALUE` strings. Values can refer to keys from earlier files or in `defined`, which
+/// the keys that are loaded are added to, as well as the environment pls is run in.
+pub fn load_env_files<'a, I>(
+    env_files: I,
+    defined: &mut HashMap<String, String>,
+) -> Result<Vec<String>>
+where
+    I: Iterator<Item = &'a EnvFile>,
+{
+    let mut values = vec![];
+    for env_file in env_files {
+        let path = Path::new(env_file.path());
+        if !path.exists() {
+            if env_file.required() {
+                return Err(anyhow!("Env file <{}> not found", path.display()));
+            }
+            debug!("Skipping optional env file <{}>", path.display());
+            continue;
+        }
+        let contents = std::fs::read_to_string(path)?;
+        for (key, value) in parse(contents.as_str(), defined)
+            .map_err(|e| anyhow!("Error parsing env file <{}>: {}", path.display(), e))?
+        {
+            defined.insert(key.clone(), value.clone());
+            values.push(format!("{}={}", key, value));
+        }
+    }
+    Ok(values)
+}
+
+/// Parse the contents of a dotenv file. Lines are `KEY=VALUE`, optionally prefixed with
+/// `export`, and values can be single quoted to be taken literally, or double quoted to
+/// allow escapes and to span lines. Unquoted and double quoted values can refer to earlier
+/// keys, or those in `defined`, with `$KEY`, `${KEY}` or `${KEY:-default}`.
+pub fn parse(contents: &str, defined: &HashMap<String, String>) -> Result<Vec<(String, String)>> {
+    let mut values = vec![];
+    let mut lookup = defined.clone();
+    let mut lines = contents.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line
+            .strip_prefix("export ")
+            .map(str::trim_start)
+            .unwrap_or(line);
+        let (key, rest) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected KEY=VALUE on line {}", line_number))?;
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(anyhow!("Invalid key <{}> on line {}", key, line_number));
+        }
+        let rest = rest.trim_start();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let mut raw = rest[1..].to_string();
+                loop {
+                    if let Some(end) = closing_quote(raw.as_str(), quote) {
+                        let trailing = raw[end + 1..].trim();
+                        if !trailing.is_empty() && !trailing.starts_with('#') {
+                            return Err(anyhow!(
+                                "Unexpected <{}> after the value of <{}>",
+                                trailing,
+                                key
+                            ));
+                        }
+                        raw.truncate(end);
+                        break;
+                    }
+                    match lines.next() {
+                        Some((_, next)) => {
+                            raw.push('\n');
+                            raw.push_str(next);
+                        }
+                        None => {
+                            return Err(anyhow!(
+                                "Unterminated quoted value for <{}> starting on line {}",
+                                key,
+                                line_number
+                            ))
+                        }
+                    }
+                }
+                if quote == '\'' {
+                    raw
+                } else {
+                    expand(raw.as_str(), &lookup, true)?
+                }
+            }
+            _ => {
+                let value = rest.split_once(" #").map(|(v, _)| v).unwrap_or(rest);
+                expand(value.trim(), &lookup, false)?
+            }
+        };
+        lookup.insert(key.to_string(), value.clone());
+        values.push((key.to_string(), value));
+    }
+    Ok(values)
+}
+
+fn closing_quote(input: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        if c == quote && !(escaped && quote == '"') {
+            return Some(i);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    None
+}
+
+fn expand(input: &str, lookup: &HashMap<String, String>, escapes: bool) -> Result<String> {
+    let mut output = String::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if escapes => match chars.next() {
+                Some('n') => output.push('\n'),
+                Some('r') => output.push('\r'),
+                Some('t') => output.push('\t'),
+                Some(other) => output.push(other),
+                None => output.push('\\'),
+            },
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let mut reference = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => reference.push(c),
+                        None => return Err(anyhow!("Unterminated <${{{}>", reference)),
+                    }
+                }
+                let (name, default) = reference
+                    .split_once(":-")
+                    .map(|(n, d)| (n, Some(d)))
+                    .unwrap_or((reference.as_str(), None));
+                match (lookup_var(name, lookup), default) {
+                    (Some(value), _) if !value.is_empty() || default.is_none() => {
+                        output.push_str(value.as_str())
+                    }
+                    (_, default) => output.push_str(default.unwrap_or_default()),
+                }
+            }
+            '$' if chars
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_') =>
+            {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                output.push_str(
+                    lookup_var(name.as_str(), lookup)
+                        .unwrap_or_default()
+                        .as_str(),
+                );
+            }
+            c => output.push(c),
+        }
+    }
+    Ok(output)
+}
+
+fn lookup_var(name: &str, lookup: &HashMap<String, String>) -> Option<String> {
+    lookup
+        .get(name)
+        .cloned()
+        .or_else(|| std::env::var(name).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(contents: &str) -> Vec<(String, String)> {
+        parse(contents, &HashMap::new()).unwrap()
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_parse_simple() {
+        let values = parse_str("# A comment\n\nA=1\nexport B = two words # comment\n");
+        assert_eq!(values, vec![pair("A", "1"), pair("B", "two words")]);
+    }
+
+    #[test]
+    fn test_parse_quotes() {
+        let values = parse_str(
+            "A='single $B # not a comment'\nB=\"double\\n\\\"quoted\\\"\"\nC=\"multi\nline\"\n",
+        );
+        assert_eq!(
+            values,
+            vec![
+                pair("A", "single $B # not a comment"),
+                pair("B", "double\n\"quoted\""),
+                pair("C", "multi\nline"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_interpolation() {
+        let defined = HashMap::from([("HOST".to_string(), "localhost".to_string())]);
+        let values = parse(
+            "PORT=5432\nURL=\"postgres://${HOST}:$PORT/${DB:-app}\"\nESCAPED=\"\\$PORT\"\n",
+            &defined,
+        )
+        .unwrap();
+        assert_eq!(
+            values,
+            vec![
+                pair("PORT", "5432"),
+                pair("URL", "postgres://localhost:5432/app"),
+                pair("ESCAPED", "$PORT"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let defined = HashMap::new();
+        assert_eq!(
+            parse("A=1\nnot a pair\n", &defined)
+                .unwrap_err()
+                .to_string(),
+            "Expected KEY=VALUE on line 2"
+        );
+        assert_eq!(
+            parse("A=\"unterminated\n", &defined)
+                .unwrap_err()
+                .to_string(),
+            "Unterminated quoted value for <A> starting on line 1"
+        );
+        assert_eq!(
+            parse("A B=1\n", &defined).unwrap_err().to_string(),
+            "Invalid key <A B> on line 1"
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use log::debug;
+
+/// Information about the git repository a project is in, for the `git.` variables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitInfo {
+    pub sha: String,
+    pub short_sha: String,
+    pub branch: String,
+    pub dirty: bool,
+}
+
+impl GitInfo {
+    pub fn read(dir: &Path) -> Result<Self> {
+        let sha = git_output(dir, &["rev-parse", "HEAD"])?;
+        let short_sha = git_output(dir, &["rev-parse", "--short", "HEAD"])?;
+        let branch = git_output(dir, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+        let dirty = !git_output(dir, &["status", "--porcelain"])?.is_empty();
+        Ok(Self {
+            sha,
+            short_sha,
+            branch,
+            dirty,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "sha" => Some(self.sha.clone()),
+            "short_sha" => Some(self.short_sha.clone()),
+            "branch" => Some(self.branch.clone()),
+            "dirty" => Some(self.dirty.to_string()),
+            _ => None,
+        }
+    }
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> Result<String> {
+    debug!("Running git <{}> in <{}>", args.join(" "), dir.display());
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| anyhow!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let info = GitInfo {
+            sha: "0123456789abcdef".to_string(),
+            short_sha: "0123456".to_string(),
+            branch: "main".to_string(),
+            dirty: false,
+        };
+        assert_eq!(info.get("sha"), Some("0123456789abcdef".to_string()));
+        assert_eq!(info.get("short_sha"), Some("0123456".to_string()));
+        assert_eq!(info.get("branch"), Some("main".to_string()));
+        assert_eq!(info.get("dirty"), Some("false".to_string()));
+        assert_eq!(info.get("author"), None);
+    }
+}
@@ -0,0 +1,760 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use log::debug;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationType, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as RequestType};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    GotoDefinitionParams, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    MarkupContent, MarkupKind, OneOf, Position, PublishDiagnosticsParams, ServerCapabilities,
+    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+use crate::cmd::show_target;
+use crate::config::{find_config_file_from, Project, Sources};
+use crate::context::{CommandLookupResult, Context, Variable};
+use crate::diagnostic::{key_span, Diagnostic, Diagnostics, Location};
+use crate::name::FullyQualifiedName;
+
+/// The variables that pls itself defines, as `pls.<name>` and `git.<name>`
+const PLS_VARIABLES: [&str; 4] = ["root", "config_path", "project", "target"];
+const GIT_VARIABLES: [&str; 4] = ["sha", "short_sha", "branch", "dirty"];
+
+/// The prefixes of variables that aren't the variables of a target
+const VARIABLE_PREFIXES: [&str; 5] = ["globals", "env", "pls", "git", "args"];
+
+/// Run a language server for config files over stdin and stdout, until the editor shuts
+/// it down
+pub fn serve() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = serde_json::to_value(ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(["{", ".", "\""].map(String::from).to_vec()),
+            ..Default::default()
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        ..Default::default()
+    })?;
+    connection.initialize(capabilities)?;
+    Server {
+        connection,
+        documents: HashMap::new(),
+        contexts: HashMap::new(),
+        published: HashMap::new(),
+    }
+    .run()?;
+    io_threads.join()?;
+    Ok(())
+}
+
+struct Server {
+    connection: Connection,
+    /// The text of each open document
+    documents: HashMap<Url, String>,
+    /// The context of the config each open document is part of, as of the last time it
+    /// loaded without errors, so that completion still works while there are some
+    contexts: HashMap<Url, Context>,
+    /// The files that diagnostics were last published for on behalf of each document,
+    /// which are cleared when they are fixed
+    published: HashMap<Url, Vec<Url>>,
+}
+
+impl Server {
+    fn run(mut self) -> Result<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a request, with an error response for one whose params can't be read rather
+    /// than stopping the server
+    fn handle_request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        self.respond(request).unwrap_or_else(|e| {
+            Response::new_err(
+                id,
+                ErrorCode::InvalidParams as i32,
+                format!("Invalid params: {}", e),
+            )
+        })
+    }
+
+    fn respond(&self, request: Request) -> Result<Response> {
+        let id = request.id.clone();
+        let response = match request.method.as_str() {
+            Completion::METHOD => {
+                let params: CompletionParams = serde_json::from_value(request.params)?;
+                let items = self
+                    .lookup(&params.text_document_position)
+                    .map(|(context, cursor)| completions(context, &cursor))
+                    .unwrap_or_default();
+                Response::new_ok(id, items)
+            }
+            GotoDefinition::METHOD => {
+                let params: GotoDefinitionParams = serde_json::from_value(request.params)?;
+                let location = self
+                    .lookup(&params.text_document_position_params)
+                    .and_then(|(context, cursor)| definition(context, &cursor))
+                    .and_then(|location| {
+                        Some(lsp_types::Location {
+                            uri: Url::from_file_path(&location.path).ok()?,
+                            range: range_of(&location),
+                        })
+                    });
+                Response::new_ok(id, location)
+            }
+            HoverRequest::METHOD => {
+                let params: HoverParams = serde_json::from_value(request.params)?;
+                let hover = self
+                    .lookup(&params.text_document_position_params)
+                    .and_then(|(context, cursor)| hover(context, &cursor))
+                    .map(|value| Hover {
+                        contents: HoverContents::Markup(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value,
+                        }),
+                        range: None,
+                    });
+                Response::new_ok(id, hover)
+            }
+            method => Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unknown method <{}>", method),
+            ),
+        };
+        Ok(response)
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                self.update(params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                // With full sync, the last change is the whole of the new text
+                match params.content_changes.into_iter().last() {
+                    Some(change) => self.update(params.text_document.uri, change.text),
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.contexts.remove(&uri);
+                self.publish(&uri, vec![])?;
+                self.published.remove(&uri);
+                // The other documents are checked again with what is saved for this one
+                let open = self
+                    .documents
+                    .iter()
+                    .map(|(uri, text)| (uri.clone(), text.clone()))
+                    .collect::<Vec<_>>();
+                for (uri, text) in open {
+                    self.update(uri, text)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the config again with the new text of a document, and publish what is wrong
+    fn update(&mut self, uri: Url, text: String) -> Result<()> {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| anyhow!("Document <{}> isn't a file", uri))?;
+        self.documents.insert(uri.clone(), text);
+        let diagnostics = match load_context(&path, &self.sources()) {
+            Ok(context) => {
+                self.contexts.insert(uri.clone(), context);
+                vec![]
+            }
+            Err(e) => Diagnostics::of(&e),
+        };
+        debug!(
+            "Found {} errors in the config of <{}>",
+            diagnostics.len(),
+            uri
+        );
+        self.publish(&uri, diagnostics)
+    }
+
+    /// The text of the open documents, which is checked rather than what is saved
+    fn sources(&self) -> Sources {
+        let mut sources = Sources::default();
+        for (uri, text) in self.documents.iter() {
+            if let Ok(path) = uri.to_file_path() {
+                sources.insert(&path, text.clone());
+            }
+        }
+        sources
+    }
+
+    fn publish(&mut self, uri: &Url, diagnostics: Vec<Diagnostic>) -> Result<()> {
+        let mut by_file = HashMap::<Url, Vec<lsp_types::Diagnostic>>::new();
+        for file in self.published.remove(uri).into_iter().flatten() {
+            by_file.entry(file).or_default();
+        }
+        by_file.entry(uri.clone()).or_default();
+        for diagnostic in diagnostics {
+            // Errors that aren't in any one place are shown at the top of the document
+            let (file, range) = match diagnostic.location {
+                Some(ref location) => (
+                    Url::from_file_path(&location.path).unwrap_or(uri.clone()),
+                    range_of(location),
+                ),
+                None => (uri.clone(), lsp_types::Range::default()),
+            };
+            by_file
+                .entry(file)
+                .or_default()
+                .push(lsp_types::Diagnostic {
+                    range,
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("pls".to_string()),
+                    message: diagnostic.message,
+                    ..Default::default()
+                });
+        }
+        let mut published = vec![];
+        for (file, diagnostics) in by_file {
+            if !diagnostics.is_empty() {
+                published.push(file.clone());
+            }
+            let params = PublishDiagnosticsParams {
+                uri: file,
+                diagnostics,
+                version: None,
+            };
+            self.connection
+                .sender
+                .send(Notification::new(PublishDiagnostics::METHOD.to_string(), params).into())?;
+        }
+        self.published.insert(uri.clone(), published);
+        Ok(())
+    }
+
+    /// The context of a document and what is at a position in it
+    fn lookup(&self, position: &TextDocumentPositionParams) -> Option<(&Context, Cursor)> {
+        let uri = &position.text_document.uri;
+        let text = self.documents.get(uri)?;
+        let context = self.contexts.get(uri)?;
+        Some((
+            context,
+            Cursor::new(text, offset_of(text, position.position)),
+        ))
+    }
+}
+
+/// Load the config that the file at `path` is part of, as a command run in its directory
+/// would, with the config files in `sources` read from there
+fn load_context(path: &Path, sources: &Sources) -> Result<Context> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let config_path = find_config_file_from(dir).unwrap_or(path.to_path_buf());
+    let root_dir = config_path.parent().unwrap_or(Path::new(""));
+    let projects = Project::load_all(&config_path, sources)?;
+    let current_project =
+        Project::containing(&projects, dir.strip_prefix(root_dir).unwrap_or(dir)).to_string();
+    Context::from_projects(
+        &projects,
+        config_path.display().to_string(),
+        current_project,
+    )
+}
+
+/// The byte offset in `text` of a position, whose character counts UTF-16 code units
+fn offset_of(text: &str, position: Position) -> usize {
+    let line_start = text
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum::<usize>();
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= position.character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16() as u32;
+    }
+    text.len()
+}
+
+/// The range a location covers, with columns counted in UTF-16 code units
+fn range_of(location: &Location) -> lsp_types::Range {
+    let utf16_len = |chars: &mut dyn Iterator<Item = char>| -> u32 {
+        chars.map(|c| c.len_utf16() as u32).sum()
+    };
+    let line = location.line as u32 - 1;
+    let start = utf16_len(&mut location.text.chars().take(location.column - 1));
+    let width = utf16_len(
+        &mut location
+            .text
+            .chars()
+            .skip(location.column - 1)
+            .take(location.width),
+    );
+    lsp_types::Range::new(
+        Position::new(line, start),
+        Position::new(line, start + width),
+    )
+}
+
+/// What is at a position in a config file, as far as references to targets and variables
+/// go
+#[derive(Debug, PartialEq)]
+struct Cursor {
+    /// The keys of the table the position is in, from the header above it
+    table: Vec<String>,
+    /// The key of the value the position is in
+    key: Option<String>,
+    /// The name around the position, such as `db.outputs.network` in a substitution
+    word: String,
+    /// How much of the word comes before the position
+    before: usize,
+    in_header: bool,
+    in_string: bool,
+    /// Whether the word is the variable of a substitution in a string
+    in_substitution: bool,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':' | '/')
+}
+
+impl Cursor {
+    fn new(text: &str, offset: usize) -> Self {
+        let line_start = text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = text[offset..]
+            .find('\n')
+            .map(|i| offset + i)
+            .unwrap_or(text.len());
+        let word_start = text[line_start..offset]
+            .rfind(|c| !is_name_char(c))
+            .map(|i| line_start + i + 1)
+            .unwrap_or(line_start);
+        let word_end = text[offset..line_end]
+            .find(|c| !is_name_char(c))
+            .map(|i| offset + i)
+            .unwrap_or(line_end);
+        let before_word = &text[line_start..word_start];
+        let in_header = before_word.trim_start().starts_with('[') && !before_word.contains('=');
+        let in_string = before_word.matches('"').count() % 2 == 1;
+        let in_substitution = in_string && before_word.trim_end().ends_with('{');
+        let mut table = vec![];
+        let mut key = None;
+        // The key is on the line that the value starts on, which may be above for an array
+        let current_line = if in_header {
+            &text[line_start..line_end]
+        } else {
+            before_word
+        };
+        for line in std::iter::once(current_line).chain(text[..line_start].lines().rev()) {
+            let line = line.trim_start();
+            if line.starts_with('[') {
+                let header = line.trim_start_matches('[');
+                let header = &header[..header.find(']').unwrap_or(header.len())];
+                table = toml_edit::Key::parse(header)
+                    .map(|keys| keys.iter().map(|k| k.get().to_string()).collect())
+                    .unwrap_or_default();
+                break;
+            }
+            if key.is_none() {
+                if let Some((k, _)) = line.split_once('=') {
+                    key = Some(k.trim().trim_matches('"').to_string());
+                }
+            }
+        }
+        if in_header {
+            key = None;
+        }
+        Cursor {
+            table,
+            key,
+            word: text[word_start..word_end].to_string(),
+            before: offset - word_start,
+            in_header,
+            in_string,
+            in_substitution,
+        }
+    }
+
+    /// The target whose table the position is in, in the project of the context
+    fn target(&self, context: &Context) -> Option<FullyQualifiedName> {
+        match self.table.as_slice() {
+            [section, kind, name, ..] if matches!(section.as_str(), "command" | "artifact") => {
+                Some(FullyQualifiedName {
+                    project: context.current_project.clone(),
+                    tag: format!("{}.{}", section, kind),
+                    name: name.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the word is the name of a target that this one requires or extends
+    fn in_target_reference(&self) -> bool {
+        self.in_string
+            && !self.in_substitution
+            && matches!(self.key.as_deref(), Some("requires" | "extends"))
+    }
+}
+
+fn completions(context: &Context, cursor: &Cursor) -> Vec<CompletionItem> {
+    let this = cursor.target(context);
+    if cursor.in_target_reference() {
+        return target_items(context, this.as_ref());
+    }
+    if !cursor.in_substitution {
+        return vec![];
+    }
+    let prefix = &cursor.word[..cursor.before];
+    let Some((head, _)) = prefix.rsplit_once('.') else {
+        let mut items = target_items(context, None);
+        items.extend(VARIABLE_PREFIXES.map(|p| item(p, CompletionItemKind::MODULE, None)));
+        if let Some(variables) = this.as_ref().and_then(|t| context.variables.get(t)) {
+            items.extend(variable_items(variables.keys()));
+        }
+        return items;
+    };
+    let find = |name: &str| find_target(context, name).and_then(|n| context.targets.get(&n));
+    match head {
+        "globals" => {
+            let project_globals = context.project_globals.get(&context.current_project);
+            variable_items(
+                context
+                    .globals
+                    .keys()
+                    .chain(project_globals.into_iter().flat_map(|g| g.keys())),
+            )
+        }
+        "pls" => variable_items(PLS_VARIABLES.iter()),
+        "git" => variable_items(GIT_VARIABLES.iter()),
+        "args" => this
+            .as_ref()
+            .and_then(|t| context.targets.get(t))
+            .and_then(|t| t.command_info())
+            .map(|c| variable_items(c.args.declared.keys()))
+            .unwrap_or_default(),
+        "env" => vec![],
+        _ => match head.rsplit_once('.') {
+            Some((producer, "outputs" | "output")) => find(producer)
+                .map(|t| {
+                    t.output_keys()
+                        .iter()
+                        .map(|k| item(k, CompletionItemKind::FIELD, None))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            _ => find(head)
+                .map(|t| {
+                    let mut items = variable_items(t.target_info().variables.keys());
+                    if !t.output_keys().is_empty() {
+                        items.push(item("outputs", CompletionItemKind::MODULE, None));
+                    }
+                    items
+                })
+                .unwrap_or_default(),
+        },
+    }
+}
+
+fn item(label: &str, kind: CompletionItemKind, detail: Option<String>) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        detail,
+        ..Default::default()
+    }
+}
+
+fn variable_items<'a>(names: impl Iterator<Item = impl AsRef<str> + 'a>) -> Vec<CompletionItem> {
+    let mut names = names.map(|n| n.as_ref().to_string()).collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+        .iter()
+        .map(|n| item(n, CompletionItemKind::VARIABLE, None))
+        .collect()
+}
+
+/// The targets, by the names they can be referred to by from the current project
+fn target_items(context: &Context, exclude: Option<&FullyQualifiedName>) -> Vec<CompletionItem> {
+    let mut names = context
+        .targets
+        .keys()
+        .filter(|name| Some(*name) != exclude)
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| {
+            let label = context.shortest_name(name);
+            let description = context.targets[name].target_info().description.clone();
+            let mut item = item(&label, CompletionItemKind::MODULE, Some(name.to_string()));
+            item.documentation = description.map(lsp_types::Documentation::String);
+            item
+        })
+        .collect()
+}
+
+/// The full name of the target a reference is to
+fn find_target(context: &Context, name: &str) -> Option<FullyQualifiedName> {
+    match context.get_target(name) {
+        CommandLookupResult::Found(target) => Some(target.target_info().name.clone()),
+        _ => None,
+    }
+}
+
+/// The target the word at the cursor refers to, or whose table it is in the header of
+fn referenced_target(context: &Context, cursor: &Cursor) -> Option<FullyQualifiedName> {
+    if cursor.in_header {
+        return cursor.target(context);
+    }
+    if cursor.in_target_reference() {
+        return find_target(context, &cursor.word);
+    }
+    if !cursor.in_substitution {
+        return None;
+    }
+    match Variable::from_string(&cursor.word).ok()? {
+        Variable::Ref(target, _) | Variable::Output(target, _) => find_target(context, &target),
+        _ => None,
+    }
+}
+
+/// Where the definition of what the word at the cursor refers to is
+fn definition(context: &Context, cursor: &Cursor) -> Option<Location> {
+    if cursor.in_substitution {
+        let variable = match Variable::from_string(&cursor.word).ok()? {
+            Variable::Simple(key) => Some((cursor.target(context)?, key)),
+            Variable::Ref(target, key) => Some((find_target(context, &target)?, key)),
+            Variable::Global(key) => {
+                return locate(
+                    context,
+                    Path::new(&context.config_path),
+                    &["globals".to_string(), key],
+                );
+            }
+            _ => None,
+        };
+        if let Some((target, key)) = variable {
+            if let Some(location) = variable_definition(context, &target, &key) {
+                return Some(location);
+            }
+        }
+    }
+    let target = referenced_target(context, cursor)?;
+    locate(
+        context,
+        context.origins.get(&target)?,
+        &target_keys(&target),
+    )
+}
+
+/// Where a variable of a target is defined, which may be in a target it extends
+fn variable_definition(
+    context: &Context,
+    target: &FullyQualifiedName,
+    key: &str,
+) -> Option<Location> {
+    let mut next = Some(target.clone());
+    while let Some(name) = next {
+        let defines = context
+            .definitions
+            .get(&name)
+            .and_then(|d| d.get("variables"))
+            .and_then(|v| v.as_table())
+            .is_some_and(|v| v.contains_key(key));
+        if defines {
+            let mut keys = target_keys(&name);
+            keys.extend(["variables".to_string(), key.to_string()]);
+            return locate(context, context.origins.get(&name)?, &keys);
+        }
+        next = context
+            .targets
+            .get(&name)
+            .and_then(|t| t.target_info().extends.clone())
+            .filter(|base| *base != name);
+    }
+    None
+}
+
+fn target_keys(name: &FullyQualifiedName) -> Vec<String> {
+    name.tag
+        .split('.')
+        .map(String::from)
+        .chain(std::iter::once(name.name.clone()))
+        .collect()
+}
+
+fn locate(context: &Context, path: &Path, keys: &[String]) -> Option<Location> {
+    let source = context.sources.read(path).ok()?;
+    let span = key_span(&source, keys)?;
+    Some(Location::new(path, &source, span))
+}
+
+/// The description and definition of the target the word at the cursor refers to
+fn hover(context: &Context, cursor: &Cursor) -> Option<String> {
+    let target = context.targets.get(&referenced_target(context, cursor)?)?;
+    let mut text = String::new();
+    if let Some(ref description) = target.target_info().description {
+        text.push_str(description);
+        text.push_str("\n\n");
+    }
+    text.push_str(&format!(
+        "```toml\n{}```",
+        show_target(context, target).ok()?
+    ));
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[command.container.db]
+image = "postgres"
+description = "The database"
+
+[command.exec.base]
+command = "echo"
+variables = { profile = "debug" }
+
+[command.exec.build]
+extends = "base"
+requires = ["db"]
+command = "cargo build --profile {profile} --network {db.outputs.network}"
+"#;
+
+    fn context() -> Context {
+        Context::from_config(&toml::from_str(CONFIG).unwrap(), "pls.toml".to_string()).unwrap()
+    }
+
+    /// The cursor at the `|` in `text`
+    fn cursor_at(text: &str) -> Cursor {
+        let offset = text.find('|').unwrap();
+        Cursor::new(&text.replace('|', ""), offset)
+    }
+
+    fn labels(items: Vec<CompletionItem>) -> Vec<String> {
+        items.into_iter().map(|i| i.label).collect()
+    }
+
+    #[test]
+    fn offsets_count_utf16() {
+        let text = "a = \"é😀x\"\nb = 1\n";
+        assert_eq!(
+            offset_of(text, Position::new(0, 8)),
+            text.find('x').unwrap()
+        );
+        assert_eq!(
+            offset_of(text, Position::new(1, 2)),
+            text.find("= 1").unwrap()
+        );
+        assert_eq!(
+            offset_of(text, Position::new(0, 99)),
+            text.find('\n').unwrap()
+        );
+    }
+
+    #[test]
+    fn range_counts_utf16() {
+        let source = "a = \"😀\"\n";
+        let location = Location::new(Path::new("pls.toml"), source, 4..10);
+        assert_eq!(
+            range_of(&location),
+            lsp_types::Range::new(Position::new(0, 4), Position::new(0, 8))
+        );
+    }
+
+    #[test]
+    fn cursor_in_substitution() {
+        let cursor = cursor_at("[command.exec.build]\ncommand = \"run {db.out|puts}\"\n");
+        assert_eq!(cursor.table, ["command", "exec", "build"]);
+        assert_eq!(cursor.key.as_deref(), Some("command"));
+        assert_eq!(cursor.word, "db.outputs");
+        assert_eq!(cursor.before, 6);
+        assert!(cursor.in_substitution);
+    }
+
+    #[test]
+    fn cursor_in_multiline_array() {
+        let cursor = cursor_at("[command.exec.build]\nrequires = [\n  \"db\",\n  \"ba|\"\n]\n");
+        assert_eq!(cursor.key.as_deref(), Some("requires"));
+        assert_eq!(cursor.word, "ba");
+        assert!(cursor.in_target_reference());
+    }
+
+    #[test]
+    fn completes_target_names() {
+        let context = context();
+        let cursor = cursor_at("[command.exec.build]\nrequires = [\"|\"]\n");
+        assert_eq!(labels(completions(&context, &cursor)), ["db", "base"]);
+    }
+
+    #[test]
+    fn completes_variables_and_outputs() {
+        let context = context();
+        let cursor = cursor_at("[command.exec.build]\ncommand = \"{|}\"\n");
+        let found = labels(completions(&context, &cursor));
+        for label in ["db", "base", "build", "globals", "profile"] {
+            assert!(
+                found.contains(&label.to_string()),
+                "{} in {:?}",
+                label,
+                found
+            );
+        }
+        let cursor = cursor_at("[command.exec.build]\ncommand = \"{base.|}\"\n");
+        assert_eq!(labels(completions(&context, &cursor)), ["profile"]);
+        let cursor = cursor_at("[command.exec.build]\ncommand = \"{db.|}\"\n");
+        assert_eq!(labels(completions(&context, &cursor)), ["outputs"]);
+        let cursor = cursor_at("[command.exec.build]\ncommand = \"{db.outputs.|}\"\n");
+        assert_eq!(labels(completions(&context, &cursor)), ["name", "network"]);
+    }
+
+    #[test]
+    fn nothing_to_complete_outside_references() {
+        let context = context();
+        let cursor = cursor_at("[command.exec.build]\ncommand = \"ca|\"\n");
+        assert!(completions(&context, &cursor).is_empty());
+    }
+
+    #[test]
+    fn hover_shows_description_and_definition() {
+        let context = context();
+        let cursor = cursor_at("[command.exec.build]\nrequires = [\"d|b\"]\n");
+        let text = hover(&context, &cursor).unwrap();
+        assert!(text.starts_with("The database\n\n```toml\n"), "{}", text);
+        assert!(text.contains("image = \"postgres\""), "{}", text);
+    }
+}
@@ -3,10 +3,12 @@ use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use std::thread;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Parser;
 use log::{debug, error, warn, Log};
 
+mod arguments;
+mod check;
 mod cleanup;
 mod cmd;
 mod commands;
@@ -14,28 +16,76 @@ mod config;
 mod containers;
 mod context;
 mod default;
+mod diagnostic;
+mod dotenv;
+mod git;
+mod lsp;
 mod name;
 mod outputs;
 mod rand;
 mod shell;
 mod target;
 mod targets;
+mod template;
 mod validate;
 mod watch;
 
 pub use cleanup::CleanupManager;
-pub use cmd::{Args, Execute};
-use config::{find_config_file, Config};
+pub use cmd::{Args, Commands, Execute};
+use config::{find_config_file, Project, Sources};
 use context::Context;
 
 pub fn run(args: Args, cleanup_manager: Arc<Mutex<CleanupManager>>) -> Result<()> {
     if let Some(directory) = args.directory {
         std::env::set_current_dir(directory)?;
     }
+    // The schema is the same for every config, so it doesn't need one to be found
+    if let Some(Commands::Schema(ref schema)) = args.command {
+        return schema.print();
+    }
+    if let Some(Commands::Completions(ref completions)) = args.command {
+        return completions.print();
+    }
+    // The language server finds the config of each file an editor opens
+    if let Some(Commands::Lsp(ref lsp)) = args.command {
+        return lsp.serve();
+    }
     let config_path =
         find_config_file().expect("Could not find config file in this directory or any parent");
-    let config = Config::load_and_validate(&config_path)?;
-    let context = Context::from_config(&config, config_path.display().to_string())?;
+    let mut projects = Project::load_all(&config_path, &Sources::default())?;
+    if let Some(profile) = args.profile {
+        Project::apply_profile(&mut projects, profile.as_str())?;
+    }
+    let root_dir = config_path.parent().unwrap_or(std::path::Path::new(""));
+    let current_dir = std::env::current_dir()?;
+    let current_project = Project::containing(
+        &projects,
+        current_dir.strip_prefix(root_dir).unwrap_or(&current_dir),
+    )
+    .to_string();
+    debug!("Running in project <{}>", current_project);
+    // Paths in the config, including those of projects, are relative to the root config
+    // file, as is where pls keeps its state, so it always runs from there
+    std::env::set_current_dir(root_dir)?;
+    let mut context = Context::from_projects(
+        &projects,
+        config_path.display().to_string(),
+        current_project,
+    )?;
+    context.apply_overrides(&args.set, &args.env)?;
+    if args.strict {
+        let errors = check::errors(&context);
+        if !errors.is_empty() {
+            return Err(anyhow!(
+                "Problems found in the config, which --strict doesn't allow:\n{}",
+                errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+    }
     match args.command {
         Some(cmd) => cmd.execute(context, cleanup_manager),
         None => panic!("No command provided"),
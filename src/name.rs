@@ -1,21 +1,51 @@
 use std::fmt;
 
+use serde::{Serialize, Serializer};
+
+/// Separates the project from the rest of the name, e.g. `services/api:command.exec.test`
+pub const PROJECT_SEPARATOR: char = ':';
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct FullyQualifiedName {
+    /// The path of the project the target is defined in, relative to the root
+    /// project, which is the empty string
+    pub project: String,
     pub tag: String,
     pub name: String,
 }
 
 impl fmt::Display for FullyQualifiedName {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}.{}", self.tag, self.name)
+        if self.project.is_empty() {
+            write!(f, "{}.{}", self.tag, self.name)
+        } else {
+            write!(
+                f,
+                "{}{}{}.{}",
+                self.project, PROJECT_SEPARATOR, self.tag, self.name
+            )
+        }
+    }
+}
+
+impl Serialize for FullyQualifiedName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
     }
 }
 
 impl FullyQualifiedName {
+    /// A form of the name that can be used to name containers and networks, which
+    /// can't contain the separators of a project path
+    pub fn resource_name(&self) -> String {
+        self.to_string().replace(['/', PROJECT_SEPARATOR], "-")
+    }
+
     pub fn from_string(input: &str) -> Self {
+        let (project, input) = input.rsplit_once(PROJECT_SEPARATOR).unwrap_or(("", input));
         let parts: Vec<&str> = input.split('.').collect();
         FullyQualifiedName {
+            project: project.to_string(),
             tag: parts[0..parts.len() - 1].join(".").to_string(),
             name: parts[parts.len() - 1].to_string(),
         }
@@ -29,6 +59,7 @@ mod tests {
     #[test]
     fn test_from_string() {
         let fqn = FullyQualifiedName::from_string("tag.name");
+        assert_eq!(fqn.project, "");
         assert_eq!(fqn.tag, "tag");
         assert_eq!(fqn.name, "name");
     }
@@ -46,4 +77,34 @@ mod tests {
         assert_eq!(fqn.tag, "");
         assert_eq!(fqn.name, "name");
     }
+
+    #[test]
+    fn test_from_string_with_project() {
+        let fqn = FullyQualifiedName::from_string("services/api:command.exec.test");
+        assert_eq!(fqn.project, "services/api");
+        assert_eq!(fqn.tag, "command.exec");
+        assert_eq!(fqn.name, "test");
+    }
+
+    #[test]
+    fn test_from_string_with_dot_in_project() {
+        let fqn = FullyQualifiedName::from_string("libs/v1.2:command.exec.test");
+        assert_eq!(fqn.project, "libs/v1.2");
+        assert_eq!(fqn.tag, "command.exec");
+        assert_eq!(fqn.name, "test");
+    }
+
+    #[test]
+    fn test_display() {
+        let mut fqn = FullyQualifiedName {
+            project: String::new(),
+            tag: "command.exec".to_string(),
+            name: "test".to_string(),
+        };
+        assert_eq!(fqn.to_string(), "command.exec.test");
+        fqn.project = "services/api".to_string();
+        assert_eq!(fqn.to_string(), "services/api:command.exec.test");
+        assert_eq!(FullyQualifiedName::from_string(&fqn.to_string()), fqn);
+        assert_eq!(fqn.resource_name(), "services-api-command.exec.test");
+    }
 }
@@ -38,6 +38,7 @@ mod tests {
     fn store_output() {
         let mut outputs = OutputsManager::default();
         let target_name = FullyQualifiedName {
+            project: String::new(),
             tag: "tag".to_string(),
             name: "test".to_string(),
         };
@@ -127,12 +127,13 @@ mod tests {
     fn test_escape_and_prepend() {
         let mut context = Context::default();
         let fully_qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "tag".to_string(),
             name: "bar".to_string(),
         };
         context.variables.insert(
             fully_qualified_name.clone(),
-            HashMap::from([("foo".to_string(), "baz".to_string())]),
+            HashMap::from([("foo".to_string(), "baz".into())]),
         );
         assert_eq!(
             escape_and_prepend(
@@ -162,12 +163,13 @@ mod tests {
     fn test_escape_and_prepend_escapes_after() {
         let mut context = Context::default();
         let fully_qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "tag".to_string(),
             name: "bar".to_string(),
         };
         context.variables.insert(
             fully_qualified_name.clone(),
-            HashMap::from([("foo".to_string(), "$baz".to_string())]),
+            HashMap::from([("foo".to_string(), "$baz".into())]),
         );
         assert_eq!(
             escape_and_prepend(
@@ -186,12 +188,13 @@ mod tests {
     fn test_escape_and_prepend_vec() {
         let mut context = Context::default();
         let fully_qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "foo".to_string(),
             name: "bar".to_string(),
         };
         context.variables.insert(
             fully_qualified_name.clone(),
-            HashMap::from([("foo".to_string(), "baz".to_string())]),
+            HashMap::from([("foo".to_string(), "baz".into())]),
         );
         assert_eq!(
             escape_and_prepend_vec(
@@ -221,12 +224,13 @@ mod tests {
     fn test_escape_and_prepend_vec_escapes_after() {
         let mut context = Context::default();
         let fully_qualified_name = FullyQualifiedName {
+            project: String::new(),
             tag: "tag".to_string(),
             name: "bar".to_string(),
         };
         context.variables.insert(
             fully_qualified_name.clone(),
-            HashMap::from([("foo".to_string(), "$baz".to_string())]),
+            HashMap::from([("foo".to_string(), "$baz".into())]),
         );
         assert_eq!(
             escape_and_prepend_vec(
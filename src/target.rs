@@ -5,35 +5,93 @@ use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, Result};
 use glob::glob;
+use indexmap::IndexMap;
 use log::{debug, info, warn};
+use serde::Serialize;
 use validator::Validate;
 
 use crate::cleanup::CleanupManager;
-use crate::context::Context;
+use crate::config::{Argument, EnvFile, EnvInfo as ConfigEnvInfo, VariableValue};
+use crate::context::{Context, VariableInputs};
 use crate::name::FullyQualifiedName;
 use crate::outputs::OutputsManager;
-use crate::targets::{ContainerArtifact, ContainerCommand, ExecArtifact, ExecCommand};
+use crate::targets::{ContainerArtifact, ContainerCommand, ExecArtifact, ExecCommand, PodCommand};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
 pub enum Target {
     Artifact(Artifact),
     Command(Command),
 }
 
-#[derive(Clone, Debug, Validate)]
+#[derive(Clone, Debug, Serialize, Validate)]
 pub struct TargetInfo {
     pub name: FullyQualifiedName,
     pub requires: Vec<FullyQualifiedName>,
-    pub variables: HashMap<String, String>,
+    pub variables: HashMap<String, VariableValue>,
     pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// The target that this one extends, if any
+    pub extends: Option<FullyQualifiedName>,
 }
 
-#[derive(Clone, Debug, Validate)]
+#[derive(Clone, Debug, Default, Serialize, Validate)]
 pub struct CommandInfo {
     pub daemon: bool,
+    #[serde(skip_serializing_if = "Args::is_empty")]
+    pub args: Args,
 }
 
-#[derive(Clone, Debug, Validate)]
+/// The arguments of a command, combined from it and the targets it extends
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Args {
+    /// Arguments that are always passed, before those the command is run with
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fixed: Vec<String>,
+    /// The arguments the command declares, which are parsed from those it is run with
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub declared: IndexMap<String, Argument>,
+}
+
+impl Args {
+    pub fn is_empty(&self) -> bool {
+        self.fixed.is_empty() && self.declared.is_empty()
+    }
+}
+
+/// The environment variables a target is run with
+#[derive(Clone, Debug, Default, Serialize, Validate)]
+pub struct EnvInfo {
+    #[validate(custom(function = "crate::validate::non_empty_strings"))]
+    pub env: Vec<String>,
+    #[validate(custom(function = "crate::validate::env_files"))]
+    pub env_file: Vec<EnvFile>,
+    /// Whether to start from an empty environment rather than the one pls is run in
+    pub clear_env: bool,
+    /// Globs of the variables from the environment pls is run in that are kept when
+    /// the environment is cleared
+    #[validate(custom(function = "crate::validate::non_empty_strings"))]
+    pub pass_env: Vec<String>,
+}
+
+impl EnvInfo {
+    pub fn from_config(defn: &ConfigEnvInfo, base: Option<&Self>) -> Self {
+        let mut env_info = base.cloned().unwrap_or_default();
+        env_info.env.extend(defn.env.clone().unwrap_or_default());
+        env_info
+            .env_file
+            .extend(defn.env_file.clone().unwrap_or_default());
+        env_info
+            .pass_env
+            .extend(defn.pass_env.clone().unwrap_or_default());
+        if let Some(clear_env) = defn.clear_env {
+            env_info.clear_env = clear_env;
+        }
+        env_info
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Validate)]
 pub struct ArtifactInfo {
     pub updates_paths: Option<Vec<String>>,
     pub if_files_changed: Option<Vec<String>>,
@@ -92,6 +150,16 @@ impl Target {
             _ => Err(anyhow!("Expected a command")),
         }
     }
+
+    /// The outputs the target sets when it runs, which others use as `{name.outputs.key}`
+    pub fn output_keys(&self) -> &'static [&'static str] {
+        match self {
+            Self::Command(Command::Container(_)) => &["name", "network"],
+            Self::Command(Command::Pod(_)) => &["network"],
+            Self::Artifact(Artifact::ContainerImage(_)) => &["id", "sha", "digest"],
+            _ => &[],
+        }
+    }
 }
 
 pub trait Targetable {
@@ -108,7 +176,8 @@ pub trait Targetable {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
 pub enum Artifact {
     ContainerImage(ContainerArtifact),
     Exec(ExecArtifact),
@@ -117,7 +186,7 @@ pub enum Artifact {
 }
 
 #[cfg(test)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct NullArtifact {
     pub target_info: TargetInfo,
     pub artifact_info: ArtifactInfo,
@@ -132,6 +201,8 @@ impl NullArtifact {
                 requires: vec![],
                 variables: HashMap::new(),
                 description: None,
+                tags: vec![],
+                extends: None,
             },
             artifact_info: ArtifactInfo {
                 if_files_changed: None,
@@ -230,6 +301,44 @@ impl Buildable for Artifact {
         }
         result
     }
+
+    fn build_no_deps(
+        &self,
+        context: &Context,
+        outputs: &mut OutputsManager,
+        cleanup_manager: Arc<Mutex<CleanupManager>>,
+    ) -> Result<()> {
+        let mut to_stop: Vec<&Target> = vec![];
+        let result = self.build_target_inner(
+            context,
+            outputs,
+            &mut to_stop,
+            cleanup_manager.clone(),
+            true,
+            false,
+        );
+        // TODO: use cleanup manager to handle the to_stop stuff?
+        // Reverse the order that they were started
+        to_stop.reverse();
+        for target in to_stop.iter() {
+            // TODO: add in errors to result
+            if let Some(s) = target.as_startable() {
+                if let Err(e) = s.stop(context, outputs, cleanup_manager.clone()) {
+                    warn!(
+                        "Error stopping target <{}>: {}",
+                        target.target_info().name,
+                        e
+                    );
+                }
+            } else {
+                panic!(
+                    "Supposed to stop <{}> but as_startable is None",
+                    target.target_info().name
+                );
+            }
+        }
+        result
+    }
 }
 
 impl Artifact {
@@ -265,12 +374,21 @@ impl Artifact {
                 outputs,
             )?
         {
-            debug!(
-                "Skipping target <{}> as it does not need to be run",
-                self.target_info().name
-            );
-            info!("[{}] Up to date", self.target_info().name);
-            return Ok(());
+            if self.inner_as_buildable().is_stale(context, outputs)? {
+                debug!(
+                    "Running target <{}> as the artifact it produced is stale",
+                    self.target_info().name
+                );
+            } else {
+                debug!(
+                    "Skipping target <{}> as it does not need to be run",
+                    self.target_info().name
+                );
+                info!("[{}] Up to date", self.target_info().name);
+                self.inner_as_buildable()
+                    .restore_outputs(context, outputs)?;
+                return Ok(());
+            }
         }
 
         // TODO: to_start
@@ -279,6 +397,7 @@ impl Artifact {
         // TODO: check that updates_paths were created?
         let _ = create_metadata_dir(self.target_info().name.to_string().as_str())?;
         File::create(last_run_path(self.target_info())?)?;
+        record_variable_inputs(self.target_info(), context)?;
         Ok(())
     }
 }
@@ -385,16 +504,18 @@ impl Artifact {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
 pub enum Command {
     #[cfg(test)]
     Null(NullCommand),
     Exec(ExecCommand),
     Container(ContainerCommand),
+    Pod(PodCommand),
 }
 
 #[cfg(test)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct NullCommand {
     pub target_info: TargetInfo,
     pub command_info: CommandInfo,
@@ -409,8 +530,10 @@ impl NullCommand {
                 requires: vec![],
                 variables: HashMap::new(),
                 description: None,
+                tags: vec![],
+                extends: None,
             },
-            command_info: CommandInfo { daemon: false },
+            command_info: CommandInfo::default(),
         }
     }
 }
@@ -418,6 +541,7 @@ impl NullCommand {
 #[cfg(test)]
 pub fn any_fully_qualified_name() -> FullyQualifiedName {
     FullyQualifiedName {
+        project: String::new(),
         tag: "test".into(),
         name: "any".into(),
     }
@@ -479,6 +603,7 @@ impl Command {
         match self {
             Self::Exec(exec) => &exec.target_info,
             Self::Container(container) => &container.target_info,
+            Self::Pod(pod) => &pod.target_info,
             #[cfg(test)]
             Self::Null(null) => &null.target_info,
         }
@@ -488,6 +613,7 @@ impl Command {
         match self {
             Self::Exec(exec) => &exec.command_info,
             Self::Container(container) => &container.command_info,
+            Self::Pod(pod) => &pod.command_info,
             #[cfg(test)]
             Self::Null(null) => &null.command_info,
         }
@@ -497,6 +623,7 @@ impl Command {
         match self {
             Self::Exec(exec) => exec,
             Self::Container(container) => container,
+            Self::Pod(pod) => pod,
             #[cfg(test)]
             Self::Null(null) => null,
         }
@@ -528,6 +655,7 @@ impl Runnable for Command {
             &mut to_stop,
             cleanup_manager.clone(),
             args,
+            true,
         );
         // TODO: use cleanup manager to handle the to_stop stuff?
         // Reverse the order that they were started
@@ -552,7 +680,44 @@ impl Runnable for Command {
         result
     }
 
-    // TODO: run_no_deps
+    fn run_no_deps(
+        &self,
+        context: &Context,
+        outputs: &mut OutputsManager,
+        cleanup_manager: Arc<Mutex<CleanupManager>>,
+        args: Vec<String>,
+    ) -> Result<()> {
+        let mut to_stop: Vec<&Target> = vec![];
+        let result = self.run_target_inner(
+            context,
+            outputs,
+            &mut to_stop,
+            cleanup_manager.clone(),
+            args,
+            false,
+        );
+        // TODO: use cleanup manager to handle the to_stop stuff?
+        // Reverse the order that they were started
+        to_stop.reverse();
+        for target in to_stop.iter() {
+            // TODO: add in errors to result
+            if let Some(s) = target.as_startable() {
+                if let Err(e) = s.stop(context, outputs, cleanup_manager.clone()) {
+                    warn!(
+                        "Error stopping target <{}>: {}",
+                        target.target_info().name,
+                        e
+                    );
+                }
+            } else {
+                panic!(
+                    "Supposed to stop <{}> but as_startable is None",
+                    target.target_info().name
+                );
+            }
+        }
+        result
+    }
 }
 
 fn run_required(
@@ -561,7 +726,22 @@ fn run_required(
     outputs: &mut OutputsManager,
     cleanup_manager: Arc<Mutex<CleanupManager>>,
 ) -> Result<()> {
-    let resolved_requirements = find_required(target_info, context)?;
+    run_required_excluding(target_info, context, outputs, cleanup_manager, &[])
+}
+
+/// Run the requirements of a target, apart from those in `excluding`, which the caller
+/// takes care of itself
+pub fn run_required_excluding(
+    target_info: &TargetInfo,
+    context: &Context,
+    outputs: &mut OutputsManager,
+    cleanup_manager: Arc<Mutex<CleanupManager>>,
+    excluding: &[FullyQualifiedName],
+) -> Result<()> {
+    let resolved_requirements = find_required(target_info, context)?
+        .into_iter()
+        .filter(|t| !excluding.contains(&t.target_info().name))
+        .collect::<Vec<_>>();
     debug!(
         "Running required targets for target <{}>: {:?}",
         target_info.name,
@@ -570,48 +750,75 @@ fn run_required(
             .map(|t| t.target_info().name.clone())
             .collect::<Vec<_>>()
     );
-    for required_target in resolved_requirements.clone().into_iter() {
-        match (
-            required_target.as_buildable(),
-            required_target
-                .command_info()
-                .map(|c| c.daemon)
-                .unwrap_or(false),
-            required_target.as_startable(),
-            required_target.as_runnable(),
-        ) {
-            (Some(buildable), _, _, _) => {
-                debug!(
-                    "Building required target <{}> for target <{}>",
-                    required_target.target_info().name,
-                    required_target.target_info().name
-                );
-                buildable.build(context, outputs, cleanup_manager.clone())?;
+    for required_target in resolved_requirements.iter() {
+        run_requirement(
+            required_target,
+            context,
+            outputs,
+            cleanup_manager.clone(),
+            true,
+        )?;
+    }
+    Ok(())
+}
+
+/// Build, start or run a target that another requires, whichever fits its kind, along
+/// with what it requires in turn if `run_deps` is set
+pub fn run_requirement(
+    required_target: &Target,
+    context: &Context,
+    outputs: &mut OutputsManager,
+    cleanup_manager: Arc<Mutex<CleanupManager>>,
+    run_deps: bool,
+) -> Result<()> {
+    match (
+        required_target.as_buildable(),
+        required_target
+            .command_info()
+            .map(|c| c.daemon)
+            .unwrap_or(false),
+        required_target.as_startable(),
+        required_target.as_runnable(),
+    ) {
+        (Some(buildable), _, _, _) => {
+            debug!(
+                "Building required target <{}>",
+                required_target.target_info().name
+            );
+            if run_deps {
+                buildable.build(context, outputs, cleanup_manager)
+            } else {
+                buildable.build_no_deps(context, outputs, cleanup_manager)
             }
-            (None, true, None, _) => panic!(
-                "Don't know how to start as it is a daemon with as_startable None {:?}",
-                required_target
-            ),
-            (None, true, Some(startable), _) => {
-                debug!(
-                    "Starting required target <{}> for target <{}>",
-                    required_target.target_info().name,
-                    required_target.target_info().name
-                );
-                startable.start(context, outputs, cleanup_manager.clone(), vec![])?;
+        }
+        (None, true, None, _) => panic!(
+            "Don't know how to start as it is a daemon with as_startable None {:?}",
+            required_target
+        ),
+        (None, true, Some(startable), _) => {
+            debug!(
+                "Starting required target <{}>",
+                required_target.target_info().name
+            );
+            if run_deps {
+                startable.start(context, outputs, cleanup_manager, vec![])
+            } else {
+                startable.start_no_deps(context, outputs, cleanup_manager, vec![])
             }
-            (None, false, _, Some(runnable)) => {
-                debug!(
-                    "Running required target <{}> for target <{}>",
-                    required_target.target_info().name,
-                    required_target.target_info().name
-                );
-                runnable.run(context, outputs, cleanup_manager.clone(), vec![])?;
+        }
+        (None, false, _, Some(runnable)) => {
+            debug!(
+                "Running required target <{}>",
+                required_target.target_info().name
+            );
+            if run_deps {
+                runnable.run(context, outputs, cleanup_manager, vec![])
+            } else {
+                runnable.run_no_deps(context, outputs, cleanup_manager, vec![])
             }
-            _ => panic!("Don't know how to build {:?}", required_target),
         }
+        _ => panic!("Don't know how to build {:?}", required_target),
     }
-    Ok(())
 }
 
 impl Command {
@@ -622,18 +829,21 @@ impl Command {
         _to_stop: &mut [&Target],
         cleanup_manager: Arc<Mutex<CleanupManager>>,
         args: Vec<String>,
+        run_deps: bool,
     ) -> Result<()> {
         debug!(
             "Running target <{}>, with definition <{:?}>",
             self.target_info().name,
             self
         );
-        run_required(
-            self.target_info(),
-            context,
-            outputs,
-            cleanup_manager.clone(),
-        )?;
+        if run_deps {
+            run_required(
+                self.target_info(),
+                context,
+                outputs,
+                cleanup_manager.clone(),
+            )?;
+        }
         self.inner_as_runnable()
             .run(context, outputs, cleanup_manager.clone(), args)?;
         let _ = create_metadata_dir(self.target_info().name.to_string().as_str())?;
@@ -787,10 +997,18 @@ impl Command {
         }
     }
 
+    pub fn pod(&self) -> Result<&PodCommand> {
+        match self {
+            Self::Pod(pod) => Ok(pod),
+            _ => Err(anyhow!("Expected a pod")),
+        }
+    }
+
     fn inner_as_startable(&self) -> &dyn Startable {
         match self {
             Self::Exec(exec) => exec,
             Self::Container(container) => container,
+            Self::Pod(pod) => pod,
             #[cfg(test)]
             Self::Null(null) => null,
         }
@@ -859,6 +1077,7 @@ pub trait Startable {
 
     fn status(&self, context: &Context, outputs: &mut OutputsManager) -> Result<StatusResult>;
 
+    #[allow(dead_code)]
     fn restart(
         &self,
         context: &Context,
@@ -889,6 +1108,28 @@ pub trait Buildable {
         outputs: &mut OutputsManager,
         cleanup_manager: Arc<Mutex<CleanupManager>>,
     ) -> Result<()>;
+
+    fn build_no_deps(
+        &self,
+        context: &Context,
+        outputs: &mut OutputsManager,
+        cleanup_manager: Arc<Mutex<CleanupManager>>,
+    ) -> Result<()> {
+        self.build(context, outputs, cleanup_manager)
+    }
+
+    /// Checks specific to the artifact type for whether it needs to be rebuilt, such as
+    /// whether the thing it produced still exists. Only consulted when the timestamp
+    /// comparisons say that the artifact is up to date.
+    fn is_stale(&self, _context: &Context, _outputs: &OutputsManager) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Restore the outputs of the last build when the artifact is up to date and so
+    /// isn't rebuilt.
+    fn restore_outputs(&self, _context: &Context, _outputs: &mut OutputsManager) -> Result<()> {
+        Ok(())
+    }
 }
 
 fn find_required(target: &TargetInfo, context: &Context) -> Result<Vec<Target>> {
@@ -913,7 +1154,7 @@ fn find_required(target: &TargetInfo, context: &Context) -> Result<Vec<Target>>
     Ok(resolved_requirements)
 }
 
-fn metadata_path(name: &str) -> Result<std::path::PathBuf> {
+pub fn metadata_path(name: &str) -> Result<std::path::PathBuf> {
     Ok(std::env::current_dir()?.join(".pls").join(name))
 }
 
@@ -932,6 +1173,49 @@ fn last_run_path(target: &TargetInfo) -> Result<std::path::PathBuf> {
     Ok(metadata_path(target.name.to_string().as_str())?.join("last_run"))
 }
 
+fn variable_inputs_path(target: &TargetInfo) -> Result<std::path::PathBuf> {
+    Ok(metadata_path(target.name.to_string().as_str())?.join("variable_inputs"))
+}
+
+/// Record the inputs from variables that the target used, so that it can be run again if
+/// any of them change
+fn record_variable_inputs(target: &TargetInfo, context: &Context) -> Result<()> {
+    let path = variable_inputs_path(target)?;
+    let used = context
+        .used_inputs
+        .lock()
+        .unwrap()
+        .get(&target.name)
+        .cloned()
+        .unwrap_or_default();
+    if used == VariableInputs::default() {
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    std::fs::write(&path, toml::to_string(&used)?)?;
+    Ok(())
+}
+
+/// Whether any of the inputs from variables that the target used when it was last run
+/// have changed
+fn variable_inputs_changed(target: &TargetInfo, context: &Context) -> Result<bool> {
+    let path = variable_inputs_path(target)?;
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return Ok(false);
+    };
+    let recorded: VariableInputs = toml::from_str(contents.as_str())
+        .map_err(|e| anyhow!("Error reading <{}>: {}", path.display(), e))?;
+    match context.changed_variable_input(&recorded)? {
+        Some(changed) => {
+            debug!("Target <{}> has changed {}", target.name, changed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 enum LastRun {
     Never,
@@ -1115,6 +1399,13 @@ fn should_rerun(
                     }
                 }
             }
+            if !run_again && variable_inputs_changed(target, context)? {
+                debug!(
+                    "Running task as the value of a variable has changed for target <{}>",
+                    target.name
+                );
+                run_again = true;
+            }
         } else {
             run_again = true;
         }
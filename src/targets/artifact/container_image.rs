@@ -1,27 +1,47 @@
-use std::io::{BufRead, BufReader};
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, Result};
-use log::{debug, info, warn};
+use log::{debug, info};
+use serde::Serialize;
 use validator::Validate;
 
 use crate::cleanup::CleanupManager;
-use crate::commands::build_command;
-use crate::config::ContainerBuild as ConfigContainerBuild;
+use crate::commands::run_command;
+use crate::config::{ContainerBuild as ConfigContainerBuild, PullPolicy};
+use crate::containers::{image_digest, image_id, normalize_image_id};
 use crate::context::Context;
-use crate::default::default_to;
+use crate::default::{default_optional, default_to};
 use crate::outputs::OutputsManager;
-use crate::target::{ArtifactInfo, Buildable, TargetInfo};
+use crate::shell::{escape_and_prepend, escape_and_prepend_vec, escape_string};
+use crate::target::{create_metadata_dir, ArtifactInfo, Buildable, TargetInfo};
 
-#[derive(Debug, Clone, Validate)]
+#[derive(Debug, Clone, Serialize, Validate)]
 pub struct ContainerArtifact {
     #[validate(length(min = 1))]
     pub context: String,
     #[validate(length(min = 1))]
     pub tag: String,
+    #[validate(length(min = 1))]
+    pub file: Option<String>,
+    #[validate(custom(function = "crate::validate::keys_non_empty_strings"))]
+    pub build_args: HashMap<String, String>,
+    #[validate(length(min = 1))]
+    pub target: Option<String>,
+    #[validate(custom(function = "crate::validate::keys_non_empty_strings"))]
+    pub labels: HashMap<String, String>,
+    #[validate(length(min = 1))]
+    pub platform: Option<String>,
+    pub no_cache: bool,
+    pub pull: Option<PullPolicy>,
+    #[validate(custom(function = "crate::validate::non_empty_strings"))]
+    pub secrets: Vec<String>,
 
+    #[serde(flatten)]
     #[validate(nested)]
     pub artifact_info: ArtifactInfo,
+    #[serde(flatten)]
     #[validate(nested)]
     pub target_info: TargetInfo,
 }
@@ -33,12 +53,119 @@ impl ContainerArtifact {
         defn: &ConfigContainerBuild,
         base: Option<&Self>,
     ) -> Self {
+        let mut build_args = HashMap::new();
+        let mut labels = HashMap::new();
+        let mut secrets = vec![];
+        if let Some(base) = base {
+            build_args.extend(base.build_args.clone());
+            labels.extend(base.labels.clone());
+            secrets.extend(base.secrets.clone());
+        }
+        build_args.extend(defn.build_args.clone().unwrap_or_default());
+        labels.extend(defn.labels.clone().unwrap_or_default());
+        secrets.extend(defn.secrets.clone().unwrap_or_default());
         Self {
             target_info,
             artifact_info,
             context: default_to!(defn, base, context),
             tag: default_to!(defn, base, tag),
+            file: default_optional!(defn, base, file),
+            build_args,
+            target: default_optional!(defn, base, target),
+            labels,
+            platform: default_optional!(defn, base, platform),
+            no_cache: default_to!(defn, base, no_cache, false),
+            pull: defn.pull.or(base.and_then(|b| b.pull)),
+            secrets,
+        }
+    }
+
+    pub fn build_command(
+        &self,
+        context: &Context,
+        outputs: &OutputsManager,
+        iidfile: Option<&Path>,
+    ) -> Result<String> {
+        let name = &self.target_info.name;
+        let tag = context.resolve_substitutions(self.tag.as_str(), name, outputs)?;
+        let container_context =
+            context.resolve_substitutions(self.context.as_str(), name, outputs)?;
+        let file_str = escape_and_prepend(name, context, outputs, "-f", &self.file)
+            .map_err(|e| anyhow!("Failed to escape file: {}", e))?;
+        let build_args_str = escape_and_prepend_vec(
+            name,
+            context,
+            outputs,
+            "--build-arg",
+            &Some(sorted_key_values(&self.build_args)),
+        )
+        .map_err(|e| anyhow!("Failed to escape build_args: {}", e))?;
+        let target_str = escape_and_prepend(name, context, outputs, "--target", &self.target)
+            .map_err(|e| anyhow!("Failed to escape target: {}", e))?;
+        let labels_str = escape_and_prepend_vec(
+            name,
+            context,
+            outputs,
+            "--label",
+            &Some(sorted_key_values(&self.labels)),
+        )
+        .map_err(|e| anyhow!("Failed to escape labels: {}", e))?;
+        let platform_str = escape_and_prepend(name, context, outputs, "--platform", &self.platform)
+            .map_err(|e| anyhow!("Failed to escape platform: {}", e))?;
+        let secrets_str = escape_and_prepend_vec(
+            name,
+            context,
+            outputs,
+            "--secret",
+            &Some(self.secrets.clone()),
+        )
+        .map_err(|e| anyhow!("Failed to escape secrets: {}", e))?;
+        let mut parts = vec![
+            "podman build".to_string(),
+            format!("-t {}", escape_string(tag.as_str())?),
+            file_str,
+            build_args_str,
+            target_str,
+            labels_str,
+            platform_str,
+            secrets_str,
+        ];
+        if self.no_cache {
+            parts.push("--no-cache".to_string());
+        }
+        if let Some(pull) = self.pull {
+            parts.push(format!("--pull={}", pull));
+        }
+        if let Some(iidfile) = iidfile {
+            parts.push(format!(
+                "--iidfile {}",
+                escape_string(iidfile.display().to_string().as_str())?
+            ));
         }
+        parts.push(escape_string(container_context.as_str())?);
+        Ok(parts
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+}
+
+fn sorted_key_values(map: &HashMap<String, String>) -> Vec<String> {
+    let mut values = map
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>();
+    values.sort();
+    values
+}
+
+impl ContainerArtifact {
+    fn store_outputs(&self, outputs: &mut OutputsManager, id: &str, digest: &str) {
+        outputs.store_output(self.target_info.name.clone(), "id", id);
+        // Kept for configs that used the output before the image ID was captured properly
+        outputs.store_output(self.target_info.name.clone(), "sha", id);
+        outputs.store_output(self.target_info.name.clone(), "digest", digest);
     }
 }
 
@@ -51,49 +178,229 @@ impl Buildable for ContainerArtifact {
     ) -> Result<()> {
         let tag =
             context.resolve_substitutions(self.tag.as_str(), &self.target_info.name, outputs)?;
-        let container_context = context.resolve_substitutions(
-            self.context.as_str(),
-            &self.target_info.name,
-            outputs,
-        )?;
-        let command = format!("podman build -t \"{}\" \"{}\"", tag, container_context);
+        let config_dir = create_metadata_dir(self.target_info.name.to_string().as_str())?;
+        let iid_path = config_dir.join("iid");
+        let command = self.build_command(context, outputs, Some(&iid_path))?;
         debug!(
             "Building container for target <{}> with command <{}>",
             self.target_info.name, command
         );
         info!("[{}] Building tag {}", self.target_info.name, tag);
-        let mut cmd = build_command(command.as_str())?;
-        cmd.stdout(std::process::Stdio::piped());
-        let mut child = cmd.spawn()?;
-        let child_stdout = child.stdout.take().unwrap();
-        let (tx, rx) = std::sync::mpsc::channel();
-        let handle = std::thread::spawn(move || {
-            let stdout_reader = BufReader::new(child_stdout);
-            let mut last_line = String::new();
-            for line in stdout_reader.lines() {
-                match line {
-                    Ok(line) => {
-                        last_line = line.clone();
-                        println!("{}", line);
-                    }
-                    Err(e) => {
-                        warn!("Error reading stdout from build: {}", e);
-                    }
+        run_command(command.as_str())?;
+        let id = std::fs::read_to_string(&iid_path)
+            .map_err(|e| {
+                anyhow!(
+                    "Error reading image ID for <{}> from <{}>: {}",
+                    self.target_info.name,
+                    iid_path.display(),
+                    e
+                )
+            })?
+            .trim()
+            .to_string();
+        let digest = image_digest(id.as_str())?;
+        std::fs::write(config_dir.join("digest"), digest.as_str())?;
+        self.store_outputs(outputs, id.as_str(), digest.as_str());
+        Ok(())
+    }
+
+    fn is_stale(&self, context: &Context, outputs: &OutputsManager) -> Result<bool> {
+        let config_dir = create_metadata_dir(self.target_info.name.to_string().as_str())?;
+        let built_id = match std::fs::read_to_string(config_dir.join("iid")) {
+            Ok(id) => id,
+            Err(_) => {
+                debug!(
+                    "No image ID recorded for target <{}>",
+                    self.target_info.name
+                );
+                return Ok(true);
+            }
+        };
+        let tag =
+            context.resolve_substitutions(self.tag.as_str(), &self.target_info.name, outputs)?;
+        match image_id(tag.as_str())? {
+            Some(current_id) => {
+                if normalize_image_id(&current_id) != normalize_image_id(&built_id) {
+                    debug!(
+                        "Image <{}> is <{}> but target <{}> built <{}>",
+                        tag,
+                        current_id,
+                        self.target_info.name,
+                        built_id.trim()
+                    );
+                    Ok(true)
+                } else {
+                    Ok(false)
                 }
             }
-            tx.send(last_line).unwrap();
-        });
-        let status = child.wait()?;
-        handle.join().unwrap();
-        if !status.success() {
-            return Err(anyhow!(
-                "Command failed with exit code: {}",
-                status.code().unwrap()
-            ));
-        }
-        for line in rx.try_iter() {
-            outputs.store_output(self.target_info.name.clone(), "sha", line.as_str());
+            None => {
+                debug!(
+                    "Image <{}> for target <{}> is missing from the local store",
+                    tag, self.target_info.name
+                );
+                Ok(true)
+            }
         }
+    }
+
+    fn restore_outputs(&self, _context: &Context, outputs: &mut OutputsManager) -> Result<()> {
+        let config_dir = create_metadata_dir(self.target_info.name.to_string().as_str())?;
+        let id = std::fs::read_to_string(config_dir.join("iid"))?;
+        let digest = std::fs::read_to_string(config_dir.join("digest")).unwrap_or_default();
+        self.store_outputs(outputs, id.trim(), digest.trim());
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::name::FullyQualifiedName;
+
+    fn artifact_from_config(
+        config_src: &str,
+        base: Option<&ContainerArtifact>,
+    ) -> ContainerArtifact {
+        let defn: ConfigContainerBuild = toml::from_str(config_src).unwrap();
+        ContainerArtifact::from_config(
+            TargetInfo {
+                name: FullyQualifiedName {
+                    project: String::new(),
+                    tag: ConfigContainerBuild::tag().to_string(),
+                    name: "image".to_string(),
+                },
+                requires: vec![],
+                variables: HashMap::new(),
+                description: None,
+                tags: vec![],
+                extends: None,
+            },
+            ArtifactInfo {
+                updates_paths: None,
+                if_files_changed: None,
+            },
+            &defn,
+            base,
+        )
+    }
+
+    #[test]
+    fn build_command_with_defaults() {
+        let artifact = artifact_from_config(
+            r#"
+            context = "."
+            tag = "myimage"
+            "#,
+            None,
+        );
+        let command = artifact
+            .build_command(&Context::default(), &OutputsManager::default(), None)
+            .unwrap();
+        assert_eq!(command, "podman build -t myimage .");
+    }
+
+    #[test]
+    fn build_command_with_options() {
+        let artifact = artifact_from_config(
+            r#"
+            context = "./container"
+            tag = "myimage"
+            file = "Containerfile.dev"
+            build_args = { VERSION = "1.0", BASE = "alpine" }
+            target = "builder"
+            labels = { "org.example.team" = "dev" }
+            platform = "linux/amd64"
+            no_cache = true
+            pull = "newer"
+            secrets = ["id=token,src=token.txt"]
+            "#,
+            None,
+        );
+        let command = artifact
+            .build_command(&Context::default(), &OutputsManager::default(), None)
+            .unwrap();
+        assert_eq!(
+            command,
+            "podman build -t myimage -f Containerfile.dev --build-arg 'BASE=alpine' --build-arg 'VERSION=1.0' --target builder --label 'org.example.team=dev' --platform linux/amd64 --secret 'id=token,src=token.txt' --no-cache --pull=newer ./container"
+        );
+    }
+
+    #[test]
+    fn build_command_with_iidfile() {
+        let artifact = artifact_from_config(
+            r#"
+            context = "."
+            tag = "myimage"
+            "#,
+            None,
+        );
+        let command = artifact
+            .build_command(
+                &Context::default(),
+                &OutputsManager::default(),
+                Some(Path::new(".pls/artifact.container_image.image/iid")),
+            )
+            .unwrap();
+        assert_eq!(
+            command,
+            "podman build -t myimage --iidfile .pls/artifact.container_image.image/iid ."
+        );
+    }
+
+    #[test]
+    fn build_command_substitutes_build_args() {
+        let mut context = Context::default();
+        context
+            .globals
+            .insert("version".to_string(), "1.2.3".into());
+        let artifact = artifact_from_config(
+            r#"
+            context = "."
+            tag = "myimage"
+            build_args = { VERSION = "{globals.version}" }
+            "#,
+            None,
+        );
+        let command = artifact
+            .build_command(&context, &OutputsManager::default(), None)
+            .unwrap();
+        assert_eq!(
+            command,
+            "podman build -t myimage --build-arg 'VERSION=1.2.3' ."
+        );
+    }
+
+    #[test]
+    fn from_config_inherits_from_base() {
+        let base = artifact_from_config(
+            r#"
+            context = "."
+            tag = "base"
+            file = "Containerfile"
+            build_args = { VERSION = "1.0", BASE = "alpine" }
+            pull = "always"
+            "#,
+            None,
+        );
+        let artifact = artifact_from_config(
+            r#"
+            tag = "test"
+            target = "test"
+            build_args = { VERSION = "2.0" }
+            "#,
+            Some(&base),
+        );
+        assert_eq!(artifact.context, ".");
+        assert_eq!(artifact.tag, "test");
+        assert_eq!(artifact.file, Some("Containerfile".to_string()));
+        assert_eq!(artifact.target, Some("test".to_string()));
+        assert_eq!(artifact.pull, Some(PullPolicy::Always));
+        assert_eq!(
+            artifact.build_args,
+            HashMap::from([
+                ("VERSION".to_string(), "2.0".to_string()),
+                ("BASE".to_string(), "alpine".to_string()),
+            ])
+        );
+    }
+}
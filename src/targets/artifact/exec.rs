@@ -2,25 +2,35 @@ use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
 use log::{debug, info};
+use serde::Serialize;
 use validator::Validate;
 
 use crate::cleanup::CleanupManager;
 use crate::commands::run_command_with_env;
 use crate::config::ExecArtifact as ConfigExecArtifact;
 use crate::context::Context;
-use crate::default::default_to;
+use crate::default::{default_optional, default_to};
 use crate::outputs::OutputsManager;
-use crate::target::{ArtifactInfo, Buildable, TargetInfo};
+use crate::target::{ArtifactInfo, Buildable, EnvInfo, TargetInfo};
 
-#[derive(Debug, Clone, Validate)]
+#[derive(Debug, Clone, Serialize, Validate)]
 pub struct ExecArtifact {
     #[validate(length(min = 1))]
     pub command: String,
-    #[validate(custom(function = "crate::validate::non_empty_strings"))]
-    pub env: Vec<String>,
+    #[validate(length(min = 1))]
+    pub cwd: Option<String>,
+    /// Where to run when `cwd` isn't set, which unlike `cwd` isn't inherited through
+    /// `extends`
+    #[serde(skip)]
+    pub default_cwd: Option<String>,
 
+    #[serde(flatten)]
     #[validate(nested)]
     pub artifact_info: ArtifactInfo,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub env_info: EnvInfo,
+    #[serde(flatten)]
     #[validate(nested)]
     pub target_info: TargetInfo,
 }
@@ -32,16 +42,13 @@ impl ExecArtifact {
         defn: &ConfigExecArtifact,
         base: Option<&Self>,
     ) -> Self {
-        let mut env = vec![];
-        if let Some(base) = base {
-            env.extend(base.env.clone());
-        }
-        env.extend(defn.env.clone().unwrap_or_default());
         Self {
             target_info,
             artifact_info,
             command: default_to!(defn, base, command),
-            env,
+            env_info: EnvInfo::from_config(&defn.env_info, base.map(|b| &b.env_info)),
+            cwd: default_optional!(defn, base, cwd),
+            default_cwd: defn.default_cwd.clone(),
         }
     }
 }
@@ -62,16 +69,18 @@ impl Buildable for ExecArtifact {
             &self.target_info.name,
             outputs,
         )?;
-        let env = self
-            .env
-            .iter()
-            .map(|s| context.resolve_substitutions(s, &self.target_info.name, outputs))
-            .collect::<Result<Vec<String>>>()?;
+        let env = context.resolve_env(&self.target_info.name, &self.env_info, outputs)?;
         debug!(
             "Building exec artifact for target <{}> with command <{}>",
             self.target_info.name, cmd
         );
         info!("[{}] Building with command {}", self.target_info.name, cmd);
-        run_command_with_env(&cmd, env.as_slice())
+        let cwd = self
+            .cwd
+            .as_ref()
+            .or(self.default_cwd.as_ref())
+            .map(|cwd| context.resolve_substitutions(cwd, &self.target_info.name, outputs))
+            .transpose()?;
+        run_command_with_env(&cmd, &env, cwd.as_deref())
     }
 }
@@ -2,29 +2,32 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, Result};
-use log::{debug, info};
+use log::{debug, info, warn};
+use serde::Serialize;
 use validator::Validate;
 
 use crate::cleanup::CleanupManager;
 use crate::commands::{
-    run_command, spawn_command_with_pidfile, status_using_pidfile, stop_using_pidfile,
+    run_command, run_command_with_env, spawn_command_with_pidfile, status_using_pidfile,
+    stop_using_pidfile, Environment,
 };
 use crate::config::ContainerCommand as ConfigContainerCommand;
+use crate::containers::{container_status, label_args, ContainerState};
 use crate::context::Context;
 use crate::default::{default_optional, default_to};
 use crate::outputs::OutputsManager;
 use crate::rand::rand_string;
-use crate::shell::{escape_and_prepend, escape_and_prepend_vec, escape_string};
+use crate::shell::{
+    escape_and_prepend, escape_and_prepend_vec, escape_string, prepend_arguments_if_set,
+};
 use crate::target::{
-    create_metadata_dir, CommandInfo, Runnable, Startable, StatusResult, TargetInfo,
+    create_metadata_dir, CommandInfo, EnvInfo, Runnable, Startable, StatusResult, TargetInfo,
 };
 
-#[derive(Debug, Clone, Validate)]
+#[derive(Debug, Clone, Serialize, Validate)]
 pub struct ContainerCommand {
     #[validate(length(min = 1, message = "image must not be empty"))]
     pub image: String,
-    #[validate(custom(function = "crate::validate::non_empty_strings"))]
-    pub env: Vec<String>,
     #[validate(length(min = 1))]
     pub command: Option<String>,
     #[validate(custom(function = "crate::validate::keys_and_values_non_empty_strings"))]
@@ -36,8 +39,13 @@ pub struct ContainerCommand {
     pub create_network: bool,
     pub default_args: Option<String>,
 
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub env_info: EnvInfo,
+    #[serde(flatten)]
     #[validate(nested)]
     pub target_info: TargetInfo,
+    #[serde(flatten)]
     #[validate(nested)]
     pub command_info: CommandInfo,
 }
@@ -49,16 +57,11 @@ impl ContainerCommand {
         defn: &ConfigContainerCommand,
         base: Option<&Self>,
     ) -> Self {
-        let mut env = vec![];
-        if let Some(base) = base {
-            env.extend(base.env.clone());
-        }
-        env.extend(defn.env.clone().unwrap_or_default());
         ContainerCommand {
             target_info,
             command_info,
             image: default_to!(defn, base, image),
-            env,
+            env_info: EnvInfo::from_config(&defn.env_info, base.map(|b| &b.env_info)),
             command: default_optional!(defn, base, command),
             mount: default_to!(defn, base, mount),
             workdir: default_optional!(defn, base, workdir),
@@ -77,16 +80,20 @@ impl Runnable for ContainerCommand {
         cleanup_manager: Arc<Mutex<CleanupManager>>,
         args: Vec<String>,
     ) -> Result<()> {
-        let container_name = format!("{}-{}", self.target_info.name, rand_string(8));
-        // TODO: default_args
-        let command = container_run_command(self, context, outputs, container_name.as_str(), args)
-            .map_err(|e| {
-                anyhow!(
-                    "Error escaping podman command for <{}>: {}",
-                    self.target_info.name,
-                    e
-                )
-            })?;
+        let container_name = format!(
+            "{}-{}",
+            self.target_info.name.resource_name(),
+            rand_string(8)
+        );
+        let command =
+            container_run_command(self, context, outputs, container_name.as_str(), args, None)
+                .map_err(|e| {
+                    anyhow!(
+                        "Error escaping podman command for <{}>: {}",
+                        self.target_info.name,
+                        e
+                    )
+                })?;
         for pre_command in command.pre_commands.iter() {
             run_command(pre_command.as_str())?;
         }
@@ -100,15 +107,8 @@ impl Runnable for ContainerCommand {
             context.resolve_substitutions(self.image.as_str(), &self.target_info.name, outputs)?
         );
         let container_name = command.name;
-        for post_command in command.post_stop_commands.into_iter() {
-            cleanup_manager.lock().unwrap().push_cleanup(
-                "clean_up_network".to_string(),
-                move || {
-                    debug!("Running post stop command <{}>", post_command);
-                    run_command(post_command.as_str()).unwrap();
-                },
-            );
-        }
+        // Cleanups run in the order they are added, so the container has to be stopped
+        // before the network it is attached to can be removed
         cleanup_manager
             .lock()
             .unwrap()
@@ -117,29 +117,81 @@ impl Runnable for ContainerCommand {
                 debug!("Stopping container with command <{}>", stop_command);
                 run_command(stop_command.as_str()).unwrap();
             });
-        let result = run_command(command.command.as_str());
+        for post_command in command.post_stop_commands.clone().into_iter() {
+            cleanup_manager.lock().unwrap().push_cleanup(
+                "clean_up_network".to_string(),
+                move || {
+                    debug!("Running post stop command <{}>", post_command);
+                    run_command(post_command.as_str()).unwrap();
+                },
+            );
+        }
+        let result = run_command_with_env(command.command.as_str(), &command.env, None);
+        {
+            let mut cleanup_manager = cleanup_manager.lock().unwrap();
+            for _ in 0..command.post_stop_commands.len() + 1 {
+                cleanup_manager.pop_cleanup();
+            }
+        }
+        for post_command in command.post_stop_commands.iter() {
+            debug!("Running post stop command <{}>", post_command);
+            if let Err(e) = run_command(post_command.as_str()) {
+                warn!(
+                    "Error running <{}> for target <{}>: {}",
+                    post_command, self.target_info.name, e
+                );
+            }
+        }
         result
     }
 }
 
-impl Startable for ContainerCommand {
-    fn start(
+/// How a container joins a pod: the shared network and the DNS alias other members
+/// can reach it by.
+pub struct PodMembership {
+    pub network: String,
+    pub alias: String,
+}
+
+impl ContainerCommand {
+    /// The `podman run` command that would be used to run the container
+    pub fn resolve_command(
+        &self,
+        context: &Context,
+        outputs: &OutputsManager,
+        args: Vec<String>,
+    ) -> Result<String> {
+        let container_name = format!(
+            "{}-{}",
+            self.target_info.name.resource_name(),
+            rand_string(8)
+        );
+        container_run_command(self, context, outputs, container_name.as_str(), args, None)
+            .map(|info| info.command)
+    }
+
+    /// Start the container as a daemon, optionally as a member of a pod
+    pub fn start_container(
         &self,
         context: &Context,
         outputs: &mut OutputsManager,
-        _cleanup_manager: Arc<Mutex<CleanupManager>>,
         args: Vec<String>,
+        pod: Option<&PodMembership>,
     ) -> Result<()> {
-        let container_name = format!("{}-{}", self.target_info.name, rand_string(8));
-        // TODO: default_args
-        let command = container_run_command(self, context, outputs, container_name.as_str(), args)
-            .map_err(|e| {
-                anyhow!(
-                    "Error escaping podman command for <{}>: {}",
-                    self.target_info.name,
-                    e
-                )
-            })?;
+        let container_name = format!(
+            "{}-{}",
+            self.target_info.name.resource_name(),
+            rand_string(8)
+        );
+        let command =
+            container_run_command(self, context, outputs, container_name.as_str(), args, pod)
+                .map_err(|e| {
+                    anyhow!(
+                        "Error escaping podman command for <{}>: {}",
+                        self.target_info.name,
+                        e
+                    )
+                })?;
         debug!(
             "Running container for target <{}> with command <{:?}>",
             self.target_info.name, self.command
@@ -161,18 +213,37 @@ impl Startable for ContainerCommand {
         }
         spawn_command_with_pidfile(
             command.command.as_str(),
-            &[],
+            &command.env,
+            None,
             &pid_path,
             &log_path,
             log_start,
         )?;
-        // TODO: post_stop_commands
+        ContainerState {
+            name: command.name.clone(),
+            network: command.network.clone(),
+            created_network: !command.post_stop_commands.is_empty(),
+            volumes: command.volumes.clone(),
+        }
+        .save(&config_dir)?;
         outputs.store_output(self.target_info.name.clone(), "name", command.name.as_str());
         if let Some(network) = command.network {
             outputs.store_output(self.target_info.name.clone(), "network", network.as_str());
         }
         Ok(())
     }
+}
+
+impl Startable for ContainerCommand {
+    fn start(
+        &self,
+        context: &Context,
+        outputs: &mut OutputsManager,
+        _cleanup_manager: Arc<Mutex<CleanupManager>>,
+        args: Vec<String>,
+    ) -> Result<()> {
+        self.start_container(context, outputs, args, None)
+    }
 
     fn stop(
         &self,
@@ -183,27 +254,87 @@ impl Startable for ContainerCommand {
         let config_dir = create_metadata_dir(self.target_info.name.to_string().as_str())?;
 
         let pid_path = config_dir.join("pid");
-        debug!(
-            "Searching for pid file for target <{}> at <{}>",
-            self.target_info.name,
-            pid_path.display()
-        );
         let log_stop = || {
             info!("[{}] Stopping", self.target_info.name);
         };
-        stop_using_pidfile(&pid_path, log_stop)
+        let state = match ContainerState::load(&config_dir)? {
+            Some(state) => state,
+            None => {
+                debug!(
+                    "No container state for target <{}>, searching for pid file at <{}>",
+                    self.target_info.name,
+                    pid_path.display()
+                );
+                return stop_using_pidfile(&pid_path, log_stop);
+            }
+        };
+        debug!(
+            "Found container state for target <{}>: {:?}",
+            self.target_info.name, state
+        );
+        // Keep the state if the container may still be running, so that stopping it can be
+        // tried again
+        let status = container_status(state.name.as_str()).map_err(|e| {
+            anyhow!(
+                "Error stopping <{}>, as whether it is running isn't known: {}",
+                self.target_info.name,
+                e
+            )
+        })?;
+        let mut errors = vec![];
+        if status.is_some() {
+            log_stop();
+            if let Err(e) = run_command(
+                format!("podman stop -i {}", escape_string(state.name.as_str())?).as_str(),
+            ) {
+                errors.push(format!("stopping container <{}>: {}", state.name, e));
+            }
+        } else {
+            debug!("Container <{}> is no longer running", state.name);
+        }
+        if pid_path.exists() {
+            // The podman process exits once the container stops, this just tidies up
+            stop_using_pidfile(&pid_path, || {})?;
+        }
+        if let (true, Some(network)) = (state.created_network, state.network.as_ref()) {
+            debug!(
+                "Removing network <{}> created for target <{}>",
+                network, self.target_info.name
+            );
+            if let Err(e) =
+                run_command(format!("podman network rm {}", escape_string(network)?).as_str())
+            {
+                errors.push(format!("removing network <{}>: {}", network, e));
+            }
+        }
+        ContainerState::remove(&config_dir)?;
+        if !errors.is_empty() {
+            return Err(anyhow!(
+                "Error stopping <{}>: {}",
+                self.target_info.name,
+                errors.join(", ")
+            ));
+        }
+        Ok(())
     }
 
     fn status(&self, _context: &Context, _outputs: &mut OutputsManager) -> Result<StatusResult> {
         let config_dir = create_metadata_dir(self.target_info.name.to_string().as_str())?;
 
-        let pid_path = config_dir.join("pid");
-        debug!(
-            "Searching for pid file for target <{}> at <{}>",
-            self.target_info.name,
-            pid_path.display()
-        );
-        status_using_pidfile(&pid_path).map(|s| s.into())
+        match ContainerState::load(&config_dir)? {
+            Some(state) => Ok(container_status(state.name.as_str())?
+                .map(|status| format!("Container <{}> is {}", state.name, status))
+                .into()),
+            None => {
+                let pid_path = config_dir.join("pid");
+                debug!(
+                    "No container state for target <{}>, searching for pid file at <{}>",
+                    self.target_info.name,
+                    pid_path.display()
+                );
+                status_using_pidfile(&pid_path).map(|s| s.into())
+            }
+        }
     }
 }
 
@@ -213,6 +344,10 @@ pub struct ContainerRunInfo {
     pub command: String,
     pub name: String,
     pub network: Option<String>,
+    pub volumes: Vec<String>,
+    /// The environment to run the command with, which the container takes the values of
+    /// its variables from so that they aren't in the command line
+    pub env: Environment,
 }
 
 // TODO: generate the name and return it in the result
@@ -222,15 +357,21 @@ fn container_run_command(
     outputs: &OutputsManager,
     container_name: &str,
     args: Vec<String>,
+    pod: Option<&PodMembership>,
 ) -> Result<ContainerRunInfo> {
-    let env_str = escape_and_prepend_vec(
-        &container.target_info.name,
-        context,
-        outputs,
-        "-e",
-        &Some(container.env.clone()),
-    )
-    .map_err(|e| anyhow!("Failed to escape env: {}", e))?;
+    // Containers never inherit the environment, so only the variables that are passed
+    // through are needed. Their values, which are often secrets, are given to podman in its
+    // environment with only the names in its arguments, where anyone could see them.
+    let env = context.resolve_env(&container.target_info.name, &container.env_info, outputs)?;
+    let mut env_keys = vec![];
+    for var in env.vars.iter() {
+        let key = var.split_once('=').map_or(var.as_str(), |(key, _)| key);
+        if !env_keys.contains(&key) {
+            env_keys.push(key);
+        }
+    }
+    let env_str = prepend_arguments_if_set("-e", &Some(env_keys))
+        .map_err(|e| anyhow!("Failed to escape env: {}", e))?;
     let mount_str = escape_and_prepend_vec(
         &container.target_info.name,
         context,
@@ -281,22 +422,52 @@ fn container_run_command(
     let mut network = container.network.clone();
     let mut pre_commands = vec![];
     let mut post_stop_commands = vec![];
-    if network.is_none() && container.create_network {
-        network = Some(container_name.to_string());
-        pre_commands.push(format!("podman network create {}", container_name));
-        post_stop_commands.push(format!("podman network rm {}", container_name));
+    let labels_str = label_args(context, &container.target_info.name)?;
+    let volumes = named_volumes(&container.mount);
+    for volume in volumes.iter() {
+        pre_commands.push(format!(
+            "podman volume create --ignore {} {}",
+            labels_str,
+            escape_string(volume)?
+        ));
     }
-    let network_str = escape_and_prepend(
-        &container.target_info.name,
-        context,
-        outputs,
-        "--network",
-        &network,
-    )
-    .map_err(|e| anyhow!("Failed to escape network: {}", e))?;
+    let network_str = if let Some(pod) = pod {
+        debug!(
+            "Container <{}> is joining pod network <{}> as <{}>",
+            container.target_info.name, pod.network, pod.alias
+        );
+        network = Some(pod.network.clone());
+        format!(
+            "--network {} --network-alias {}",
+            escape_string(pod.network.as_str())?,
+            escape_string(pod.alias.as_str())?
+        )
+    } else {
+        if network.is_none() && container.create_network {
+            network = Some(container_name.to_string());
+            pre_commands.push(format!(
+                "podman network create {} {}",
+                labels_str,
+                escape_string(container_name)?
+            ));
+            post_stop_commands.push(format!(
+                "podman network rm {}",
+                escape_string(container_name)?
+            ));
+        }
+        escape_and_prepend(
+            &container.target_info.name,
+            context,
+            outputs,
+            "--network",
+            &network,
+        )
+        .map_err(|e| anyhow!("Failed to escape network: {}", e))?
+    };
     let cmd = format!(
-        "podman run --name {} --rm -u 1000 --userns keep-id {} {} {} {} {} {}",
+        "podman run --name {} --rm -u 1000 --userns keep-id {} {} {} {} {} {} {}",
         escape_string(container_name)?,
+        labels_str,
         env_str,
         mount_str,
         workdir_str,
@@ -310,5 +481,118 @@ fn container_run_command(
         command: cmd,
         name: container_name.to_string(),
         network,
+        volumes,
+        env: Environment {
+            vars: env.vars,
+            clear: false,
+        },
     })
 }
+
+/// Named volumes are mounts whose source is a name rather than a host path
+pub fn named_volumes(mount: &HashMap<String, String>) -> Vec<String> {
+    let mut volumes = mount
+        .keys()
+        .filter(|source| !source.starts_with(['/', '.', '~', '{']))
+        .cloned()
+        .collect::<Vec<_>>();
+    volumes.sort();
+    volumes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn container(config_src: &str) -> (Context, ContainerCommand) {
+        let config: Config = toml::from_str(config_src).unwrap();
+        let mut context = Context::from_config(&config, "/project/pls.toml".to_string()).unwrap();
+        let (_, target) = context.targets.drain().next().unwrap();
+        let container = target.command().unwrap().container().unwrap().clone();
+        (context, container)
+    }
+
+    #[test]
+    fn test_run_command_creates_network_and_volumes() {
+        let (context, container) = container(
+            r#"
+            [command.container.db]
+            image = "postgres"
+            create_network = true
+            mount = { "pgdata" = "/data" }
+            "#,
+        );
+        let info = container_run_command(
+            &container,
+            &context,
+            &OutputsManager::default(),
+            "db-abc",
+            vec![],
+            None,
+        )
+        .unwrap();
+        let labels = "--label 'pls.project=/project' --label 'pls.target=command.container.db'";
+        assert_eq!(
+            info.pre_commands,
+            vec![
+                format!("podman volume create --ignore {} pgdata", labels),
+                format!("podman network create {} db-abc", labels),
+            ]
+        );
+        assert_eq!(info.post_stop_commands, vec!["podman network rm db-abc"]);
+        assert_eq!(info.network, Some("db-abc".to_string()));
+        assert_eq!(info.volumes, vec!["pgdata"]);
+        assert!(
+            info.command.contains("--network db-abc postgres"),
+            "{}",
+            info.command
+        );
+    }
+
+    #[test]
+    fn test_run_command_joins_pod() {
+        let (context, container) = container(
+            r#"
+            [command.container.db]
+            image = "postgres"
+            create_network = true
+            "#,
+        );
+        let pod = PodMembership {
+            network: "stack-def".to_string(),
+            alias: "db".to_string(),
+        };
+        let info = container_run_command(
+            &container,
+            &context,
+            &OutputsManager::default(),
+            "db-abc",
+            vec![],
+            Some(&pod),
+        )
+        .unwrap();
+        // The pod owns the network, so the container neither creates nor removes one
+        assert!(info.pre_commands.is_empty());
+        assert!(info.post_stop_commands.is_empty());
+        assert_eq!(info.network, Some("stack-def".to_string()));
+        assert!(
+            info.command
+                .contains("--network stack-def --network-alias db postgres"),
+            "{}",
+            info.command
+        );
+    }
+
+    #[test]
+    fn test_named_volumes() {
+        let mount = HashMap::from([
+            ("pgdata".to_string(), "/var/lib/postgresql/data".to_string()),
+            ("./src".to_string(), "/src".to_string()),
+            ("/tmp".to_string(), "/tmp".to_string()),
+            ("~/.cache".to_string(), "/cache".to_string()),
+            ("cache".to_string(), "/cache".to_string()),
+        ]);
+        assert_eq!(named_volumes(&mount), vec!["cache", "pgdata"]);
+    }
+}
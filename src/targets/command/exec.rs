@@ -2,6 +2,7 @@ use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
 use log::{debug, info};
+use serde::Serialize;
 use validator::Validate;
 
 use crate::cleanup::CleanupManager;
@@ -13,18 +14,27 @@ use crate::context::Context;
 use crate::default::{default_optional, default_to};
 use crate::outputs::OutputsManager;
 use crate::target::create_metadata_dir;
-use crate::target::{CommandInfo, Runnable, Startable, StatusResult, TargetInfo};
+use crate::target::{CommandInfo, EnvInfo, Runnable, Startable, StatusResult, TargetInfo};
 
-#[derive(Debug, Clone, Validate)]
+#[derive(Debug, Clone, Serialize, Validate)]
 pub struct ExecCommand {
     #[validate(length(min = 1))]
     pub command: String,
     pub default_args: Option<String>,
-    #[validate(custom(function = "crate::validate::non_empty_strings"))]
-    pub env: Vec<String>,
+    #[validate(length(min = 1))]
+    pub cwd: Option<String>,
+    /// Where to run when `cwd` isn't set, which unlike `cwd` isn't inherited through
+    /// `extends`
+    #[serde(skip)]
+    pub default_cwd: Option<String>,
 
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub env_info: EnvInfo,
+    #[serde(flatten)]
     #[validate(nested)]
     pub target_info: TargetInfo,
+    #[serde(flatten)]
     #[validate(nested)]
     pub command_info: CommandInfo,
 }
@@ -36,17 +46,14 @@ impl ExecCommand {
         defn: &ConfigExecCommand,
         base: Option<&Self>,
     ) -> Self {
-        let mut env = vec![];
-        if let Some(base) = base {
-            env.extend(base.env.clone());
-        }
-        env.extend(defn.env.clone().unwrap_or_default());
         ExecCommand {
             command: default_to!(defn, base, command),
             default_args: default_optional!(defn, base, default_args),
             target_info,
             command_info,
-            env,
+            env_info: EnvInfo::from_config(&defn.env_info, base.map(|b| &b.env_info)),
+            cwd: default_optional!(defn, base, cwd),
+            default_cwd: defn.default_cwd.clone(),
         }
     }
 }
@@ -72,6 +79,14 @@ impl ExecCommand {
         debug!("Resolved command to <{}>", resolved);
         Ok(resolved)
     }
+
+    fn resolve_cwd(&self, context: &Context, outputs: &OutputsManager) -> Result<Option<String>> {
+        self.cwd
+            .as_ref()
+            .or(self.default_cwd.as_ref())
+            .map(|cwd| context.resolve_substitutions(cwd, &self.target_info.name, outputs))
+            .transpose()
+    }
 }
 
 impl Runnable for ExecCommand {
@@ -84,18 +99,14 @@ impl Runnable for ExecCommand {
     ) -> Result<()> {
         // TODO: default_args
         let command = self.resolve_command(context, outputs, args)?;
-        let env = self
-            .env
-            .iter()
-            .map(|s| context.resolve_substitutions(s, &self.target_info.name, outputs))
-            .collect::<Result<Vec<String>>>()?;
+        let env = context.resolve_env(&self.target_info.name, &self.env_info, outputs)?;
         debug!(
             "Running target <{}> with command <{}>",
             self.target_info.name, command
         );
         info!("[{}] Running {}", self.target_info.name, command);
-        // TODO: cwd
-        run_command_with_env(command.as_str(), env.as_slice())
+        let cwd = self.resolve_cwd(context, outputs)?;
+        run_command_with_env(command.as_str(), &env, cwd.as_deref())
     }
 }
 
@@ -113,17 +124,15 @@ impl Startable for ExecCommand {
         let log_path = config_dir.join("log");
         // TODO: default_args
         let cmd = self.resolve_command(context, outputs, args)?;
-        let env = self
-            .env
-            .iter()
-            .map(|s| context.resolve_substitutions(s, &self.target_info.name, outputs))
-            .collect::<Result<Vec<String>>>()?;
+        let env = context.resolve_env(&self.target_info.name, &self.env_info, outputs)?;
+        let cwd = self.resolve_cwd(context, outputs)?;
         let log_start = || {
             info!("[{}] Starting {}", self.target_info.name, cmd);
         };
         spawn_command_with_pidfile(
             cmd.as_str(),
-            env.as_slice(),
+            &env,
+            cwd.as_deref(),
             &pid_path,
             &log_path,
             log_start,
@@ -0,0 +1,448 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use serde::Serialize;
+use validator::Validate;
+
+use crate::cleanup::CleanupManager;
+use crate::commands::run_command;
+use crate::config::Pod as ConfigPod;
+use crate::containers::label_args;
+use crate::context::Context;
+use crate::name::FullyQualifiedName;
+use crate::outputs::OutputsManager;
+use crate::rand::rand_string;
+use crate::shell::escape_string;
+use crate::target::{
+    create_metadata_dir, run_required_excluding, CommandInfo, Runnable, Startable, StatusResult,
+    TargetInfo,
+};
+use crate::targets::command::container::{ContainerCommand, PodMembership};
+
+#[derive(Debug, Clone, Serialize, Validate)]
+pub struct PodCommand {
+    pub members: Vec<FullyQualifiedName>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub target_info: TargetInfo,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub command_info: CommandInfo,
+}
+
+impl PodCommand {
+    pub fn from_config(
+        target_info: TargetInfo,
+        mut command_info: CommandInfo,
+        _defn: &ConfigPod,
+        members: Option<Vec<FullyQualifiedName>>,
+        base: Option<&Self>,
+    ) -> Self {
+        // Pods only make sense running in the background
+        command_info.daemon = true;
+        PodCommand {
+            members: members
+                .or(base.map(|b| b.members.clone()))
+                .unwrap_or_default(),
+            target_info,
+            command_info,
+        }
+    }
+
+    fn member_container<'a>(
+        &self,
+        context: &'a Context,
+        member: &FullyQualifiedName,
+    ) -> Result<&'a ContainerCommand> {
+        context
+            .targets
+            .get(member)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Member <{}> of pod <{}> not found in config file <{}>",
+                    member,
+                    self.target_info.name,
+                    context.config_path
+                )
+            })?
+            .command()
+            .and_then(|c| c.container())
+            .map_err(|_| {
+                anyhow!(
+                    "Member <{}> of pod <{}> is not a container command",
+                    member,
+                    self.target_info.name
+                )
+            })
+    }
+
+    /// The member containers in the order that they should be started, so that each
+    /// one comes after any other member that it requires, directly or indirectly.
+    pub fn members_in_start_order<'a>(
+        &self,
+        context: &'a Context,
+    ) -> Result<Vec<&'a ContainerCommand>> {
+        let mut ordered = vec![];
+        let mut visited = HashSet::new();
+        let mut visiting = vec![];
+        for member in self.members.iter() {
+            self.visit(member, context, &mut visiting, &mut visited, &mut ordered)?;
+        }
+        let members = ordered
+            .iter()
+            .map(|member| self.member_container(context, member))
+            .collect::<Result<Vec<_>>>()?;
+        self.check_aliases(&members)?;
+        Ok(members)
+    }
+
+    /// Members are reachable on the pod's network by their name, so two members from
+    /// different projects can't have the same one
+    fn check_aliases(&self, members: &[&ContainerCommand]) -> Result<()> {
+        let mut aliases: HashMap<&str, &FullyQualifiedName> = HashMap::new();
+        for member in members {
+            let name = &member.target_info.name;
+            if let Some(other) = aliases.insert(name.name.as_str(), name) {
+                return Err(anyhow!(
+                    "Members <{}> and <{}> of pod <{}> would both have the alias <{}> on its network",
+                    other,
+                    name,
+                    self.target_info.name,
+                    name.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn visit(
+        &self,
+        name: &FullyQualifiedName,
+        context: &Context,
+        visiting: &mut Vec<FullyQualifiedName>,
+        visited: &mut HashSet<FullyQualifiedName>,
+        ordered: &mut Vec<FullyQualifiedName>,
+    ) -> Result<()> {
+        if visited.contains(name) {
+            return Ok(());
+        }
+        if visiting.contains(name) {
+            return Err(anyhow!(
+                "Members of pod <{}> have a cycle in their requirements: {} -> {}",
+                self.target_info.name,
+                visiting
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> "),
+                name
+            ));
+        }
+        visiting.push(name.clone());
+        if let Some(target) = context.targets.get(name) {
+            for required in target.target_info().requires.iter() {
+                self.visit(required, context, visiting, visited, ordered)?;
+            }
+        }
+        visiting.pop();
+        visited.insert(name.clone());
+        if self.members.contains(name) {
+            ordered.push(name.clone());
+        }
+        Ok(())
+    }
+}
+
+impl Runnable for PodCommand {
+    fn run(
+        &self,
+        _context: &Context,
+        _outputs: &mut OutputsManager,
+        _cleanup_manager: Arc<Mutex<CleanupManager>>,
+        _args: Vec<String>,
+    ) -> Result<()> {
+        Err(anyhow!(
+            "Pod <{}> can't be run in the foreground, use the start command instead",
+            self.target_info.name
+        ))
+    }
+}
+
+impl Startable for PodCommand {
+    fn start(
+        &self,
+        context: &Context,
+        outputs: &mut OutputsManager,
+        cleanup_manager: Arc<Mutex<CleanupManager>>,
+        args: Vec<String>,
+    ) -> Result<()> {
+        if !args.is_empty() {
+            return Err(anyhow!("Pods do not accept arguments"));
+        }
+        let members = self.members_in_start_order(context)?;
+        if let StatusResult::Running(_) = self.status(context, outputs)? {
+            return Err(anyhow!(
+                "Pod <{}> is already running",
+                self.target_info.name
+            ));
+        }
+        let config_dir = create_metadata_dir(self.target_info.name.to_string().as_str())?;
+        let network_path = config_dir.join("network");
+        let network = format!(
+            "{}-{}",
+            self.target_info.name.resource_name(),
+            rand_string(8)
+        );
+        info!(
+            "[{}] Starting pod with network {}",
+            self.target_info.name, network
+        );
+        run_command(
+            format!(
+                "podman network create {} {}",
+                label_args(context, &self.target_info.name)?,
+                escape_string(network.as_str())?
+            )
+            .as_str(),
+        )?;
+        std::fs::write(&network_path, network.as_str())?;
+        outputs.store_output(self.target_info.name.clone(), "network", network.as_str());
+        for member in members.iter() {
+            let membership = PodMembership {
+                network: network.clone(),
+                alias: member.target_info.name.name.clone(),
+            };
+            let result = run_required_excluding(
+                &member.target_info,
+                context,
+                outputs,
+                cleanup_manager.clone(),
+                &self.members,
+            )
+            .and_then(|_| member.start_container(context, outputs, vec![], Some(&membership)));
+            if let Err(e) = result {
+                warn!(
+                    "[{}] Failed to start <{}>, stopping the rest of the pod",
+                    self.target_info.name, member.target_info.name
+                );
+                if let Err(stop_err) = self.stop(context, outputs, cleanup_manager.clone()) {
+                    warn!(
+                        "Error stopping pod <{}>: {}",
+                        self.target_info.name, stop_err
+                    );
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn stop(
+        &self,
+        context: &Context,
+        outputs: &mut OutputsManager,
+        cleanup_manager: Arc<Mutex<CleanupManager>>,
+    ) -> Result<()> {
+        let members = self.members_in_start_order(context)?;
+        let config_dir = create_metadata_dir(self.target_info.name.to_string().as_str())?;
+        let network_path = config_dir.join("network");
+        let mut was_running = false;
+        let mut errors = vec![];
+        for member in members.iter().rev() {
+            match member.status(context, outputs)? {
+                StatusResult::Running(_) => {
+                    was_running = true;
+                    if let Err(e) = member.stop(context, outputs, cleanup_manager.clone()) {
+                        errors.push(format!("<{}>: {}", member.target_info.name, e));
+                    }
+                }
+                StatusResult::NotRunning() => {
+                    debug!(
+                        "Member <{}> of pod <{}> is not running",
+                        member.target_info.name, self.target_info.name
+                    );
+                }
+            }
+        }
+        if let Ok(network) = std::fs::read_to_string(&network_path) {
+            was_running = true;
+            info!(
+                "[{}] Removing pod network {}",
+                self.target_info.name,
+                network.trim()
+            );
+            if let Err(e) = run_command(
+                format!("podman network rm {}", escape_string(network.trim())?).as_str(),
+            ) {
+                errors.push(format!("network <{}>: {}", network.trim(), e));
+            }
+            std::fs::remove_file(&network_path)?;
+        }
+        if !was_running {
+            return Err(anyhow!("Task not running"));
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!(
+                "Error stopping pod <{}>: {}",
+                self.target_info.name,
+                errors.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    fn status(&self, context: &Context, outputs: &mut OutputsManager) -> Result<StatusResult> {
+        let members = self.members_in_start_order(context)?;
+        let mut running = 0;
+        let mut lines = vec![];
+        for member in members.iter() {
+            match member.status(context, outputs)? {
+                StatusResult::Running(msg) => {
+                    running += 1;
+                    lines.push(format!("  {}: {}", member.target_info.name.name, msg));
+                }
+                StatusResult::NotRunning() => {
+                    lines.push(format!("  {}: Not running", member.target_info.name.name));
+                }
+            }
+        }
+        if running == 0 {
+            return Ok(StatusResult::NotRunning());
+        }
+        Ok(StatusResult::Running(format!(
+            "{}/{} members running\n{}",
+            running,
+            members.len(),
+            lines.join("\n")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn context_from_str(config_src: &str) -> Result<Context> {
+        let config: Config = toml::from_str(config_src).unwrap();
+        Context::from_config(&config, "pls.toml".to_string())
+    }
+
+    fn pod<'a>(context: &'a Context, name: &str) -> &'a PodCommand {
+        context
+            .targets
+            .get(&FullyQualifiedName {
+                project: String::new(),
+                tag: ConfigPod::tag().to_string(),
+                name: name.to_string(),
+            })
+            .unwrap()
+            .command()
+            .unwrap()
+            .pod()
+            .unwrap()
+    }
+
+    #[test]
+    fn members_in_start_order_follows_requires() {
+        let context = context_from_str(
+            r#"
+            [command.container.app]
+            image = "app"
+            requires = ["migrate"]
+
+            [command.exec.migrate]
+            command = "migrate"
+            requires = ["db"]
+
+            [command.container.db]
+            image = "postgres"
+
+            [command.container.cache]
+            image = "redis"
+
+            [command.pod.stack]
+            members = ["app", "db", "cache"]
+            "#,
+        )
+        .unwrap();
+        let order = pod(&context, "stack")
+            .members_in_start_order(&context)
+            .unwrap()
+            .iter()
+            .map(|m| m.target_info.name.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(order, vec!["db", "app", "cache"]);
+    }
+
+    #[test]
+    fn pods_are_daemons() {
+        let context = context_from_str(
+            r#"
+            [command.container.db]
+            image = "postgres"
+
+            [command.pod.stack]
+            members = ["db"]
+            "#,
+        )
+        .unwrap();
+        assert!(pod(&context, "stack").command_info.daemon);
+    }
+
+    #[test]
+    fn members_must_be_containers() {
+        let result = context_from_str(
+            r#"
+            [command.exec.db]
+            command = "postgres"
+
+            [command.pod.stack]
+            members = ["db"]
+            "#,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Member <command.exec.db> of pod <command.pod.stack> is not a container command"
+        );
+    }
+
+    #[test]
+    fn members_must_exist() {
+        let result = context_from_str(
+            r#"
+            [command.pod.stack]
+            members = ["db"]
+            "#,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid member of <command.pod.stack>: Non-existent reference <db>"
+        );
+    }
+
+    #[test]
+    fn members_with_cycle() {
+        let result = context_from_str(
+            r#"
+            [command.container.a]
+            image = "a"
+            requires = ["b"]
+
+            [command.container.b]
+            image = "b"
+            requires = ["a"]
+
+            [command.pod.stack]
+            members = ["a"]
+            "#,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Members of pod <command.pod.stack> have a cycle in their requirements: command.container.a -> command.container.b -> command.container.a"
+        );
+    }
+}
@@ -5,3 +5,4 @@ pub use artifact::container_image::ContainerArtifact;
 pub use artifact::exec::ExecArtifact;
 pub use command::container::ContainerCommand;
 pub use command::exec::ExecCommand;
+pub use command::pod::PodCommand;
@@ -0,0 +1,335 @@
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+
+use crate::shell::escape_string;
+
+/// A string with `{variable}` substitutions in it, where each substitution can be followed
+/// by filters, e.g. `{port|default:8080}`. Literal braces are written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Literal(String),
+    Substitution(Substitution),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    pub variable: String,
+    pub filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// The value to use if the variable isn't defined
+    Default(String),
+    /// Quote the value so that it is a single shell word
+    Quote,
+    Upper,
+    /// The last component of a path
+    Basename,
+    /// Join the shell words of the value with the separator, a space by default
+    Join(Option<String>),
+}
+
+impl Filter {
+    fn parse(input: &str) -> std::result::Result<Self, String> {
+        let (name, arg) = input
+            .split_once(':')
+            .map(|(n, a)| (n.trim(), Some(a)))
+            .unwrap_or((input.trim(), None));
+        let no_arg = |filter| match arg {
+            Some(_) => Err(format!("Filter <{}> doesn't take an argument", name)),
+            None => Ok(filter),
+        };
+        match name {
+            "default" => arg
+                .map(|a| Filter::Default(a.to_string()))
+                .ok_or_else(|| "Filter <default> needs a value, e.g. <default:value>".to_string()),
+            "quote" => no_arg(Filter::Quote),
+            "upper" => no_arg(Filter::Upper),
+            "basename" => no_arg(Filter::Basename),
+            "join" => Ok(Filter::Join(arg.map(|a| a.to_string()))),
+            "" => Err("Empty filter".to_string()),
+            _ => Err(format!("Unknown filter <{}>", name)),
+        }
+    }
+
+    fn apply(&self, value: String) -> Result<String> {
+        match self {
+            Filter::Default(_) => Ok(value),
+            Filter::Quote => {
+                escape_string(value.as_str()).map_err(|e| anyhow!("Can't quote <{}>: {}", value, e))
+            }
+            Filter::Upper => Ok(value.to_uppercase()),
+            Filter::Basename => Ok(value
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string()),
+            Filter::Join(separator) => {
+                let words = shlex::split(value.as_str())
+                    .ok_or_else(|| anyhow!("Can't split <{}> into words to join", value))?;
+                Ok(words.join(separator.as_deref().unwrap_or(" ")))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::Default(value) => write!(f, "default:{}", value),
+            Filter::Quote => write!(f, "quote"),
+            Filter::Upper => write!(f, "upper"),
+            Filter::Basename => write!(f, "basename"),
+            Filter::Join(None) => write!(f, "join"),
+            Filter::Join(Some(separator)) => write!(f, "join:{}", separator),
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(input: &str) -> Result<Self> {
+        let error = |index: usize, message: String| {
+            let column = input[..index].chars().count() + 1;
+            anyhow!("{} at column {} of <{}>", message, column, input)
+        };
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut chars = input.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|(_, c)| *c == '{').is_some() => literal.push('{'),
+                '}' if chars.next_if(|(_, c)| *c == '}').is_some() => literal.push('}'),
+                '}' => {
+                    return Err(error(
+                        index,
+                        "Unexpected <}>, use <}}> for a literal brace".to_string(),
+                    ))
+                }
+                '{' => {
+                    let start = index + 1;
+                    let end = loop {
+                        match chars.next() {
+                            Some((end, '}')) => break end,
+                            Some((nested, '{')) => {
+                                return Err(error(
+                                    nested,
+                                    "Unexpected <{> inside a substitution".to_string(),
+                                ))
+                            }
+                            Some(_) => (),
+                            None => {
+                                return Err(error(
+                                    index,
+                                    "Unclosed <{>, use <{{> for a literal brace".to_string(),
+                                ))
+                            }
+                        }
+                    };
+                    let mut parts = input[start..end].split('|');
+                    let variable = parts.next().unwrap_or_default().trim();
+                    if variable.is_empty() {
+                        return Err(error(start, "Missing variable name".to_string()));
+                    }
+                    let mut filters = vec![];
+                    let mut offset = start + input[start..end].find('|').unwrap_or(0) + 1;
+                    for part in parts {
+                        filters.push(Filter::parse(part).map_err(|e| error(offset, e))?);
+                        offset += part.len() + 1;
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Substitution(Substitution {
+                        variable: variable.to_string(),
+                        filters,
+                    }));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Self { segments })
+    }
+
+    pub fn substitutions_mut(&mut self) -> impl Iterator<Item = &mut Substitution> {
+        self.segments.iter_mut().filter_map(|s| match s {
+            Segment::Substitution(substitution) => Some(substitution),
+            Segment::Literal(_) => None,
+        })
+    }
+
+    /// Render the template, using `lookup` to find the value of each variable, which
+    /// returns `None` for variables that aren't defined
+    pub fn render<F>(&self, mut lookup: F) -> Result<String>
+    where
+        F: FnMut(&str) -> Result<Option<String>>,
+    {
+        let mut output = String::new();
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Literal(literal) => output.push_str(literal),
+                Segment::Substitution(substitution) => {
+                    output.push_str(substitution.render(&mut lookup)?.as_str())
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl Substitution {
+    fn render<F>(&self, lookup: &mut F) -> Result<String>
+    where
+        F: FnMut(&str) -> Result<Option<String>>,
+    {
+        let default = self.filters.iter().find_map(|f| match f {
+            Filter::Default(value) => Some(value.clone()),
+            _ => None,
+        });
+        let mut value = match (lookup(self.variable.as_str())?, default) {
+            (Some(value), _) | (None, Some(value)) => value,
+            (None, None) => return Err(anyhow!("Variable <{}> not found", self.variable)),
+        };
+        for filter in self.filters.iter() {
+            value = filter.apply(value)?;
+        }
+        Ok(value)
+    }
+}
+
+impl fmt::Display for Template {
+    /// Write the template back out in the form it is parsed from
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Literal(literal) => {
+                    write!(f, "{}", literal.replace('{', "{{").replace('}', "}}"))?
+                }
+                Segment::Substitution(substitution) => {
+                    write!(f, "{{{}", substitution.variable)?;
+                    for filter in substitution.filters.iter() {
+                        write!(f, "|{}", filter)?;
+                    }
+                    write!(f, "}}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn render(input: &str, variables: &[(&str, &str)]) -> Result<String> {
+        let variables = variables
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        Template::parse(input)?.render(|name| Ok(variables.get(name).cloned()))
+    }
+
+    #[test]
+    fn test_parse() {
+        let template = Template::parse("echo {name|default:x|upper} done").unwrap();
+        assert_eq!(
+            template.segments,
+            vec![
+                Segment::Literal("echo ".to_string()),
+                Segment::Substitution(Substitution {
+                    variable: "name".to_string(),
+                    filters: vec![Filter::Default("x".to_string()), Filter::Upper],
+                }),
+                Segment::Literal(" done".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_escapes() {
+        assert_eq!(
+            render(
+                r#"echo '{{"name": "{name}"}}' ${{HOME}}"#,
+                &[("name", "pls")]
+            )
+            .unwrap(),
+            r#"echo '{"name": "pls"}' ${HOME}"#
+        );
+    }
+
+    #[test]
+    fn test_render_default() {
+        assert_eq!(
+            render("serve --port {port|default:8080}", &[]).unwrap(),
+            "serve --port 8080"
+        );
+        assert_eq!(
+            render("serve --port {port|default:8080}", &[("port", "9000")]).unwrap(),
+            "serve --port 9000"
+        );
+        assert_eq!(
+            render("serve --port {port}", &[]).unwrap_err().to_string(),
+            "Variable <port> not found"
+        );
+    }
+
+    #[test]
+    fn test_render_filters() {
+        assert_eq!(
+            render(
+                "{path|basename} {path|basename|upper} {msg|quote} {args|join:,} {args|join}",
+                &[
+                    ("path", "/src/main.rs"),
+                    ("msg", "hello world"),
+                    ("args", "a 'b c' d"),
+                ]
+            )
+            .unwrap(),
+            "main.rs MAIN.RS 'hello world' a,b c,d a b c d"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input: &str| Template::parse(input).unwrap_err().to_string();
+        assert_eq!(
+            error("echo {name"),
+            "Unclosed <{>, use <{{> for a literal brace at column 6 of <echo {name>"
+        );
+        assert_eq!(
+            error("echo }"),
+            "Unexpected <}>, use <}}> for a literal brace at column 6 of <echo }>"
+        );
+        assert_eq!(
+            error("a {} b"),
+            "Missing variable name at column 4 of <a {} b>"
+        );
+        assert_eq!(
+            error("{name|upper|shout}"),
+            "Unknown filter <shout> at column 13 of <{name|upper|shout}>"
+        );
+        assert_eq!(
+            error("{a{b}}"),
+            "Unexpected <{> inside a substitution at column 3 of <{a{b}}>"
+        );
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let input = "echo {{literal}} {name|default:x|join:,} {other}";
+        assert_eq!(Template::parse(input).unwrap().to_string(), input);
+    }
+}
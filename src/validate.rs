@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 
+use indexmap::IndexMap;
 use validator::ValidationError;
 
+use crate::config::{Args, Argument, EnvFile, VariableValue};
+
 pub fn non_empty_strings(value: &Vec<String>) -> Result<(), ValidationError> {
     for s in value {
         if s.is_empty() {
@@ -38,6 +41,81 @@ pub fn keys_and_values_non_empty_strings(
     Ok(())
 }
 
+pub fn env_files(value: &Vec<EnvFile>) -> Result<(), ValidationError> {
+    for env_file in value {
+        if env_file.path().is_empty() {
+            return Err(ValidationError::new("empty_env_file_path")
+                .with_message(std::borrow::Cow::from("env file path cannot be empty")));
+        }
+    }
+    Ok(())
+}
+
+pub fn variables(value: &HashMap<String, VariableValue>) -> Result<(), ValidationError> {
+    keys_non_empty_strings(value)?;
+    for v in value.values() {
+        match v {
+            VariableValue::Value(v) if v.is_empty() => {
+                return Err(ValidationError::new("invalid_hash_value")
+                    .with_message(std::borrow::Cow::from("value cannot be empty")));
+            }
+            VariableValue::Command { command } if command.is_empty() => {
+                return Err(ValidationError::new("empty_variable_command")
+                    .with_message(std::borrow::Cow::from("command cannot be empty")));
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+pub fn fixed_args(value: &Args) -> Result<(), ValidationError> {
+    match value {
+        Args::Line(line) if shlex::split(line).is_none() => {
+            Err(
+                ValidationError::new("invalid_argument").with_message(std::borrow::Cow::from(
+                    format!("args <{}> can't be split into words", line),
+                )),
+            )
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn arguments(value: &IndexMap<String, Argument>) -> Result<(), ValidationError> {
+    let invalid = |message: String| {
+        Err(ValidationError::new("invalid_argument").with_message(std::borrow::Cow::from(message)))
+    };
+    for (name, arg) in value.iter() {
+        if name.is_empty() {
+            return invalid("argument name cannot be empty".to_string());
+        }
+        if arg.flag.unwrap_or(false) {
+            if arg.positional.unwrap_or(false) {
+                return invalid(format!(
+                    "argument <{}> can't be both a flag and positional",
+                    name
+                ));
+            }
+            if arg.choices.is_some() || arg.default.is_some() {
+                return invalid(format!(
+                    "flag <{}> can't have choices or a default as it doesn't take a value",
+                    name
+                ));
+            }
+        }
+        if let (Some(choices), Some(default)) = (&arg.choices, &arg.default) {
+            if !choices.contains(default) {
+                return invalid(format!(
+                    "default <{}> of argument <{}> is not one of its choices",
+                    default, name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -114,4 +192,41 @@ mod test {
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "key cannot be empty");
     }
+
+    #[test]
+    fn variables_empty_command() {
+        let res = variables(&HashMap::from([(
+            "foo".to_string(),
+            VariableValue::Command {
+                command: "".to_string(),
+            },
+        )]));
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "command cannot be empty");
+    }
+
+    #[test]
+    fn arguments_unclosed_quote() {
+        let res = fixed_args(&Args::Line("test 'unclosed".to_string()));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "args <test 'unclosed> can't be split into words"
+        );
+    }
+
+    #[test]
+    fn arguments_flag_with_default() {
+        let res = arguments(&IndexMap::from([(
+            "verbose".to_string(),
+            Argument {
+                flag: Some(true),
+                default: Some("yes".to_string()),
+                ..Default::default()
+            },
+        )]));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "flag <verbose> can't have choices or a default as it doesn't take a value"
+        );
+    }
 }
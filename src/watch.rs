@@ -36,7 +36,7 @@ impl Debug for WatchTrigger<'_> {
 }
 
 impl WatchTrigger<'_> {
-    fn get_one(target: &Target) -> Result<WatchTrigger> {
+    fn get_one(target: &Target) -> Result<WatchTrigger<'_>> {
         let paths = if let Ok(artifact) = target.artifact() {
             let artifact_info = artifact.artifact_info();
             // TODO: variables
@@ -163,10 +163,14 @@ fn find_matching_paths(patterns: &[&Pattern]) -> HashSet<String> {
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
+    use std::sync::{Mutex, OnceLock};
+
     use super::*;
 
     use crate::{
+        config::Sources,
         name::FullyQualifiedName,
         target::{any_artifact_target, any_target, Command, CommandInfo, NullCommand, TargetInfo},
     };
@@ -200,8 +204,22 @@ mod tests {
         let mut context = Context {
             variables: HashMap::new(),
             targets: HashMap::new(),
+            definitions: HashMap::new(),
             config_path: "<test>".to_string(),
             globals: HashMap::new(),
+            project_globals: HashMap::new(),
+            current_project: String::new(),
+            env_files: HashMap::new(),
+            env: HashMap::new(),
+            git: OnceLock::new(),
+            expanded: Mutex::new(HashMap::new()),
+            command_outputs: Mutex::new(HashMap::new()),
+            used_inputs: Mutex::new(HashMap::new()),
+            overrides: HashMap::new(),
+            env_overrides: vec![],
+            unknown_keys: vec![],
+            origins: HashMap::new(),
+            sources: Sources::default(),
         };
         let target = any_target();
         context
@@ -221,8 +239,22 @@ mod tests {
         let mut context = Context {
             variables: HashMap::new(),
             targets: HashMap::new(),
+            definitions: HashMap::new(),
             config_path: "<test>".to_string(),
             globals: HashMap::new(),
+            project_globals: HashMap::new(),
+            current_project: String::new(),
+            env_files: HashMap::new(),
+            env: HashMap::new(),
+            git: OnceLock::new(),
+            expanded: Mutex::new(HashMap::new()),
+            command_outputs: Mutex::new(HashMap::new()),
+            used_inputs: Mutex::new(HashMap::new()),
+            overrides: HashMap::new(),
+            env_overrides: vec![],
+            unknown_keys: vec![],
+            origins: HashMap::new(),
+            sources: Sources::default(),
         };
         let target = any_artifact_target();
         context
@@ -246,8 +278,22 @@ mod tests {
         let mut context = Context {
             variables: HashMap::new(),
             targets: HashMap::new(),
+            definitions: HashMap::new(),
             config_path: "<test>".to_string(),
             globals: HashMap::new(),
+            project_globals: HashMap::new(),
+            current_project: String::new(),
+            env_files: HashMap::new(),
+            env: HashMap::new(),
+            git: OnceLock::new(),
+            expanded: Mutex::new(HashMap::new()),
+            command_outputs: Mutex::new(HashMap::new()),
+            used_inputs: Mutex::new(HashMap::new()),
+            overrides: HashMap::new(),
+            env_overrides: vec![],
+            unknown_keys: vec![],
+            origins: HashMap::new(),
+            sources: Sources::default(),
         };
         let dependency = any_target();
         context
@@ -255,16 +301,19 @@ mod tests {
             .insert(dependency.target_info().name.clone(), dependency.clone());
         let target_info = TargetInfo {
             name: FullyQualifiedName {
+                project: String::new(),
                 tag: "test".to_string(),
                 name: "target".to_string(),
             },
             requires: vec![dependency.target_info().name.clone()],
             variables: HashMap::new(),
             description: None,
+            tags: vec![],
+            extends: None,
         };
         let target = Target::Command(Command::Null(NullCommand {
             target_info,
-            command_info: CommandInfo { daemon: false },
+            command_info: CommandInfo::default(),
         }));
         context
             .targets
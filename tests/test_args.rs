@@ -0,0 +1,214 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+const DEPLOY_CONFIG: &str = r#"
+    [command.exec.deploy]
+    description = "Deploy a service"
+    command = "echo deploying {args.service} to {args.env} {args.dry-run}"
+
+    [command.exec.deploy.arguments]
+    env = { choices = ["dev", "prod"], required = true, help = "Where to deploy" }
+    service = { positional = true, default = "web" }
+    dry-run = { flag = true, short = "n" }
+"#;
+
+#[test]
+fn test_declared_args() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(DEPLOY_CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run")
+        .arg("deploy")
+        .arg("--env")
+        .arg("prod")
+        .arg("api")
+        .arg("-n");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("deploying api to prod true\n"));
+}
+
+#[test]
+fn test_declared_args_defaults() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(DEPLOY_CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("deploy").arg("--env").arg("dev");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("deploying web to dev false\n"));
+}
+
+#[test]
+fn test_declared_args_invalid() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(DEPLOY_CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("deploy").arg("--env").arg("staging");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid arguments for <deploy>"))
+        .stderr(predicate::str::contains(
+            "invalid value 'staging' for '--env <ENV>'",
+        ));
+}
+
+#[test]
+fn test_declared_args_help() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(DEPLOY_CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("deploy").arg("--help");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("Deploy a service\n"))
+        .stdout(predicate::str::contains(
+            "Usage: pls run deploy [OPTIONS] --env <ENV> [SERVICE]",
+        ))
+        .stdout(predicate::str::contains(
+            "Where to deploy [possible values: dev, prod]",
+        ));
+}
+
+#[test]
+fn test_undeclared_args_are_passed_through() {
+    let config_src = r#"
+        [command.exec.hello]
+        command = "echo"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("hello").arg("-a").arg("--world");
+
+    cmd.assert().success().stdout(predicate::eq("-a --world\n"));
+}
+
+const CARGO_CONFIG: &str = r#"
+    [command.exec.cargo]
+    command = "echo cargo"
+
+    [command.exec.test]
+    extends = "cargo"
+    args = "test"
+    default_args = "--lib"
+
+    [command.exec.test-all]
+    extends = "test"
+    args = ["--workspace"]
+"#;
+
+#[test]
+fn test_fixed_args_from_extends() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CARGO_CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("test-all").arg("--nocapture");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("cargo test --workspace --nocapture\n"));
+}
+
+#[test]
+fn test_fixed_args_with_default_args() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CARGO_CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("test");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("cargo test --lib\n"));
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("test").arg("integration");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("cargo test integration\n"));
+}
+
+#[test]
+fn test_fixed_args_for_declared_args() {
+    let config_src = format!(
+        r#"
+        {}
+        [command.exec.deploy-prod]
+        extends = "deploy"
+        args = "--env prod"
+    "#,
+        DEPLOY_CONFIG
+    );
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src.as_str());
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("deploy-prod").arg("api");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("deploying api to prod false\n"));
+}
+
+#[test]
+fn test_declared_args_are_escaped() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        [command.exec.say]
+        command = "printf '[%s]\n' {args.msg}"
+
+        [command.exec.say.arguments]
+        msg = { required = true }
+        "#,
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run")
+        .arg("say")
+        .arg("--msg")
+        .arg("hello 'quoted' world");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("[hello 'quoted' world]\n"));
+}
+
+#[test]
+fn test_fixed_args_with_declared_args_in_one_target() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        [command.exec.deploy]
+        command = "echo deploying {args.service} to {args.env}"
+        args = "--env prod"
+
+        [command.exec.deploy.arguments]
+        env = { choices = ["dev", "prod"], required = true }
+        service = { positional = true, default = "web" }
+        "#,
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("deploy").arg("api");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("deploying api to prod\n"));
+}
@@ -0,0 +1,77 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+fn git(test_context: &common::TestContext, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=pls", "-c", "user.email=pls@example.com"])
+        .args(args)
+        .current_dir(test_context.workdir())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+#[test]
+fn test_git_variables() {
+    let config_src = r#"
+        [command.exec.version]
+        command = "echo {git.sha} {git.branch} {git.dirty}"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    git(&test_context, &["init", "-q", "-b", "main"]);
+    git(&test_context, &["add", "pls.toml"]);
+    git(&test_context, &["commit", "-q", "-m", "Initial commit"]);
+    let sha = git(&test_context, &["rev-parse", "HEAD"]);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("version");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq(format!("{} main false", sha)).trim());
+}
+
+#[test]
+fn test_git_variables_outside_repository() {
+    let config_src = r#"
+        [command.exec.version]
+        command = "echo {git.sha}"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run")
+        .arg("version")
+        .env("GIT_CEILING_DIRECTORIES", "/");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Could not read git information"));
+}
+
+#[test]
+fn test_env_and_pls_variables() {
+    let config_src = r#"
+        [command.exec.info]
+        command = "echo {env.PLS_TEST_GREETING} {pls.target}"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("info").env("PLS_TEST_GREETING", "hello");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("hello command.exec.info").trim());
+}
@@ -0,0 +1,157 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+#[test]
+fn test_check_clean_config() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        [globals]
+        greeting = "hello"
+
+        [command.exec.greet]
+        command = "echo {globals.greeting} {name}"
+        variables = { name = "world" }
+        "#,
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("check");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("Found 0 errors and 0 warnings\n"));
+}
+
+#[test]
+fn test_check_unknown_keys() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        [command.exec.build]
+        command = "echo build"
+        reqiures = ["lint"]
+
+        [artifact.exec.bin]
+        command = "echo bin"
+        if_file_changed = ["src"]
+        "#,
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("check");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "error: Unknown key <command.exec.build.reqiures>",
+        ))
+        .stdout(predicate::str::contains(
+            "error: Unknown key <artifact.exec.bin.if_file_changed>",
+        ))
+        .stderr(predicate::str::contains("Found 2 errors and 0 warnings"));
+}
+
+#[test]
+fn test_check_undefined_variables() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        [command.exec.base]
+        command = "echo {subcommand}"
+
+        [command.exec.test]
+        extends = "base"
+        variables = { subcommand = "test" }
+
+        [command.exec.lint]
+        command = "echo {flags} {globals.mode} {level|default:1} {args.fix}"
+        "#,
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("check");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "error: Variable <flags> used in <command.exec.lint> is not defined",
+        ))
+        .stdout(predicate::str::contains(
+            "error: Global <mode> used in <command.exec.lint> is not defined",
+        ))
+        .stdout(predicate::str::contains(
+            "error: Argument <fix> used in <command.exec.lint> is not declared",
+        ))
+        .stdout(predicate::str::contains("subcommand").not())
+        .stdout(predicate::str::contains("level").not());
+}
+
+#[test]
+fn test_check_warnings() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        [globals]
+        unused = "x"
+
+        [command.exec.deploy]
+        command = "echo {bin.output.path}"
+        variables = { spare = "1" }
+        tags = ["ci"]
+
+        [command.exec.forgotten]
+        command = "echo forgotten"
+
+        [artifact.exec.bin]
+        command = "echo bin"
+        if_files_changed = ["src/*.rs", "missing/*.c"]
+        "#,
+    );
+    test_context.workdir.child("src/main.rs").touch().unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("check");
+
+    cmd.assert().success().stdout(predicate::eq(
+        "warning: <artifact.exec.bin> isn't reachable from any tagged target\n\
+         warning: <command.exec.deploy> uses outputs of <artifact.exec.bin> without requiring it, so they won't be set\n\
+         warning: <command.exec.forgotten> isn't reachable from any tagged target\n\
+         warning: Global <globals.unused> is never used\n\
+         warning: Variable <spare> of <command.exec.deploy> is never used\n\
+         warning: if_files_changed <missing/*.c> of <artifact.exec.bin> matches no files\n\
+         Found 0 errors and 6 warnings\n",
+    ));
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("check").arg("--deny-warnings");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Found 0 errors and 6 warnings"));
+}
+
+#[test]
+fn test_strict_rejects_unknown_keys() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        [command.exec.build]
+        command = "echo build"
+        reqiures = ["lint"]
+        "#,
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("build");
+    cmd.assert().success().stdout(predicate::eq("build\n"));
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("--strict").arg("run").arg("build");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "error: Unknown key <command.exec.build.reqiures>",
+    ));
+}
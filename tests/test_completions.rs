@@ -0,0 +1,40 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+#[test]
+fn test_completions_complete_target_names() {
+    // The script doesn't depend on the config, so it works even when it is broken
+    let test_context = common::TestContext::new();
+    test_context.write_config("not a config");
+    for (shell, defines) in [
+        ("bash", "complete -F _pls_with_targets"),
+        ("zsh", "compdef _pls_with_targets pls"),
+        (
+            "fish",
+            "complete -c pls -n \"__fish_seen_subcommand_from build\"",
+        ),
+    ] {
+        let mut cmd = test_context.get_command();
+        cmd.arg("completions").arg(shell);
+        cmd.assert().success().stdout(
+            predicate::str::contains(defines).and(predicate::str::contains("--format names")),
+        );
+    }
+}
+
+#[test]
+fn test_completions_bash_script_is_valid() {
+    let test_context = common::TestContext::new();
+    test_context.write_config("");
+    let mut cmd = test_context.get_command();
+    cmd.arg("completions").arg("bash");
+    let script = test_context.workdir().join("pls.bash");
+    std::fs::write(&script, cmd.output().unwrap().stdout).unwrap();
+    std::process::Command::new("bash")
+        .arg("-n")
+        .arg(&script)
+        .assert()
+        .success();
+}
@@ -0,0 +1,135 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+#[test]
+fn test_parse_error_shows_line() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        "[command.exec.build]\n\
+         command = 3\n",
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert().failure().stderr(
+        predicate::str::contains(
+            "invalid type: integer `3`, expected a string\n \
+         --> ",
+        )
+        .and(predicate::str::contains(
+            "pls.toml:2:11\n  \
+         |\n\
+         2 | command = 3\n  \
+         |           ^\n",
+        )),
+    );
+}
+
+#[test]
+fn test_validation_errors_are_collected() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        "include = [\"more.toml\"]\n\
+         \n\
+         [command.container.web]\n\
+         image = \"\"\n",
+    );
+    test_context
+        .workdir
+        .child("more.toml")
+        .write_str(
+            "[command.exec.build]\n\
+             command = \"\"\n",
+        )
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Found 2 errors in the config"))
+        .stderr(predicate::str::contains(
+            "Error validating <command.container.web.image>: image must not be empty\n",
+        ))
+        .stderr(predicate::str::contains(
+            "pls.toml:4:9\n  \
+             |\n\
+             4 | image = \"\"\n  \
+             |         ^^\n",
+        ))
+        .stderr(predicate::str::contains(
+            "Error validating <command.exec.build.command>: Command must not be empty\n",
+        ))
+        .stderr(predicate::str::contains(
+            "more.toml:2:11\n  \
+             |\n\
+             2 | command = \"\"\n  \
+             |           ^^\n",
+        ));
+}
+
+#[test]
+fn test_extends_errors_are_collected() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        "[command.exec.first]\n\
+         extends = \"missing\"\n\
+         \n\
+         [command.exec.second]\n\
+         extends = \"gone\"\n",
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Found 2 errors in the config"))
+        .stderr(predicate::str::contains(
+            "<command.exec.first> extends non-existent <command.exec.missing>\n",
+        ))
+        .stderr(predicate::str::contains(
+            "2 | extends = \"missing\"\n  \
+             |           ^^^^^^^^^\n",
+        ))
+        .stderr(predicate::str::contains(
+            "<command.exec.second> extends non-existent <command.exec.gone>\n",
+        ));
+}
+
+#[test]
+fn test_validation_and_reference_errors_are_collected() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        "[command.exec.build]\n\
+         command = \"\"\n\
+         \n\
+         [command.exec.test]\n\
+         command = \"true\"\n\
+         requires = [\"missing\"]\n",
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Found 2 errors in the config"))
+        .stderr(predicate::str::contains(
+            "Error validating <command.exec.build.command>: Command must not be empty\n",
+        ))
+        .stderr(predicate::str::contains(
+            "Invalid reference from <command.exec.test>: Non-existent reference <missing>\n",
+        ))
+        .stderr(predicate::str::contains(
+            "pls.toml:6:12\n  \
+             |\n\
+             6 | requires = [\"missing\"]\n  \
+             |            ^^^^^^^^^^^\n",
+        ));
+}
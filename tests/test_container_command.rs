@@ -1,4 +1,7 @@
+use std::os::unix::fs::PermissionsExt;
+
 use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
 use predicates::prelude::*;
 
 mod common;
@@ -39,3 +42,45 @@ fn test_extends() {
 
     cmd.assert().success().stdout(predicate::eq("world").trim());
 }
+
+#[test]
+fn test_stop_keeps_state_when_container_status_is_unknown() {
+    let config_src = r#"
+        [command.container.db]
+        image = "postgres"
+        daemon = true
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    let state = test_context
+        .workdir
+        .child(".pls/command.container.db/container.toml");
+    state
+        .write_str("name = \"db-abc\"\ncreated_network = false\nvolumes = []\n")
+        .unwrap();
+    // A podman that can't reach its service, so fails whatever it is asked to do
+    let bin = test_context.workdir.child("bin");
+    bin.create_dir_all().unwrap();
+    let podman = bin.child("podman");
+    podman
+        .write_str("#!/bin/sh\necho 'Error: cannot connect to Podman' >&2\nexit 125\n")
+        .unwrap();
+    std::fs::set_permissions(podman.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!(
+        "{}:{}",
+        bin.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.env("PATH", path).arg("stop").arg("db");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Error stopping <command.container.db>, as whether it is running isn't known",
+        ))
+        .stderr(predicate::str::contains("cannot connect to Podman"));
+    state.assert(predicate::path::exists());
+}
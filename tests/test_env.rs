@@ -0,0 +1,87 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+#[test]
+fn test_global_env() {
+    let config_src = r#"
+        [globals]
+        mode = "release"
+
+        [env]
+        BUILD_MODE = "{globals.mode}"
+        LOG_LEVEL = "info"
+
+        [command.exec.env]
+        command = "env"
+        env = ["LOG_LEVEL=debug"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("env");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("BUILD_MODE=release\n"))
+        .stdout(predicate::str::contains("LOG_LEVEL=debug\n"));
+}
+
+#[test]
+fn test_clear_env() {
+    let config_src = r#"
+        [env]
+        FROM_CONFIG = "yes"
+
+        [command.exec.env]
+        command = "env"
+        clear_env = true
+        pass_env = ["PATH", "PLS_TEST_*"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run")
+        .arg("env")
+        .env("PLS_TEST_KEPT", "1")
+        .env("PLS_OTHER", "2");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("PLS_TEST_KEPT=1\n"))
+        .stdout(predicate::str::contains("FROM_CONFIG=yes\n"))
+        .stdout(predicate::str::contains("PLS_OTHER").not())
+        .stdout(predicate::str::contains("HOME=").not());
+}
+
+#[test]
+fn test_pass_env_for_container() {
+    let config_src = r#"
+        [command.container.env]
+        image = "docker.io/library/alpine:latest"
+        command = "env"
+        pass_env = ["PLS_TEST_*"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("show")
+        .arg("env")
+        .arg("--")
+        .arg("-a")
+        .env("PLS_TEST_KEPT", "1")
+        .env("PLS_OTHER", "2");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("-e PLS_TEST_KEPT "))
+        .stdout(predicate::str::contains("PLS_TEST_KEPT=1").not())
+        .stdout(predicate::str::contains("PLS_OTHER").not());
+}
@@ -0,0 +1,130 @@
+use std::os::unix::fs::PermissionsExt;
+
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+#[test]
+fn test_env_file() {
+    let config_src = r#"
+        env_file = [".env"]
+
+        [command.exec.env]
+        command = "env"
+        env_file = [".env.target", { path = ".env.local", required = false }]
+        env = ["INLINE=inline"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context
+        .workdir
+        .child(".env")
+        .write_str("# Shared settings\nexport HOST=localhost\nINLINE=from file\n")
+        .unwrap();
+    test_context
+        .workdir
+        .child(".env.target")
+        .write_str("URL=\"http://${HOST}:$PORT\"\nPORT=8080\nQUOTED='$HOST'\n")
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("env");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("HOST=localhost\n"))
+        .stdout(predicate::str::contains("URL=http://localhost:\n"))
+        .stdout(predicate::str::contains("PORT=8080\n"))
+        .stdout(predicate::str::contains("QUOTED=$HOST\n"))
+        .stdout(predicate::str::contains("INLINE=inline\n"));
+}
+
+#[test]
+fn test_env_file_missing() {
+    let config_src = r#"
+        [command.exec.env]
+        command = "env"
+        env_file = [".env"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("env");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Env file <.env> not found"));
+}
+
+#[test]
+fn test_env_file_for_container() {
+    let config_src = r#"
+        [command.container.env]
+        image = "docker.io/library/alpine:latest"
+        command = "env"
+        env_file = [".env"]
+        env = ["B=inline"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context
+        .workdir
+        .child(".env")
+        .write_str("A=1\nB=2\n")
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("show").arg("env").arg("--").arg("-a");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("-e A -e B "))
+        .stdout(predicate::str::contains("A=1").not());
+}
+
+#[test]
+fn test_env_file_values_are_not_in_podman_arguments() {
+    let config_src = r#"
+        [command.container.env]
+        image = "docker.io/library/alpine:latest"
+        command = "env"
+        env_file = [".env"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context
+        .workdir
+        .child(".env")
+        .write_str("TOKEN=secret\n")
+        .unwrap();
+    // A podman that shows what it was run with rather than running anything
+    let bin = test_context.workdir.child("bin");
+    bin.create_dir_all().unwrap();
+    let podman = bin.child("podman");
+    podman
+        .write_str("#!/bin/sh\necho \"args: $*\"\necho \"TOKEN is $TOKEN\"\n")
+        .unwrap();
+    std::fs::set_permissions(podman.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!(
+        "{}:{}",
+        bin.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.env("PATH", path).arg("run").arg("env");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(" -e TOKEN "))
+        .stdout(predicate::str::contains("TOKEN is secret"))
+        .stdout(predicate::str::contains("TOKEN=secret").not())
+        .stderr(predicate::str::contains("secret").not());
+}
@@ -0,0 +1,64 @@
+use std::os::unix::fs::PermissionsExt;
+
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+/// A `podman` that logs how it is called to `podman.log`, lists `db-abc` as a container
+/// of the project and fails to inspect it, as it does when it can't reach its service
+fn fake_podman(test_context: &common::TestContext) -> String {
+    let bin = test_context.workdir.child("bin");
+    bin.create_dir_all().unwrap();
+    let podman = bin.child("podman");
+    podman
+        .write_str(&format!(
+            "#!/bin/sh\n\
+             echo \"$@\" >> {}\n\
+             case \"$1 $2\" in\n\
+             \"container inspect\") echo 'Error: cannot connect to Podman' >&2; exit 125 ;;\n\
+             \"ps -a\") echo db-abc ;;\n\
+             esac\n",
+            test_context.workdir().join("podman.log").display()
+        ))
+        .unwrap();
+    std::fs::set_permissions(podman.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    format!(
+        "{}:{}",
+        bin.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    )
+}
+
+#[test]
+fn test_gc_removes_nothing_when_container_status_is_unknown() {
+    let config_src = r#"
+        [command.container.db]
+        image = "postgres"
+        daemon = true
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context
+        .workdir
+        .child(".pls/command.container.db/container.toml")
+        .write_str("name = \"db-abc\"\ncreated_network = false\nvolumes = []\n")
+        .unwrap();
+    let path = fake_podman(&test_context);
+
+    let mut cmd = test_context.get_command();
+    cmd.env("PATH", path).arg("gc");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Not removing anything as <command.container.db> may be in use",
+        ))
+        .stderr(predicate::str::contains("cannot connect to Podman"));
+    test_context
+        .workdir
+        .child("podman.log")
+        .assert(predicate::str::contains("rm -f").not());
+}
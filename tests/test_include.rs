@@ -0,0 +1,174 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+#[test]
+fn test_include() {
+    let config_src = r#"
+        include = ["ci/pls.toml", "services/*/pls.toml"]
+
+        [command.exec.hello]
+        command = "echo hello"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context
+        .workdir
+        .child("ci/pls.toml")
+        .write_str(
+            r#"
+            [command.exec.lint]
+            command = "echo lint"
+            "#,
+        )
+        .unwrap();
+    test_context
+        .workdir
+        .child("services/api/pls.toml")
+        .write_str(
+            r#"
+            [command.exec.api]
+            command = "echo api"
+            "#,
+        )
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert().success().stdout(predicate::eq(
+        "command.exec.api - \ncommand.exec.hello - \ncommand.exec.lint - \n",
+    ));
+}
+
+#[test]
+fn test_include_paths_are_relative_to_included_file() {
+    let config_src = r#"
+        include = ["sub/pls.toml"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context
+        .workdir
+        .child("sub/pls.toml")
+        .write_str(
+            r#"
+            [artifact.exec.copy]
+            command = "cp hello world"
+            updates_paths = ["world"]
+            if_files_changed = ["hello"]
+            "#,
+        )
+        .unwrap();
+    test_context.workdir.child("sub/hello").touch().unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("build").arg("copy");
+
+    cmd.assert().success();
+
+    test_context
+        .workdir
+        .child("sub/world")
+        .assert(predicate::path::exists());
+}
+
+#[test]
+fn test_include_duplicate() {
+    let config_src = r#"
+        include = ["other.toml"]
+
+        [command.exec.hello]
+        command = "echo hello"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context
+        .workdir
+        .child("other.toml")
+        .write_str(
+            r#"
+            [command.exec.hello]
+            command = "echo goodbye"
+            "#,
+        )
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Target <command.exec.hello> is defined in both <",
+        ))
+        .stderr(predicate::str::contains("pls.toml> and <"))
+        .stderr(predicate::str::contains("other.toml>"));
+}
+
+#[test]
+fn test_include_missing_file() {
+    let config_src = r#"
+        include = ["missing.toml"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Included file <"))
+        .stderr(predicate::str::contains("missing.toml> not found"));
+}
+
+#[test]
+fn test_include_keeps_inherited_cwd() {
+    let config_src = r#"
+        include = ["sub/pls.toml"]
+
+        [command.exec.base]
+        command = "pwd"
+        cwd = "tools"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context
+        .workdir
+        .child("sub/pls.toml")
+        .write_str(
+            r#"
+            [command.exec.inherited]
+            extends = "base"
+
+            [command.exec.local]
+            command = "pwd"
+            "#,
+        )
+        .unwrap();
+    test_context
+        .workdir
+        .child("tools")
+        .create_dir_all()
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("inherited");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("/tools\n"));
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("local");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("/sub\n"));
+}
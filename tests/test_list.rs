@@ -24,3 +24,140 @@ fn test_list() {
         "artifact.exec.copy - \ncommand.container.hello - Hello world\n",
     ));
 }
+
+#[test]
+fn test_list_json() {
+    let config_src = r#"
+        [command.exec.base]
+        command = "echo base"
+
+        [command.exec.server]
+        extends = "base"
+        daemon = true
+        requires = ["build"]
+        description = "Run the server"
+
+        [artifact.exec.build]
+        command = "make"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list").arg("--format").arg("json").arg("server");
+
+    cmd.assert().success().stdout(predicate::eq(
+        r#"[
+  {
+    "name": "command.exec.server",
+    "type": "command.exec",
+    "description": "Run the server",
+    "daemon": true,
+    "artifact": false,
+    "requires": [
+      "artifact.exec.build"
+    ],
+    "extends": "command.exec.base",
+    "tags": []
+  }
+]
+"#,
+    ));
+}
+
+#[test]
+fn test_list_filters() {
+    let config_src = r#"
+        [artifact.exec.build]
+        command = "make"
+
+        [command.exec.server]
+        command = "serve"
+        daemon = true
+
+        [command.exec.test_unit]
+        command = "test unit"
+
+        [command.exec.test_integration]
+        command = "test integration"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list").arg("--artifacts");
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("artifact.exec.build - \n"));
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list").arg("--daemons");
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("command.exec.server - \n"));
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list").arg("test_*");
+    cmd.assert().success().stdout(predicate::eq(
+        "command.exec.test_integration - \ncommand.exec.test_unit - \n",
+    ));
+}
+
+#[test]
+fn test_list_tree() {
+    let config_src = r#"
+        [artifact.exec.build]
+        command = "make"
+
+        [command.exec.deploy]
+        command = "deploy"
+        requires = ["test"]
+
+        [command.exec.test]
+        command = "test"
+        requires = ["build"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list").arg("--tree");
+
+    cmd.assert().success().stdout(predicate::eq(
+        "command.exec.deploy - \n  command.exec.test - \n    artifact.exec.build - \n",
+    ));
+}
+
+#[test]
+fn test_list_names() {
+    let config_src = r#"
+        [artifact.exec.build]
+        command = "make"
+
+        [command.exec.build]
+        command = "make run"
+
+        [command.exec.server]
+        command = "serve"
+        daemon = true
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list").arg("--format").arg("names");
+    cmd.assert().success().stdout(predicate::eq(
+        "artifact.exec.build\ncommand.exec.build\nserver\n",
+    ));
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list")
+        .arg("--daemons")
+        .arg("--format")
+        .arg("names");
+    cmd.assert().success().stdout(predicate::eq("server\n"));
+}
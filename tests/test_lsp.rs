@@ -0,0 +1,215 @@
+use assert_fs::prelude::*;
+use serde_json::{json, Value};
+
+mod common;
+
+const CONFIG: &str = r#"
+[command.container.db]
+image = "postgres"
+
+[command.exec.build]
+requires = ["db"]
+command = "cargo build"
+"#;
+
+fn frame(message: Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+/// Open `text` as the config file of the test context, make the requests and return
+/// the messages the server sends back
+fn session(test_context: &common::TestContext, text: &str, requests: Vec<Value>) -> Vec<Value> {
+    let uri = uri_of(test_context, "pls.toml");
+    let mut messages = vec![open(&uri, text)];
+    for (id, mut request) in requests.into_iter().enumerate() {
+        request["jsonrpc"] = json!("2.0");
+        request["id"] = json!(id + 1);
+        request["params"]["textDocument"] = json!({"uri": uri});
+        messages.push(request);
+    }
+    exchange(test_context, messages)
+}
+
+fn uri_of(test_context: &common::TestContext, path: &str) -> String {
+    format!("file://{}", test_context.workdir().join(path).display())
+}
+
+fn open(uri: &str, text: &str) -> Value {
+    json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+        "textDocument": {"uri": uri, "languageId": "toml", "version": 1, "text": text}
+    }})
+}
+
+/// Send the messages to a server between starting and shutting it down, and return the
+/// messages it sends back
+fn exchange(test_context: &common::TestContext, messages: Vec<Value>) -> Vec<Value> {
+    let mut all = vec![
+        json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {"capabilities": {}}}),
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+    ];
+    all.extend(messages);
+    all.push(json!({"jsonrpc": "2.0", "id": 99, "method": "shutdown"}));
+    all.push(json!({"jsonrpc": "2.0", "method": "exit"}));
+    let input = all.into_iter().map(frame).collect::<String>();
+    let mut cmd = assert_cmd::Command::from_std(test_context.get_command());
+    cmd.arg("lsp").write_stdin(input);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let mut output = String::from_utf8(output).unwrap();
+    let mut received = vec![];
+    while let Some((header, rest)) = output.split_once("\r\n\r\n") {
+        let length = header
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+        received.push(serde_json::from_str(&rest[..length]).unwrap());
+        output = rest[length..].to_string();
+    }
+    received
+}
+
+fn response(messages: &[Value], id: u64) -> &Value {
+    &messages.iter().find(|m| m["id"] == json!(id)).unwrap()["result"]
+}
+
+#[test]
+fn test_lsp_publishes_errors_in_unsaved_text() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CONFIG);
+    let messages = session(&test_context, &CONFIG.replace("cargo build", ""), vec![]);
+    let published = messages
+        .iter()
+        .find(|m| m["method"] == "textDocument/publishDiagnostics")
+        .unwrap();
+    let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0]["message"],
+        "Error validating <command.exec.build.command>: Command must not be empty"
+    );
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({"line": 6, "character": 10})
+    );
+}
+
+#[test]
+fn test_lsp_completes_and_finds_targets() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CONFIG);
+    let text = CONFIG.replace("cargo build", "cargo build --network {db.outputs.}");
+    let position = json!({"line": 6, "character": 45});
+    let messages = session(
+        &test_context,
+        &text,
+        vec![
+            json!({"method": "textDocument/completion", "params": {"position": position}}),
+            json!({"method": "textDocument/definition", "params": {"position": position}}),
+            json!({"method": "textDocument/hover", "params": {"position": position}}),
+        ],
+    );
+    let labels = response(&messages, 1)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(labels, ["name", "network"]);
+    assert_eq!(
+        response(&messages, 2)["range"]["start"],
+        json!({"line": 1, "character": 19})
+    );
+    let hover = response(&messages, 3)["contents"]["value"]
+        .as_str()
+        .unwrap();
+    assert!(hover.contains("image = \"postgres\""), "{}", hover);
+}
+
+#[test]
+fn test_lsp_answers_invalid_params_with_error() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CONFIG);
+    let position = json!({"line": 5, "character": 13});
+    let messages = session(
+        &test_context,
+        CONFIG,
+        vec![
+            json!({"method": "textDocument/completion", "params": {}}),
+            json!({"method": "textDocument/hover", "params": {"position": position}}),
+        ],
+    );
+    let error = &messages.iter().find(|m| m["id"] == json!(1)).unwrap()["error"];
+    assert_eq!(error["code"], json!(-32602));
+    let hover = response(&messages, 2)["contents"]["value"]
+        .as_str()
+        .unwrap();
+    assert!(hover.contains("image = \"postgres\""), "{}", hover);
+}
+
+#[test]
+fn test_lsp_clears_and_rechecks_when_closing() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        projects = ["api"]
+
+        [command.exec.database]
+        command = "true"
+        "#,
+    );
+    let api_text = r#"
+        [command.exec.test]
+        command = "true"
+        requires = [":db"]
+        "#;
+    test_context
+        .workdir
+        .child("api/pls.toml")
+        .write_str(api_text)
+        .unwrap();
+    let root = uri_of(&test_context, "pls.toml");
+    let api = uri_of(&test_context, "api/pls.toml");
+    let unsaved = r#"
+        projects = ["api"]
+
+        [command.exec.db]
+        command = "true"
+
+        [command.exec.lint]
+        command = ""
+        "#;
+    let messages = exchange(
+        &test_context,
+        vec![
+            open(&root, unsaved),
+            open(&api, api_text),
+            json!({"jsonrpc": "2.0", "method": "textDocument/didClose", "params": {
+                "textDocument": {"uri": root}
+            }}),
+        ],
+    );
+    let last_published = |uri: &str| {
+        messages
+            .iter()
+            .rfind(|m| {
+                m["method"] == "textDocument/publishDiagnostics" && m["params"]["uri"] == uri
+            })
+            .unwrap()["params"]["diagnostics"]
+            .as_array()
+            .unwrap()
+            .clone()
+    };
+    // The error in the unsaved text is gone, and the saved text is missing `db`
+    assert!(last_published(&root).is_empty());
+    let diagnostics = last_published(&api);
+    assert_eq!(diagnostics.len(), 1);
+    assert!(
+        diagnostics[0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Non-existent reference <:db>"),
+        "{}",
+        diagnostics[0]
+    );
+}
@@ -0,0 +1,143 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+#[test]
+fn test_set_variables() {
+    let config_src = r#"
+        [globals]
+        registry = "docker.io"
+
+        [command.exec.push]
+        command = "echo pushing {image} to {globals.registry}"
+        variables = { image = "app:{tag}", tag = "latest" }
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("--set")
+        .arg("globals.registry=localhost:5000")
+        .arg("--set")
+        .arg("push.tag=dev")
+        .arg("run")
+        .arg("push");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("pushing app:dev to localhost:5000\n"));
+}
+
+#[test]
+fn test_set_unknown_target() {
+    let config_src = r#"
+        [command.exec.push]
+        command = "echo pushing"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("--set").arg("pull.tag=dev").arg("run").arg("push");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Target <pull> not found for --set <pull.tag=dev>",
+    ));
+}
+
+#[test]
+fn test_env_override() {
+    let config_src = r#"
+        [env]
+        RUST_LOG = "info"
+
+        [command.exec.env]
+        command = "env"
+        env = ["RUST_LOG=warn"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("--env").arg("RUST_LOG=debug").arg("run").arg("env");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("RUST_LOG=debug\n"))
+        .stdout(predicate::str::contains("RUST_LOG=warn\n").not());
+}
+
+#[test]
+fn test_set_rebuilds_artifacts() {
+    let config_src = r#"
+        [artifact.exec.build]
+        command = "echo building {mode}"
+        variables = { mode = "debug" }
+        if_files_changed = ["src"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context.workdir.child("src").touch().unwrap();
+
+    let build = |set: Option<&str>, expected: &str| {
+        let mut cmd = test_context.get_command();
+        if let Some(set) = set {
+            cmd.arg("--set").arg(set);
+        }
+        cmd.arg("build").arg("build");
+        cmd.assert().success().stdout(predicate::eq(expected));
+    };
+
+    build(None, "building debug\n");
+    build(None, "");
+    build(Some("build.mode=release"), "building release\n");
+    build(Some("build.mode=release"), "");
+    build(None, "building debug\n");
+}
+
+#[test]
+fn test_set_rebuilds_artifacts_sharing_a_global() {
+    let config_src = r#"
+        [globals]
+        registry = "r1"
+        img = "{globals.registry}/img"
+
+        [artifact.exec.a]
+        command = "echo a {globals.img}"
+        if_files_changed = ["src"]
+        tags = ["x"]
+
+        [artifact.exec.b]
+        command = "echo b {globals.img}"
+        if_files_changed = ["src"]
+        tags = ["x"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context.workdir.child("src").touch().unwrap();
+
+    let build = |set: Option<&str>, expected: &str| {
+        let mut cmd = test_context.get_command();
+        if let Some(set) = set {
+            cmd.arg("--set").arg(set);
+        }
+        cmd.arg("build").arg("--tag").arg("x");
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::starts_with(format!(
+                "{}Summary for tag <x>:",
+                expected
+            )));
+    };
+
+    build(None, "a r1/img\nb r1/img\n");
+    build(Some("globals.registry=r2"), "a r2/img\nb r2/img\n");
+    build(Some("globals.registry=r2"), "");
+}
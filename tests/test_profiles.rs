@@ -0,0 +1,137 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+const CONFIG: &str = r#"
+    [globals]
+    mode = "debug"
+
+    [env]
+    LOG_LEVEL = "info"
+
+    [command.exec.build]
+    command = "echo building {globals.mode} {flags}"
+    variables = { flags = "--quick", target = "x86" }
+    description = "Build it"
+
+    [command.exec.env]
+    command = "env"
+
+    [profile.ci.globals]
+    mode = "release"
+
+    [profile.ci.env]
+    LOG_LEVEL = "debug"
+
+    [profile.ci.command.exec.build]
+    variables = { flags = "--locked {target}" }
+    description = "Build it for CI"
+"#;
+
+#[test]
+fn test_without_profile() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("build");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("building debug --quick\n"));
+}
+
+#[test]
+fn test_profile() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("--profile").arg("ci").arg("run").arg("build");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("building release --locked x86\n"));
+}
+
+#[test]
+fn test_profile_from_env() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("env").env("PLS_PROFILE", "ci");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("LOG_LEVEL=debug\n"));
+}
+
+#[test]
+fn test_profile_in_list() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("--profile").arg("ci").arg("list");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "command.exec.build - Build it for CI\n",
+    ));
+}
+
+#[test]
+fn test_profile_not_found() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(CONFIG);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("--profile").arg("staging").arg("list");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Profile <staging> not found in any config file",
+    ));
+}
+
+#[test]
+fn test_profile_paths_in_included_file() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        include = ["sub/pls.toml"]
+        "#,
+    );
+    test_context
+        .workdir
+        .child("sub/pls.toml")
+        .write_str(
+            r#"
+            [command.exec.where]
+            command = "sh -c 'pwd; echo $MODE'"
+
+            [profile.ci.command.exec.where]
+            cwd = "tools"
+            env_file = ["ci.env"]
+            "#,
+        )
+        .unwrap();
+    test_context
+        .workdir
+        .child("sub/tools")
+        .create_dir_all()
+        .unwrap();
+    test_context
+        .workdir
+        .child("sub/ci.env")
+        .write_str("MODE=ci\n")
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("--profile").arg("ci").arg("run").arg("where");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("/sub/tools\nci\n"));
+}
@@ -0,0 +1,250 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+fn monorepo() -> common::TestContext {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        projects = ["services/*", "libs/*"]
+
+        [globals]
+        greeting = "hello"
+
+        [command.exec.test]
+        command = "echo root tests"
+        "#,
+    );
+    test_context
+        .workdir
+        .child("services/api/pls.toml")
+        .write_str(
+            r#"
+            [artifact.exec.build]
+            command = "touch built"
+            variables = { version = "1.2" }
+
+            [command.exec.test]
+            command = "echo {globals.greeting} api {build.version}"
+            requires = ["build"]
+            "#,
+        )
+        .unwrap();
+    test_context
+        .workdir
+        .child("services/web/pls.toml")
+        .write_str(
+            r#"
+            [command.exec.test]
+            command = "echo web uses {services/api:build.version}"
+            requires = ["services/api:test"]
+            "#,
+        )
+        .unwrap();
+    test_context
+}
+
+fn command_in(test_context: &common::TestContext, dir: &str) -> Command {
+    let mut cmd = Command::cargo_bin("pls").unwrap();
+    cmd.arg("--debug")
+        .arg("-C")
+        .arg(test_context.workdir().join(dir));
+    cmd
+}
+
+#[test]
+fn test_run_in_root_project() {
+    let test_context = monorepo();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("test");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("root tests").trim());
+}
+
+#[test]
+fn test_run_in_subproject() {
+    let test_context = monorepo();
+
+    let mut cmd = command_in(&test_context, "services/api");
+    cmd.arg("run").arg("test");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("hello api 1.2").trim());
+    test_context
+        .workdir
+        .child("services/api/built")
+        .assert(predicate::path::exists());
+}
+
+#[test]
+fn test_run_namespaced_target() {
+    let test_context = monorepo();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("services/web:test");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("hello api 1.2\nweb uses 1.2").trim());
+}
+
+#[test]
+fn test_list_shows_namespaced_targets() {
+    let test_context = monorepo();
+
+    let mut cmd = command_in(&test_context, "services/web");
+    cmd.arg("list");
+
+    cmd.assert().success().stdout(predicate::eq(
+        "command.exec.test - \nservices/api:artifact.exec.build - \nservices/api:command.exec.test - \nservices/web:command.exec.test - \n",
+    ));
+}
+
+#[test]
+fn test_project_with_dot_in_directory() {
+    let test_context = monorepo();
+    test_context
+        .workdir
+        .child("libs/v1.2/pls.toml")
+        .write_str(
+            r#"
+            [command.exec.test]
+            command = "echo lib {globals.greeting}"
+            variables = { version = "1.2.3" }
+            "#,
+        )
+        .unwrap();
+    test_context
+        .workdir
+        .child("services/web/pls.toml")
+        .write_str(
+            r#"
+            [command.exec.test]
+            command = "echo web uses {libs/v1.2:test.version}"
+            requires = ["libs/v1.2:test"]
+            "#,
+        )
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("services/web:test");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("lib hello\nweb uses 1.2.3").trim());
+}
+
+#[test]
+fn test_run_in_subdirectory_runs_from_config_root() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        [command.exec.where]
+        command = "pwd"
+        "#,
+    );
+    test_context.workdir.child("sub").create_dir_all().unwrap();
+
+    let mut cmd = command_in(&test_context, "sub");
+    cmd.arg("run").arg("where");
+
+    cmd.assert().success().stdout(predicate::eq(format!(
+        "{}\n",
+        test_context.workdir().canonicalize().unwrap().display()
+    )));
+}
+
+#[test]
+fn test_undeclared_config_is_not_a_project() {
+    let test_context = monorepo();
+    test_context
+        .workdir
+        .child("fixtures/broken/pls.toml")
+        .write_str("this isn't toml")
+        .unwrap();
+    test_context
+        .workdir
+        .child("tools/pls.toml")
+        .write_str(
+            r#"
+            [command.exec.test]
+            command = "echo tools on their own"
+            "#,
+        )
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("fixtures").not())
+        .stdout(predicate::str::contains("tools").not());
+
+    let mut cmd = command_in(&test_context, "tools");
+    cmd.arg("run").arg("test");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("tools on their own").trim());
+}
+
+#[test]
+fn test_error_for_declared_project_without_config() {
+    let test_context = common::TestContext::new();
+    test_context.write_config(
+        r#"
+        projects = ["services/api"]
+
+        [command.exec.test]
+        command = "true"
+        "#,
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("services/api> has no pls.toml"));
+}
+
+#[test]
+fn test_error_for_pod_members_with_the_same_name() {
+    let test_context = monorepo();
+    for project in ["services/api", "services/web"] {
+        test_context
+            .workdir
+            .child(format!("{}/pls.toml", project))
+            .write_str(
+                r#"
+                [command.container.db]
+                image = "postgres"
+                "#,
+            )
+            .unwrap();
+    }
+    test_context.write_config(
+        r#"
+        projects = ["services/*"]
+
+        [command.pod.stack]
+        members = ["services/api:db", "services/web:db"]
+        "#,
+    );
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Members <services/api:command.container.db> and <services/web:command.container.db> of pod <command.pod.stack> would both have the alias <db> on its network",
+    ));
+}
@@ -0,0 +1,85 @@
+use assert_cmd::prelude::*;
+
+mod common;
+
+fn schema() -> serde_json::Value {
+    // The config isn't loaded to get the schema, so it works even when it is broken
+    let test_context = common::TestContext::new();
+    test_context.write_config("not a config");
+    let mut cmd = test_context.get_command();
+    cmd.arg("schema");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    serde_json::from_slice(&output).unwrap()
+}
+
+#[test]
+fn test_schema_covers_targets() {
+    let schema = schema();
+    let definitions = &schema["definitions"];
+    for (definition, fields) in [
+        (
+            "ExecCommand",
+            ["command", "requires", "env", "daemon", "args"],
+        ),
+        (
+            "ContainerCommand",
+            ["image", "create_network", "extends", "pass_env", "daemon"],
+        ),
+        (
+            "Pod",
+            ["members", "requires", "variables", "tags", "daemon"],
+        ),
+        (
+            "ExecArtifact",
+            [
+                "command",
+                "if_files_changed",
+                "updates_paths",
+                "env_file",
+                "description",
+            ],
+        ),
+        (
+            "ContainerBuild",
+            ["tag", "pull", "if_files_changed", "requires", "build_args"],
+        ),
+    ] {
+        for field in fields {
+            assert!(
+                definitions[definition]["properties"][field].is_object(),
+                "{} is missing {}",
+                definition,
+                field
+            );
+        }
+    }
+    assert_eq!(
+        definitions["PullPolicy"]["enum"],
+        serde_json::json!(["always", "missing", "never", "newer"])
+    );
+    assert_eq!(
+        definitions["ExecCommand"]["properties"]["daemon"]["type"],
+        serde_json::json!(["boolean", "null"])
+    );
+}
+
+#[test]
+fn test_schema_describes_every_field() {
+    let schema = schema();
+    let mut undescribed = vec![];
+    let mut objects = vec![("Config".to_string(), &schema)];
+    for (name, definition) in schema["definitions"].as_object().unwrap() {
+        objects.push((name.clone(), definition));
+        for variant in definition["anyOf"].as_array().into_iter().flatten() {
+            objects.push((name.clone(), variant));
+        }
+    }
+    for (name, object) in objects {
+        for (field, property) in object["properties"].as_object().into_iter().flatten() {
+            if !property["description"].is_string() {
+                undescribed.push(format!("{}.{}", name, field));
+            }
+        }
+    }
+    assert_eq!(undescribed, Vec::<String>::new());
+}
@@ -0,0 +1,165 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+#[test]
+fn test_show() {
+    let config_src = r#"
+        [command.exec.base]
+        command = "echo {args}"
+        env = ["A=1"]
+        variables = { greeting = "hello" }
+
+        [command.exec.greet]
+        extends = "base"
+        env = ["B=2"]
+        requires = ["build"]
+        description = "Say hello"
+
+        [artifact.exec.build]
+        command = "make"
+        requires = ["generate"]
+
+        [artifact.exec.generate]
+        command = "generate"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("show").arg("greet").arg("--").arg("world");
+
+    cmd.assert().success().stdout(predicate::eq(
+        r#"name = "command.exec.greet"
+extends = ["command.exec.base"]
+requires = ["artifact.exec.generate", "artifact.exec.build"]
+command_line = "echo world"
+
+[variables]
+greeting = "hello"
+
+[definition]
+clear_env = false
+command = "echo {args}"
+daemon = false
+description = "Say hello"
+env = ["A=1", "B=2"]
+env_file = []
+pass_env = []
+requires = ["artifact.exec.build"]
+tags = []
+
+[sources]
+clear_env = ["default"]
+command = ["command.exec.base"]
+daemon = ["default"]
+description = ["command.exec.greet"]
+env = ["command.exec.greet", "command.exec.base"]
+env_file = ["default"]
+pass_env = ["default"]
+requires = ["command.exec.greet"]
+tags = ["default"]
+variables = ["command.exec.base"]
+"#,
+    ));
+}
+
+#[test]
+fn test_show_json() {
+    let config_src = r#"
+        [command.exec.hello]
+        command = "echo hello"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("show").arg("--format").arg("json").arg("hello");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "{\n  \"name\": \"command.exec.hello\",\n  \"extends\": [],\n",
+        ))
+        .stdout(predicate::str::contains("\"command\": \"echo hello\""))
+        .stdout(predicate::str::contains("\"command_line\"").not());
+}
+
+#[test]
+fn test_show_args_for_artifact() {
+    let config_src = r#"
+        [artifact.exec.build]
+        command = "make"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("show").arg("build").arg("--").arg("all");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Target <artifact.exec.build> does not accept arguments",
+    ));
+}
+
+#[test]
+fn test_show_expands_variables() {
+    let config_src = r#"
+        [globals]
+        name = "world"
+
+        [command.exec.greet]
+        command = "echo {message}"
+        variables = { greeting = "hello", message = "{greeting} {globals.name}", loop = "{loop}" }
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("show").arg("greet");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        r#"[variables]
+greeting = "hello"
+message = "hello world"
+
+[variables.loop]
+value = "{loop}"
+error = "Variables refer to each other in a cycle <command.exec.greet.loop -> command.exec.greet.loop>"
+"#,
+    ));
+}
+
+#[test]
+fn test_show_does_not_run_variable_commands() {
+    let config_src = r#"
+        [command.exec.greet]
+        command = "echo {message}"
+        variables = { name = { command = "touch ran" }, message = "hello {name}" }
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("show").arg("greet");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        r#"[variables.message]
+value = "hello {name}"
+error = "Variable <command.exec.greet.name> is the output of <touch ran>, which is only run when the target is"
+
+[variables.name]
+error = "Variable <command.exec.greet.name> is the output of <touch ran>, which is only run when the target is"
+
+[variables.name.value]
+command = "touch ran"
+"#,
+    ));
+    assert!(!test_context.workdir().join("ran").exists());
+}
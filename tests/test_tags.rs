@@ -0,0 +1,226 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+mod common;
+
+#[test]
+fn test_list_with_tag() {
+    let config_src = r#"
+        [command.exec.lint]
+        command = "true"
+        tags = ["ci"]
+
+        [command.exec.serve]
+        command = "true"
+
+        [command.exec.strict_lint]
+        extends = "lint"
+        tags = ["strict"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("list").arg("--tag").arg("ci");
+
+    cmd.assert().success().stdout(predicate::eq(
+        "command.exec.lint - \ncommand.exec.strict_lint - \n",
+    ));
+}
+
+#[test]
+fn test_run_with_tag_in_dependency_order() {
+    let config_src = r#"
+        [command.exec.a]
+        command = "sh -c 'echo a >> order'"
+        requires = ["b"]
+        tags = ["ci"]
+
+        [command.exec.b]
+        command = "sh -c 'echo b >> order'"
+        tags = ["ci"]
+
+        [command.exec.c]
+        command = "sh -c 'echo c >> order'"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("--tag").arg("ci");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Summary for tag <ci>:\n  command.exec.b: ok\n  command.exec.a: ok\n",
+    ));
+
+    test_context
+        .workdir
+        .child("order")
+        .assert(predicate::eq("b\na\n"));
+    test_context
+        .workdir
+        .child("order")
+        .assert(predicate::str::contains("c").not());
+}
+
+#[test]
+fn test_run_with_tag_skips_dependants_of_failures() {
+    let config_src = r#"
+        [command.exec.broken]
+        command = "false"
+        tags = ["ci"]
+
+        [command.exec.after]
+        command = "true"
+        requires = ["broken"]
+        tags = ["ci"]
+
+        [command.exec.other]
+        command = "true"
+        tags = ["ci"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("--tag").arg("ci");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("command.exec.broken: failed"))
+        .stdout(predicate::str::contains(
+            "command.exec.after: skipped as <command.exec.broken> failed",
+        ))
+        .stdout(predicate::str::contains("command.exec.other: ok"))
+        .stderr(predicate::str::contains(
+            "2 of 3 targets with tag <ci> did not succeed",
+        ));
+}
+
+#[test]
+fn test_build_with_tag() {
+    let config_src = r#"
+        [artifact.exec.one]
+        command = "touch one"
+        updates_paths = ["one"]
+        tags = ["ci"]
+
+        [artifact.exec.two]
+        command = "touch two"
+        updates_paths = ["two"]
+
+        [command.exec.three]
+        command = "touch three"
+        tags = ["ci"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("build").arg("--tag").arg("ci");
+
+    cmd.assert().success();
+
+    test_context
+        .workdir
+        .child("one")
+        .assert(predicate::path::exists());
+    test_context
+        .workdir
+        .child("two")
+        .assert(predicate::path::missing());
+    test_context
+        .workdir
+        .child("three")
+        .assert(predicate::path::missing());
+}
+
+#[test]
+fn test_error_when_no_targets_have_tag() {
+    let config_src = r#"
+        [command.exec.lint]
+        command = "true"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("--tag").arg("ci");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "No targets with tag <ci> found in config file <",
+    ));
+}
+
+#[test]
+fn test_run_with_tag_runs_shared_requirements_once() {
+    let config_src = r#"
+        [command.exec.setup]
+        command = "sh -c 'echo setup >> order'"
+
+        [command.exec.lint]
+        command = "sh -c 'echo lint >> order'"
+        requires = ["setup"]
+        tags = ["ci"]
+
+        [command.exec.test]
+        command = "sh -c 'echo test >> order'"
+        requires = ["setup", "lint"]
+        tags = ["ci"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("--tag").arg("ci");
+
+    cmd.assert().success();
+
+    test_context
+        .workdir
+        .child("order")
+        .assert(predicate::eq("setup\nlint\ntest\n"));
+}
+
+#[test]
+fn test_run_with_tag_runs_failed_requirement_once_and_skips_dependants() {
+    let config_src = r#"
+        [command.exec.broken]
+        command = "sh -c 'echo broken >> order; false'"
+
+        [command.exec.first]
+        command = "sh -c 'echo first >> order'"
+        requires = ["broken"]
+        tags = ["ci"]
+
+        [command.exec.second]
+        command = "sh -c 'echo second >> order'"
+        requires = ["broken"]
+        tags = ["ci"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("--tag").arg("ci");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("command.exec.first: failed"))
+        .stdout(predicate::str::contains(
+            "command.exec.second: skipped as <command.exec.broken> failed",
+        ));
+
+    test_context
+        .workdir
+        .child("order")
+        .assert(predicate::eq("broken\n"));
+}
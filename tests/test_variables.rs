@@ -1,4 +1,5 @@
 use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
 use predicates::prelude::*;
 
 mod common;
@@ -42,3 +43,231 @@ fn test_globals() {
         .success()
         .stdout(predicate::eq("hello world\n"));
 }
+
+#[test]
+fn test_escapes_defaults_and_filters() {
+    let config_src = r#"
+        [command.exec.hello]
+        command = "echo {{\"place\": \"{place|upper}\"}} {port|default:8080} {file|basename}"
+        variables = { place = "world", file = "src/main.rs" }
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("hello");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("{place: WORLD} 8080 main.rs\n"));
+}
+
+#[test]
+fn test_template_error() {
+    let config_src = r#"
+        [command.exec.hello]
+        command = "echo {place"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("hello");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Unclosed <{>, use <{{> for a literal brace at column 6 of <echo {place>",
+    ));
+}
+
+#[test]
+fn test_recursive_variables() {
+    let config_src = r#"
+        [globals]
+        profile = "release"
+
+        [artifact.exec.build]
+        command = "echo building {out}"
+        variables = { out = "{dir}/bin", dir = "target/{globals.profile}" }
+
+        [command.exec.run]
+        command = "echo running {build.out}"
+        requires = ["build"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("run");
+
+    cmd.assert().success().stdout(predicate::eq(
+        "building target/release/bin\nrunning target/release/bin\n",
+    ));
+}
+
+#[test]
+fn test_variable_cycle() {
+    let config_src = r#"
+        [command.exec.hello]
+        command = "echo {a}"
+        variables = { a = "{b}", b = "{a}" }
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("hello");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Variables refer to each other in a cycle <command.exec.hello.a -> command.exec.hello.b -> command.exec.hello.a>",
+    ));
+}
+
+#[test]
+fn test_command_variables() {
+    let config_src = r#"
+        [globals]
+        greeting = { command = "echo hello" }
+
+        [command.exec.hello]
+        command = "echo {globals.greeting} {place}"
+        variables = { place = { command = "echo world" } }
+
+        [command.exec.unused]
+        command = "echo {broken}"
+        variables = { broken = { command = "false" } }
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("hello");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq("hello world\n"));
+}
+
+#[test]
+fn test_command_variable_failure() {
+    let config_src = r#"
+        [command.exec.hello]
+        command = "echo {version}"
+        variables = { version = { command = "false" } }
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("hello");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Error running <false> for variable <command.exec.hello.version>",
+    ));
+}
+
+#[test]
+fn test_command_variable_change_rebuilds() {
+    let config_src = r#"
+        [artifact.exec.build]
+        command = "echo building {version}"
+        variables = { version = { command = "cat version" } }
+        if_files_changed = ["src"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context.workdir.child("src").touch().unwrap();
+    test_context
+        .workdir
+        .child("version")
+        .write_str("1")
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("build").arg("build");
+    cmd.assert().success().stdout(predicate::eq("building 1\n"));
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("build").arg("build");
+    cmd.assert().success().stdout(predicate::eq(""));
+
+    test_context
+        .workdir
+        .child("version")
+        .write_str("2")
+        .unwrap();
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("build").arg("build");
+    cmd.assert().success().stdout(predicate::eq("building 2\n"));
+}
+
+#[test]
+fn test_command_variable_change_rebuilds_targets_sharing_it() {
+    let config_src = r#"
+        [globals]
+        version = { command = "cat version" }
+        image = "img:{globals.version}"
+
+        [artifact.exec.a]
+        command = "echo a {globals.image}"
+        if_files_changed = ["src"]
+        tags = ["all"]
+
+        [artifact.exec.b]
+        command = "echo b {globals.image}"
+        if_files_changed = ["src"]
+        tags = ["all"]
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+    test_context.workdir.child("src").touch().unwrap();
+    test_context
+        .workdir
+        .child("version")
+        .write_str("v1")
+        .unwrap();
+
+    let build = |expected: &str| {
+        let mut cmd = test_context.get_command();
+        cmd.arg("build").arg("--tag").arg("all");
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::starts_with(format!(
+                "{}Summary for tag <all>:",
+                expected
+            )));
+    };
+
+    build("a img:v1\nb img:v1\n");
+    build("");
+    test_context
+        .workdir
+        .child("version")
+        .write_str("v2")
+        .unwrap();
+    build("a img:v2\nb img:v2\n");
+}
+
+#[test]
+fn test_default_for_reference_to_missing_target() {
+    let config_src = r#"
+        [command.exec.hello]
+        command = "echo {missing.place|default:world}"
+    "#;
+
+    let test_context = common::TestContext::new();
+    test_context.write_config(config_src);
+
+    let mut cmd = test_context.get_command();
+    cmd.arg("run").arg("hello");
+
+    cmd.assert().success().stdout(predicate::eq("world\n"));
+}